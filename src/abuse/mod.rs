@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use rbatis::RBatis;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::system_program;
+use solana_sdk::transaction::VersionedTransaction;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::AbuseCandidate;
+use crate::time::now_secs;
+
+pub const ABUSE_FLAG_OPEN: &str = "open";
+pub const ABUSE_FLAG_CLEARED: &str = "cleared";
+pub const ABUSE_FLAG_CONFIRMED: &str = "confirmed";
+
+pub const REASON_SELF_REFERRAL: &str = "self_referral";
+pub const REASON_SHARED_FUNDER: &str = "shared_funder";
+pub const REASON_IP_BURST: &str = "ip_burst";
+
+// Wallet that sent lamports to address in a top level system transfer or account creation.
+pub fn find_funder(tx: &VersionedTransaction, address: &Pubkey) -> Option<Pubkey> {
+    let keys = tx.message.static_account_keys();
+    for ins in tx.message.instructions() {
+        if keys.get(ins.program_id_index as usize) != Some(&system_program::ID) {
+            continue;
+        }
+        let (Some(from), Some(to)) = (
+            ins.accounts.first().and_then(|i| keys.get(*i as usize)),
+            ins.accounts.get(1).and_then(|i| keys.get(*i as usize)),
+        ) else {
+            continue;
+        };
+        let Ok(system_ins) = bincode::deserialize::<SystemInstruction>(&ins.data) else {
+            continue;
+        };
+        let funds = matches!(system_ins, SystemInstruction::Transfer { .. } | SystemInstruction::CreateAccount { .. });
+        if funds && to == address && from != address {
+            return Some(*from);
+        }
+    }
+    None
+}
+
+// Wallet that paid for a launch of minter, either by sending it lamports in the launch transaction or by paying
+// the fee of the transaction, None when the minter paid for itself.
+pub fn launch_funder(tx: &VersionedTransaction, minter: &Pubkey) -> Option<Pubkey> {
+    find_funder(tx, minter).or_else(|| {
+        tx.message.static_account_keys().first().filter(|payer| *payer != minter).copied()
+    })
+}
+
+// Reasons and details per flagged address, an address picked up by several rules is flagged once.
+pub fn merge_candidates(found: Vec<(&str, Vec<AbuseCandidate>)>) -> BTreeMap<String, (Vec<String>, Vec<String>)> {
+    let mut merged: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+    for (reason, candidates) in found {
+        for candidate in candidates {
+            let entry = merged.entry(candidate.address).or_default();
+            if !entry.0.iter().any(|r| r == reason) {
+                entry.0.push(reason.to_string());
+            }
+            entry.1.push(candidate.detail);
+        }
+    }
+    merged
+}
+
+// Runs every rule and opens flags for accounts that have none, returns the number of new flags.
+// Self referral is looked for as many levels up as the rebate policy pays.
+pub async fn flag_abuse(rb: &mut RBatis, config: &Config, depth: usize) -> anyhow::Result<usize> {
+    let found = vec![
+        (REASON_SELF_REFERRAL, db::get_self_referral_candidates(rb, depth, &config.abuse_funder_allowlist).await?),
+        (REASON_SHARED_FUNDER, db::get_shared_funder_candidates(rb, config.abuse_funder_threshold,
+                                                                &config.abuse_funder_allowlist).await?),
+        (REASON_IP_BURST, db::get_ip_burst_candidates(rb, config.abuse_ip_threshold, config.abuse_ip_window).await?),
+    ];
+    let mut flagged = 0;
+    for (address, (reasons, details)) in merge_candidates(found) {
+        if db::flag_account(rb, &address, &reasons.join(","), &details.join("; "), now_secs()).await? {
+            log::warn!("account {address} flagged for {}", reasons.join(","));
+            flagged += 1;
+        }
+    }
+    Ok(flagged)
+}
+
+// "clear" releases the held points and rebates, "confirm" forfeits them.
+pub async fn review_flag(rb: &mut RBatis, address: &str, action: &str) -> anyhow::Result<bool> {
+    let status = match action {
+        "clear" => ABUSE_FLAG_CLEARED,
+        "confirm" => ABUSE_FLAG_CONFIRMED,
+        _ => anyhow::bail!("Action must be clear or confirm"),
+    };
+    Ok(db::review_abuse_flag(rb, address, status, now_secs()).await? > 0)
+}
+
+#[cfg(test)]
+mod test {
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::Transaction;
+    use super::*;
+
+    #[test]
+    fn test_find_funder() {
+        let (funder, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let tx = Transaction::new_with_payer(&[system_instruction::transfer(&funder, &wallet, 1000)], Some(&funder));
+        let tx = VersionedTransaction::from(tx);
+        assert_eq!(find_funder(&tx, &wallet), Some(funder));
+        assert_eq!(find_funder(&tx, &Pubkey::new_unique()), None);
+        assert_eq!(launch_funder(&tx, &wallet), Some(funder));
+        // the fee payer of a launch it does not fund itself
+        let minter = Pubkey::new_unique();
+        assert_eq!(launch_funder(&tx, &minter), Some(funder));
+        assert_eq!(launch_funder(&tx, &funder), None);
+    }
+
+    #[test]
+    fn test_merge_candidates() {
+        let candidate = |address: &str, detail: &str| AbuseCandidate {
+            address: address.to_string(),
+            detail: detail.to_string(),
+        };
+        let merged = merge_candidates(vec![
+            (REASON_SELF_REFERRAL, vec![candidate("a", "funded by inviter b")]),
+            (REASON_SHARED_FUNDER, vec![candidate("a", "funder c funded 5 invitees"), candidate("d", "funder c funded 5 invitees")]),
+        ]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged["a"].0, vec![REASON_SELF_REFERRAL, REASON_SHARED_FUNDER]);
+        assert_eq!(merged["a"].1.len(), 2);
+        assert_eq!(merged["d"].0, vec![REASON_SHARED_FUNDER]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use solana_sdk::keccak;
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EvmAddress([u8; 20]);
+
+impl EvmAddress {
+    pub fn to_bytes(&self) -> [u8; 20] {
+        self.0
+    }
+
+    // The form stored in the db: lowercase hex without 0x.
+    pub fn to_db_string(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    // EIP-55: a hex letter is uppercase when the matching nibble of keccak(lowercase hex) is >= 8.
+    pub fn to_checksum_string(&self) -> String {
+        let lower = self.to_db_string();
+        let hash = keccak::hash(lower.as_bytes()).0;
+        let checksummed: String = lower.chars().enumerate().map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if c.is_ascii_alphabetic() && nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        }).collect();
+        format!("0x{checksummed}")
+    }
+}
+
+// All lowercase or all uppercase input carries no checksum, mixed case has to match EIP-55.
+impl FromStr for EvmAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let hex_part = s.strip_prefix("0x").or(s.strip_prefix("0X")).unwrap_or(s);
+        if hex_part.len() != 40 || !hex_part.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("evm address must be 20 bytes of hex: {s}");
+        }
+        let bytes: [u8; 20] = hex::decode(hex_part)?.try_into()
+            .map_err(|_| anyhow::anyhow!("evm address must be 20 bytes of hex: {s}"))?;
+        let address = EvmAddress(bytes);
+        let is_mixed_case = hex_part.chars().any(|c| c.is_ascii_lowercase())
+            && hex_part.chars().any(|c| c.is_ascii_uppercase());
+        if is_mixed_case && address.to_checksum_string()[2..] != *hex_part {
+            anyhow::bail!("evm address checksum mismatch: {s}");
+        }
+        Ok(address)
+    }
+}
+
+impl Display for EvmAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_checksum_string())
+    }
+}
+
+// Addresses users pass around: a solana wallet or an evm wallet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountAddress {
+    Solana(Pubkey),
+    Evm(EvmAddress),
+}
+
+impl AccountAddress {
+    pub fn to_db_string(&self) -> String {
+        match self {
+            AccountAddress::Solana(pubkey) => pubkey.to_string(),
+            AccountAddress::Evm(address) => address.to_db_string(),
+        }
+    }
+}
+
+impl FromStr for AccountAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(pubkey) = Pubkey::from_str(s.trim()) {
+            return Ok(AccountAddress::Solana(pubkey));
+        }
+        Ok(AccountAddress::Evm(EvmAddress::from_str(s)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_eip55_checksum() {
+        for checksummed in ["0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+                            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+                            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+                            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb"] {
+            let address = EvmAddress::from_str(checksummed).unwrap();
+            assert_eq!(address.to_checksum_string(), checksummed);
+            assert_eq!(EvmAddress::from_str(&checksummed.to_lowercase()).unwrap(), address);
+            assert_eq!(EvmAddress::from_str(&address.to_db_string()).unwrap(), address);
+        }
+        assert!(EvmAddress::from_str("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+        assert!(EvmAddress::from_str("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beae").is_err());
+        assert!(EvmAddress::from_str("0xzaaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::Allocation;
+use crate::refund;
+use crate::time::now_secs;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PricingRule {
+    Fixed { price: BigDecimal },
+    ProRata { price: BigDecimal },
+    // (raised amount threshold, price) sorted by threshold, the first threshold is 0
+    Tiered { tiers: Vec<(BigDecimal, BigDecimal)> },
+}
+
+impl PricingRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PricingRule::Fixed { .. } => "fixed",
+            PricingRule::ProRata { .. } => "pro_rata",
+            PricingRule::Tiered { .. } => "tiered",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AllocationParams {
+    pub supply: BigDecimal,
+    pub rule: PricingRule,
+    pub token_decimal: u32,
+}
+
+fn parse_positive(value: &str, name: &str) -> anyhow::Result<BigDecimal> {
+    let v = BigDecimal::from_str(value.trim())
+        .map_err(|e| anyhow::anyhow!("invalid {name} {value}: {e}"))?;
+    if v <= BigDecimal::zero() {
+        anyhow::bail!("{name} must be positive");
+    }
+    Ok(v)
+}
+
+fn parse_tiers(tiers: &str) -> anyhow::Result<Vec<(BigDecimal, BigDecimal)>> {
+    let mut ret = vec![];
+    for tier in tiers.split(',').filter(|t| !t.trim().is_empty()) {
+        let Some((threshold, price)) = tier.split_once(':') else {
+            anyhow::bail!("invalid pricing tier {tier}");
+        };
+        let threshold = BigDecimal::from_str(threshold.trim())
+            .map_err(|e| anyhow::anyhow!("invalid pricing tier {tier}: {e}"))?;
+        ret.push((threshold, parse_positive(price, "tier price")?));
+    }
+    ret.sort_by(|a, b| a.0.cmp(&b.0));
+    if ret.first().map(|t| !t.0.is_zero()).unwrap_or(true) {
+        anyhow::bail!("pricing tiers must start at 0");
+    }
+    Ok(ret)
+}
+
+impl AllocationParams {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let supply = parse_positive(&config.token_supply_for_sale, "token supply for sale")?;
+        let rule = match config.pricing_rule.as_str() {
+            "fixed" => PricingRule::Fixed { price: parse_positive(&config.token_price, "token price")? },
+            "pro_rata" => PricingRule::ProRata { price: parse_positive(&config.token_price, "token price")? },
+            "tiered" => PricingRule::Tiered { tiers: parse_tiers(&config.pricing_tiers)? },
+            rule => anyhow::bail!("unknown pricing rule {rule}"),
+        };
+        let params = Self {
+            supply,
+            rule,
+            token_decimal: config.token_decimal,
+        };
+        params.check_cap(config.launch_max_amount)?;
+        Ok(params)
+    }
+
+    // Fixed and tiered prices give contributions past the supply no tokens, and nothing refunds that part,
+    // so the launch cap must not buy more than the supply. Pro rata scales everyone down instead.
+    pub fn check_cap(&self, launch_max_amount: u64) -> anyhow::Result<()> {
+        let cap = BigDecimal::from(launch_max_amount);
+        let tokens = match &self.rule {
+            PricingRule::Fixed { price } => cap / price.clone(),
+            PricingRule::Tiered { tiers } => tiered_tokens(tiers, &BigDecimal::zero(), &cap),
+            PricingRule::ProRata { .. } => return Ok(()),
+        };
+        if launch_max_amount == 0 || tokens > self.supply {
+            anyhow::bail!("{} pricing needs a launch max amount that the token supply for sale covers", self.rule.name());
+        }
+        Ok(())
+    }
+}
+
+// Tokens bought by `amount` when `raised` has already been contributed, walking up the price tiers.
+fn tiered_tokens(tiers: &[(BigDecimal, BigDecimal)], raised: &BigDecimal, amount: &BigDecimal) -> BigDecimal {
+    let mut raised = raised.clone();
+    let mut remaining = amount.clone();
+    let mut tokens = BigDecimal::zero();
+    while remaining > BigDecimal::zero() {
+        let idx = tiers.iter().rposition(|(threshold, _)| *threshold <= raised).unwrap_or(0);
+        let price = &tiers[idx].1;
+        let portion = match tiers.get(idx + 1) {
+            Some((next, _)) if next.clone() - raised.clone() < remaining => next.clone() - raised.clone(),
+            _ => remaining.clone(),
+        };
+        tokens += portion.clone() / price.clone();
+        raised += portion.clone();
+        remaining -= portion;
+    }
+    tokens
+}
+
+// Contributions are (address, amount) in chain order, returns the token amount of each contribution.
+pub fn compute_token_amounts(params: &AllocationParams, contributions: &[(String, BigDecimal)]) -> Vec<BigDecimal> {
+    match &params.rule {
+        PricingRule::Fixed { price } => {
+            let mut remaining = params.supply.clone();
+            contributions.iter().map(|(_, amount)| {
+                let tokens = std::cmp::min(amount.clone() / price.clone(), remaining.clone());
+                remaining -= tokens.clone();
+                tokens
+            }).collect()
+        }
+        PricingRule::ProRata { price } => {
+            let total = contributions.iter().fold(BigDecimal::zero(), |acc, (_, a)| acc + a.clone());
+            let oversubscribed = total.clone() / price.clone() > params.supply;
+            contributions.iter().map(|(_, amount)| {
+                if oversubscribed {
+                    params.supply.clone() * amount.clone() / total.clone()
+                } else {
+                    amount.clone() / price.clone()
+                }
+            }).collect()
+        }
+        PricingRule::Tiered { tiers } => {
+            let mut raised = BigDecimal::zero();
+            let mut remaining = params.supply.clone();
+            contributions.iter().map(|(_, amount)| {
+                let tokens = std::cmp::min(tiered_tokens(tiers, &raised, amount), remaining.clone());
+                raised += amount.clone();
+                remaining -= tokens.clone();
+                tokens
+            }).collect()
+        }
+    }
+}
+
+pub fn to_base_units(tokens: &BigDecimal, token_decimal: u32) -> BigDecimal {
+    let unit = BigDecimal::from_str(&format!("1e{token_decimal}")).unwrap();
+    (tokens.clone() * unit).with_scale(0)
+}
+
+pub fn compute_allocations(params: &AllocationParams, contributions: &[(String, BigDecimal)], update_time: i64) -> Vec<Allocation> {
+    let token_amounts = compute_token_amounts(params, contributions);
+    let mut per_address: BTreeMap<String, (BigDecimal, BigDecimal)> = BTreeMap::new();
+    for ((address, amount), tokens) in contributions.iter().zip(token_amounts) {
+        let entry = per_address.entry(address.clone()).or_insert((BigDecimal::zero(), BigDecimal::zero()));
+        entry.0 += amount.clone();
+        entry.1 += tokens;
+    }
+    per_address.into_iter().map(|(address, (contributed, tokens))| Allocation {
+        address,
+        contributed_amount: Decimal::from_str(&contributed.to_string()).unwrap(),
+        token_amount: Decimal::from_str(&to_base_units(&tokens, params.token_decimal).to_string()).unwrap(),
+        pricing_rule: params.rule.name().to_string(),
+        update_time,
+    }).collect()
+}
+
+// Contributions in chain order, without the part refunded for going over the launch cap.
+async fn get_effective_contributions(rb: &RBatis, config: &Config) -> anyhow::Result<Vec<(String, BigDecimal)>> {
+    let records = db::get_all_launch_records(rb).await?;
+    let refunds: HashMap<(String, i32), BigDecimal> = if config.launch_max_amount > 0 {
+        refund::compute_refund_ledger(&records, config.launch_max_amount, 0)?
+            .into_iter()
+            .map(|r| ((r.launch_tx_hash, r.log_index), BigDecimal::from_str(&r.refund_amount.to_string()).unwrap_or_default()))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    let contributions = records.into_iter().filter_map(|r| {
+        let amount = BigDecimal::from_str(&r.launch_amount.to_string()).unwrap_or_default();
+        let refunded = refunds.get(&(r.launch_tx_hash.clone(), r.log_index)).cloned().unwrap_or_default();
+        let effective = amount - refunded;
+        (effective > BigDecimal::zero()).then_some((r.address, effective))
+    }).collect();
+    Ok(contributions)
+}
+
+pub async fn sync_allocations(rb: &mut RBatis, config: &Config) -> anyhow::Result<usize> {
+    let params = AllocationParams::from_config(config)?;
+    let contributions = get_effective_contributions(rb, config).await?;
+    let now = now_secs();
+    let allocations = compute_allocations(&params, &contributions, now);
+    db::replace_allocations(rb, &allocations).await?;
+    Ok(allocations.len())
+}
+
+pub async fn export_allocations(rb: &RBatis, path: &str) -> anyhow::Result<usize> {
+    let allocations = db::get_all_allocations(rb).await?;
+    let mut csv = "address,contributed_amount,token_amount,pricing_rule\n".to_string();
+    for allocation in &allocations {
+        csv += &format!("{},{},{},{}\n", allocation.address, allocation.contributed_amount,
+                        allocation.token_amount, allocation.pricing_rule);
+    }
+    std::fs::write(path, csv)?;
+    Ok(allocations.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn params(rule: PricingRule) -> AllocationParams {
+        AllocationParams {
+            supply: BigDecimal::from(1000),
+            rule,
+            token_decimal: 2,
+        }
+    }
+
+    fn contributions() -> Vec<(String, BigDecimal)> {
+        vec![("a".to_string(), BigDecimal::from(6)), ("b".to_string(), BigDecimal::from(3)),
+             ("a".to_string(), BigDecimal::from(3))]
+    }
+
+    #[test]
+    fn test_fixed_price_stops_at_supply() {
+        let rule = PricingRule::Fixed { price: BigDecimal::from_str("0.01").unwrap() };
+        let tokens = compute_token_amounts(&params(rule), &contributions());
+        assert_eq!(tokens, vec![BigDecimal::from(600), BigDecimal::from(300), BigDecimal::from(100)]);
+    }
+
+    #[test]
+    fn test_pro_rata_on_oversubscription() {
+        let rule = PricingRule::ProRata { price: BigDecimal::from_str("0.01").unwrap() };
+        let allocations = compute_allocations(&params(rule), &contributions(), 0);
+        assert_eq!(allocations[0].address, "a");
+        assert_eq!(allocations[0].token_amount.to_string(), "75000");
+        assert_eq!(allocations[1].token_amount.to_string(), "25000");
+    }
+
+    #[test]
+    fn test_tiered_price_splits_across_tiers() {
+        let rule = PricingRule::Tiered {
+            tiers: parse_tiers("0:0.01,5:0.02").unwrap(),
+        };
+        let tokens = compute_token_amounts(&params(rule), &contributions());
+        // 5 at 0.01 + 1 at 0.02, the later contributions are all priced at 0.02
+        assert_eq!(tokens, vec![BigDecimal::from(550), BigDecimal::from(150), BigDecimal::from(150)]);
+    }
+
+    #[test]
+    fn test_launch_cap_within_supply() {
+        let fixed = params(PricingRule::Fixed { price: BigDecimal::from_str("0.01").unwrap() });
+        assert!(fixed.check_cap(10).is_ok());
+        assert!(fixed.check_cap(11).is_err());
+        // without a cap nothing bounds the contributions
+        assert!(fixed.check_cap(0).is_err());
+        let tiered = params(PricingRule::Tiered { tiers: parse_tiers("0:0.01,5:0.02").unwrap() });
+        // 500 tokens for the first 5, then 50 per unit
+        assert!(tiered.check_cap(15).is_ok());
+        assert!(tiered.check_cap(16).is_err());
+        let pro_rata = params(PricingRule::ProRata { price: BigDecimal::from_str("0.01").unwrap() });
+        assert!(pro_rata.check_cap(0).is_ok());
+    }
+}
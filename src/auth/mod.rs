@@ -0,0 +1,210 @@
+use std::str::FromStr;
+use chrono::{DateTime, SecondsFormat};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use solana_sdk::keccak;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::secp256k1_recover::secp256k1_recover;
+use solana_sdk::signature::Signature;
+use crate::address::EvmAddress;
+use crate::config::Config;
+
+pub fn generate_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn bind_challenge_message(domain: &str, evm_address: &str, sol_address: &str,
+                              nonce: &str, expire_time: i64) -> String {
+    format!("{domain} wants you to bind your wallets.\n\
+             EVM address: {}\n\
+             Solana address: {sol_address}\n\
+             Nonce: {nonce}\n\
+             Expiration Time: {expire_time}", checksum_address(evm_address))
+}
+
+// Signed messages show evm addresses in the EIP-55 form wallets display.
+fn checksum_address(evm_address: &str) -> String {
+    EvmAddress::from_str(evm_address)
+        .map(|a| a.to_checksum_string())
+        .unwrap_or(format!("0x{evm_address}"))
+}
+
+// Where the sign-in messages say the user is signing in, wallets compare the domain with the page origin.
+#[derive(Clone, Debug)]
+pub struct SignInOrigin {
+    pub domain: String,
+    pub uri: String,
+    pub evm_chain_id: u64,
+    // SIWS chain id, e.g. mainnet or devnet
+    pub solana_chain_id: String,
+}
+
+impl SignInOrigin {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            domain: config.auth_domain.clone(),
+            uri: config.auth_uri.clone(),
+            evm_chain_id: config.auth_evm_chain_id,
+            solana_chain_id: config.auth_solana_chain_id.clone(),
+        }
+    }
+}
+
+fn rfc3339(secs: i64) -> String {
+    DateTime::from_timestamp(secs, 0)
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+// EIP-4361 sign-in message, or its Sign In With Solana equivalent for solana addresses.
+// The address is shown the way the wallet displays it.
+pub fn login_message(origin: &SignInOrigin, address: &str, nonce: &str, issued_at: i64, expire_time: i64) -> String {
+    let (chain, address, chain_id) = if is_solana_address(address) {
+        ("Solana", address.to_string(), origin.solana_chain_id.clone())
+    } else {
+        ("Ethereum", checksum_address(address), origin.evm_chain_id.to_string())
+    };
+    format!("{domain} wants you to sign in with your {chain} account:\n\
+             {address}\n\
+             \n\
+             Sign in to {domain}.\n\
+             \n\
+             URI: {uri}\n\
+             Version: 1\n\
+             Chain ID: {chain_id}\n\
+             Nonce: {nonce}\n\
+             Issued At: {}\n\
+             Expiration Time: {}", rfc3339(issued_at), rfc3339(expire_time),
+            domain = origin.domain, uri = origin.uri)
+}
+
+pub fn is_solana_address(address: &str) -> bool {
+    Pubkey::from_str(address).is_ok()
+}
+
+pub fn generate_session_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// Only the hash of a session token is stored, a leaked table does not leak sessions.
+pub fn hash_session_token(token: &str) -> String {
+    hex::encode(keccak::hash(token.as_bytes()).0)
+}
+
+// Picks SIWS or SIWE verification from the kind of address.
+pub fn verify_login_signature(address: &str, message: &str, signature: &str) -> bool {
+    if is_solana_address(address) {
+        verify_solana_signature(address, message, signature)
+    } else {
+        verify_personal_sign(address, message, signature)
+    }
+}
+
+// Base58 ed25519 signature of the message by the solana wallet.
+pub fn verify_solana_signature(address: &str, message: &str, signature: &str) -> bool {
+    let Ok(pubkey) = Pubkey::from_str(address) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_str(signature) else {
+        return false;
+    };
+    signature.verify(pubkey.as_ref(), message.as_bytes())
+}
+
+// EIP-191 personal_sign: returns the lowercase evm address without 0x that signed the message.
+pub fn recover_personal_sign_address(message: &str, signature: &str) -> Option<String> {
+    let signature = hex::decode(signature.trim().trim_start_matches("0x")).ok()?;
+    if signature.len() != 65 {
+        return None;
+    }
+    let recovery_id = match signature[64] {
+        v @ 27..=28 => v - 27,
+        v @ 0..=1 => v,
+        _ => return None,
+    };
+    let prefixed = format!("\x19Ethereum Signed Message:\n{}{}", message.len(), message);
+    let hash = keccak::hash(prefixed.as_bytes());
+    let pubkey = secp256k1_recover(&hash.0, recovery_id, &signature[..64]).ok()?;
+    let address_hash = keccak::hash(&pubkey.to_bytes());
+    Some(hex::encode(&address_hash.0[12..]))
+}
+
+pub fn verify_personal_sign(evm_address: &str, message: &str, signature: &str) -> bool {
+    recover_personal_sign_address(message, signature)
+        .map(|a| a == evm_address.to_lowercase().trim_start_matches("0x"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use solana_sdk::signature::{Keypair, Signer};
+    use super::*;
+
+    #[test]
+    fn test_verify_solana_signature() {
+        let keypair = Keypair::new();
+        let message = bind_challenge_message("octopus", "00", &keypair.pubkey().to_string(), "01", 0);
+        let signature = keypair.sign_message(message.as_bytes()).to_string();
+        assert!(verify_solana_signature(&keypair.pubkey().to_string(), &message, &signature));
+        assert!(!verify_solana_signature(&Pubkey::new_unique().to_string(), &message, &signature));
+        assert!(!verify_solana_signature(&keypair.pubkey().to_string(), "other message", &signature));
+    }
+
+    fn origin() -> SignInOrigin {
+        SignInOrigin {
+            domain: "octopus.xyz".to_string(),
+            uri: "https://octopus.xyz".to_string(),
+            evm_chain_id: 1,
+            solana_chain_id: "mainnet".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_evm_login_message() {
+        let message = login_message(&origin(), "14791697260e4c9a71f18484c9f997b308e59325", "0123456789abcdef", 0, 300);
+        assert_eq!(message, "octopus.xyz wants you to sign in with your Ethereum account:\n\
+                             0x14791697260E4c9A71f18484C9f997B308e59325\n\
+                             \n\
+                             Sign in to octopus.xyz.\n\
+                             \n\
+                             URI: https://octopus.xyz\n\
+                             Version: 1\n\
+                             Chain ID: 1\n\
+                             Nonce: 0123456789abcdef\n\
+                             Issued At: 1970-01-01T00:00:00Z\n\
+                             Expiration Time: 1970-01-01T00:05:00Z");
+    }
+
+    #[test]
+    fn test_solana_login_signature() {
+        let keypair = Keypair::new();
+        let address = keypair.pubkey().to_string();
+        let message = login_message(&origin(), &address, "01", 0, 300);
+        assert!(message.contains("sign in with your Solana account"));
+        assert!(message.contains("\nChain ID: mainnet\n"));
+        let signature = keypair.sign_message(message.as_bytes()).to_string();
+        assert!(verify_login_signature(&address, &message, &signature));
+    }
+
+    #[test]
+    fn test_recover_personal_sign_address() {
+        // "Hello World" signed with the ethers docs key 0x0123456789012345678901234567890123456789012345678901234567890123
+        let signature = "0x66b794a2ecc7044ae9872d7e9c49539a730b20071d21d563e628dfa9efb70c82\
+                         306b11529f8625ee942436c83583e4293ac4e78a8dd54692721b1a9ca35e65371c";
+        let address = "14791697260e4c9a71f18484c9f997b308e59325";
+        assert_eq!(recover_personal_sign_address("Hello World", signature).as_deref(), Some(address));
+        assert!(verify_personal_sign("0x14791697260E4c9A71f18484C9f997B308e59325", "Hello World", signature));
+        assert!(verify_login_signature(address, "Hello World", signature));
+        assert!(!verify_personal_sign(address, "Hello World!", signature));
+    }
+
+    #[test]
+    fn test_reject_malformed_evm_signature() {
+        assert!(recover_personal_sign_address("message", "0x1234").is_none());
+        assert!(!verify_personal_sign("0000000000000000000000000000000000000000", "message", &"00".repeat(65)));
+    }
+}
@@ -0,0 +1,112 @@
+use rbatis::RBatis;
+use crate::config::Config;
+use crate::eligibility::EligibilityRules;
+use crate::eligibility::snapshot::{import_snapshot, SnapshotFormat};
+use crate::{allocation, merkle, refund};
+use crate::points::{self, season, PointRules};
+use crate::rebate::{ledger, payout, RebatePolicy};
+
+const USAGE: &str = "usage:
+    octupus                                  run the api server and the watcher
+    octupus refund sync                      compute refunds for contributions over the launch cap
+    octupus refund build                     print unsigned refund transactions as json
+    octupus refund record <batch_id> <sig>   record the broadcast signature of a refund batch
+    octupus rebate sync                      accrue rebate entries for new launch records
+    octupus rebate batch                     group accrued rebates into payout batches
+    octupus rebate build                     print unsigned rebate payout transactions as json
+    octupus rebate record <batch_id> <sig>   record the broadcast signature of a rebate batch
+    octupus rebate confirm <batch_id>        mark a sent rebate batch as paid
+    octupus points sync                      recompute every point balance from the point rules
+    octupus season export <id> <path>        write the frozen season end snapshot to a csv file
+    octupus allocation sync                  recompute token allocations from the launch records
+    octupus allocation export <path>         write the token allocations to a csv file
+    octupus merkle generate                  build a new claim merkle tree from the bound query accounts
+    octupus merkle verify [version]          rebuild the stored tree and check its root and proofs
+    octupus snapshot import <path> [source]  import an eligibility snapshot from a csv or json file";
+
+pub async fn run_command(args: &[String], config: Config, mut rb: RBatis) -> anyhow::Result<()> {
+    let args = args.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["refund", "sync"] => {
+            let count = refund::sync_refund_ledger(&mut rb, &config).await?;
+            println!("{count} refund records computed");
+        }
+        ["refund", "build"] => {
+            let txs = refund::build_refund_transactions(&mut rb, &config).await?;
+            println!("{}", serde_json::to_string_pretty(&txs)?);
+        }
+        ["refund", "record", batch_id, signature] => {
+            let batch_id = batch_id.parse::<i64>()?;
+            refund::record_refund_signature(&mut rb, batch_id, signature).await?;
+            println!("batch {batch_id} marked as sent");
+        }
+        ["rebate", "sync"] => {
+            let policy = RebatePolicy::load(&config)?;
+            let count = ledger::sync_rebate_ledger(&mut rb, &policy).await?;
+            println!("{count} rebate entries accrued");
+        }
+        ["rebate", "batch"] => {
+            let payout = payout::RebatePayout::from_config(&config)?;
+            let batches = ledger::create_rebate_batches(&mut rb, &payout).await?;
+            println!("{}", serde_json::to_string_pretty(&batches)?);
+        }
+        ["rebate", "build"] => {
+            let payout = payout::RebatePayout::from_config(&config)?;
+            let txs = payout::build_rebate_transactions(&rb, &payout).await?;
+            println!("{}", serde_json::to_string_pretty(&txs)?);
+        }
+        ["rebate", "record", batch_id, signature] => {
+            let batch_id = batch_id.parse::<i64>()?;
+            ledger::record_rebate_signature(&mut rb, batch_id, signature).await?;
+            println!("rebate batch {batch_id} marked as sent");
+        }
+        ["rebate", "confirm", batch_id] => {
+            let batch_id = batch_id.parse::<i64>()?;
+            ledger::confirm_rebate_batch(&mut rb, batch_id).await?;
+            println!("rebate batch {batch_id} marked as paid");
+        }
+        ["points", "sync"] => {
+            let rules = PointRules::from_config(&config)?;
+            let count = points::sync_points(&mut rb, &rules).await?;
+            println!("{count} point events recorded");
+        }
+        ["season", "export", season_id, path] => {
+            let season_id = season_id.parse::<i64>()?;
+            let (count, csv) = season::export_season_snapshot(&rb, season_id).await?;
+            std::fs::write(path, csv)?;
+            println!("{count} season points exported to {path}");
+        }
+        ["allocation", "sync"] => {
+            let count = allocation::sync_allocations(&mut rb, &config).await?;
+            println!("{count} allocations computed");
+        }
+        ["allocation", "export", path] => {
+            let count = allocation::export_allocations(&rb, path).await?;
+            println!("{count} allocations exported to {path}");
+        }
+        ["merkle", "generate"] => {
+            let distribution = merkle::generate_distribution(&mut rb, &config).await?;
+            println!("merkle distribution {} root {} total amount {} nodes {}", distribution.version,
+                     distribution.root, distribution.total_amount, distribution.num_nodes);
+        }
+        ["merkle", "verify"] | ["merkle", "verify", _] => {
+            let version = match args.get(2) {
+                Some(v) => Some(v.parse::<i64>()?),
+                None => None,
+            };
+            let distribution = merkle::verify_distribution(&rb, version).await?;
+            println!("merkle distribution {} root {} verified", distribution.version, distribution.root);
+        }
+        ["snapshot", "import", path] | ["snapshot", "import", path, _] => {
+            let format = SnapshotFormat::from_path(path)
+                .ok_or(anyhow::anyhow!("snapshot file must end with .csv or .json"))?;
+            let source = args.get(3).cloned().unwrap_or(*path);
+            let content = std::fs::read_to_string(path)?;
+            let rules = EligibilityRules::load(&config.eligibility_rules_path)?;
+            let diff = import_snapshot(&mut rb, &rules, source, &content, &format).await?;
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        }
+        _ => anyhow::bail!("{USAGE}"),
+    }
+    Ok(())
+}
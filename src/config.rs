@@ -13,6 +13,45 @@ pub struct Config {
     pub receiver_address: String,
     pub launch_program_id: String,
     pub launch_max_amount: u64,
+    pub refund_wallet_address: String,
+    pub wallet_min_contribution: String,
+    pub wallet_max_contribution: String,
+    pub token_supply_for_sale: String,
+    pub pricing_rule: String,
+    pub token_price: String,
+    pub pricing_tiers: String,
+    pub eligibility_rules_path: String,
+    pub admin_token: String,
+    pub orbiter_base_url: String,
+    pub orbiter_timeout_ms: u64,
+    pub orbiter_cache_ttl: i64,
+    pub orbiter_breaker_threshold: u32,
+    pub orbiter_breaker_cooldown: u64,
+    pub auth_domain: String,
+    pub auth_uri: String,
+    pub auth_evm_chain_id: u64,
+    pub auth_solana_chain_id: String,
+    pub bind_challenge_ttl: i64,
+    pub login_challenge_ttl: i64,
+    pub session_ttl: i64,
+    pub referral_level_rates: String,
+    pub rebate_policy_path: String,
+    pub rebate_treasury_address: String,
+    pub rebate_payout_asset: String,
+    pub rebate_token_rate: String,
+    pub abuse_funder_threshold: i64,
+    pub abuse_ip_threshold: i64,
+    pub abuse_ip_window: i64,
+    // comma separated funding wallets, e.g. exchange hot wallets, that never count as a shared funder
+    pub abuse_funder_allowlist: String,
+    // comma separated proxy ips whose X-Forwarded-For is trusted for the client ip
+    pub trusted_proxies: String,
+    pub points_per_sol: String,
+    pub points_per_invite: i64,
+    pub points_per_invited: i64,
+    pub points_per_invitee_sol: String,
+    pub points_early_bird: String,
+    pub leaderboard_refresh_interval: u64,
 }
 
 impl Config {
@@ -37,6 +76,58 @@ impl Config {
         let launch_program_id = env::var("LAUNCH_PROGRAM_ID").unwrap_or_default();
         let launch_max_amount = env::var("LAUNCH_MAX_AMOUNT").unwrap_or_default()
             .parse::<u64>().unwrap_or(0u64);
+        let refund_wallet_address = env::var("REFUND_WALLET_ADDRESS").unwrap_or_default();
+        let wallet_min_contribution = env::var("WALLET_MIN_CONTRIBUTION").unwrap_or_default();
+        let wallet_max_contribution = env::var("WALLET_MAX_CONTRIBUTION").unwrap_or_default();
+        let token_supply_for_sale = env::var("TOKEN_SUPPLY_FOR_SALE").unwrap_or_default();
+        let pricing_rule = env::var("PRICING_RULE").unwrap_or("fixed".to_string());
+        let token_price = env::var("TOKEN_PRICE").unwrap_or_default();
+        let pricing_tiers = env::var("PRICING_TIERS").unwrap_or_default();
+        let eligibility_rules_path = env::var("ELIGIBILITY_RULES_PATH").unwrap_or_default();
+        let admin_token = env::var("ADMIN_TOKEN").unwrap_or_default();
+        let orbiter_base_url = env::var("ORBITER_BASE_URL")
+            .unwrap_or("https://openapi.orbiter.finance/mainnet/v1".to_string());
+        let orbiter_timeout_ms = env::var("ORBITER_TIMEOUT_MS").unwrap_or_default()
+            .parse::<u64>().unwrap_or(5000u64);
+        let orbiter_cache_ttl = env::var("ORBITER_CACHE_TTL").unwrap_or_default()
+            .parse::<i64>().unwrap_or(86400i64);
+        let orbiter_breaker_threshold = env::var("ORBITER_BREAKER_THRESHOLD").unwrap_or_default()
+            .parse::<u32>().unwrap_or(5u32);
+        let orbiter_breaker_cooldown = env::var("ORBITER_BREAKER_COOLDOWN").unwrap_or_default()
+            .parse::<u64>().unwrap_or(60u64);
+        let auth_domain = env::var("AUTH_DOMAIN").unwrap_or("octopus".to_string());
+        let auth_uri = env::var("AUTH_URI").unwrap_or(format!("https://{auth_domain}"));
+        let auth_evm_chain_id = env::var("AUTH_EVM_CHAIN_ID").unwrap_or_default()
+            .parse::<u64>().unwrap_or(1u64);
+        let auth_solana_chain_id = env::var("AUTH_SOLANA_CHAIN_ID").unwrap_or("mainnet".to_string());
+        let bind_challenge_ttl = env::var("BIND_CHALLENGE_TTL").unwrap_or_default()
+            .parse::<i64>().unwrap_or(300i64);
+        let login_challenge_ttl = env::var("LOGIN_CHALLENGE_TTL").unwrap_or_default()
+            .parse::<i64>().unwrap_or(300i64);
+        let session_ttl = env::var("SESSION_TTL").unwrap_or_default()
+            .parse::<i64>().unwrap_or(3600i64);
+        let points_per_sol = env::var("POINTS_PER_SOL").unwrap_or("0".to_string());
+        let points_per_invite = env::var("POINTS_PER_INVITE").unwrap_or_default()
+            .parse::<i64>().unwrap_or(1000i64);
+        let points_per_invited = env::var("POINTS_PER_INVITED").unwrap_or_default()
+            .parse::<i64>().unwrap_or(1000i64);
+        let points_per_invitee_sol = env::var("POINTS_PER_INVITEE_SOL").unwrap_or("0".to_string());
+        let points_early_bird = env::var("POINTS_EARLY_BIRD").unwrap_or_default();
+        let leaderboard_refresh_interval = env::var("LEADERBOARD_REFRESH_INTERVAL").unwrap_or_default()
+            .parse::<u64>().unwrap_or(300u64);
+        let referral_level_rates = env::var("REFERRAL_LEVEL_RATES").unwrap_or("0.1".to_string());
+        let rebate_policy_path = env::var("REBATE_POLICY_PATH").unwrap_or_default();
+        let rebate_treasury_address = env::var("REBATE_TREASURY_ADDRESS").unwrap_or_default();
+        let rebate_payout_asset = env::var("REBATE_PAYOUT_ASSET").unwrap_or("sol".to_string());
+        let rebate_token_rate = env::var("REBATE_TOKEN_RATE").unwrap_or_default();
+        let abuse_funder_threshold = env::var("ABUSE_FUNDER_THRESHOLD").unwrap_or_default()
+            .parse::<i64>().unwrap_or(5i64);
+        let abuse_ip_threshold = env::var("ABUSE_IP_THRESHOLD").unwrap_or_default()
+            .parse::<i64>().unwrap_or(5i64);
+        let abuse_ip_window = env::var("ABUSE_IP_WINDOW").unwrap_or_default()
+            .parse::<i64>().unwrap_or(3600i64);
+        let abuse_funder_allowlist = env::var("ABUSE_FUNDER_ALLOWLIST").unwrap_or_default();
+        let trusted_proxies = env::var("TRUSTED_PROXIES").unwrap_or_default();
         Self {
             port,
             workers,
@@ -49,7 +140,44 @@ impl Config {
             claim_start,
             receiver_address,
             launch_program_id,
-            launch_max_amount
+            launch_max_amount,
+            refund_wallet_address,
+            wallet_min_contribution,
+            wallet_max_contribution,
+            token_supply_for_sale,
+            pricing_rule,
+            token_price,
+            pricing_tiers,
+            eligibility_rules_path,
+            admin_token,
+            orbiter_base_url,
+            orbiter_timeout_ms,
+            orbiter_cache_ttl,
+            orbiter_breaker_threshold,
+            orbiter_breaker_cooldown,
+            auth_domain,
+            auth_uri,
+            auth_evm_chain_id,
+            auth_solana_chain_id,
+            bind_challenge_ttl,
+            login_challenge_ttl,
+            session_ttl,
+            referral_level_rates,
+            rebate_policy_path,
+            rebate_treasury_address,
+            rebate_payout_asset,
+            rebate_token_rate,
+            abuse_funder_threshold,
+            abuse_ip_threshold,
+            abuse_ip_window,
+            abuse_funder_allowlist,
+            trusted_proxies,
+            points_per_sol,
+            points_per_invite,
+            points_per_invited,
+            points_per_invitee_sol,
+            points_early_bird,
+            leaderboard_refresh_interval,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::{LaunchRecord, LaunchRecordCheck};
+
+pub const VIOLATION_BELOW_MIN: &str = "below_min";
+pub const VIOLATION_ABOVE_MAX: &str = "above_max";
+
+#[derive(Clone, Debug)]
+pub struct ContributionLimits {
+    pub min: Option<BigDecimal>,
+    pub max: Option<BigDecimal>,
+}
+
+impl ContributionLimits {
+    pub fn from_config(config: &Config) -> Self {
+        let parse = |s: &str| BigDecimal::from_str(s).ok().filter(|v| !v.is_zero());
+        Self {
+            min: parse(&config.wallet_min_contribution),
+            max: parse(&config.wallet_max_contribution),
+        }
+    }
+
+    // The maximum caps the running total of the wallet, the minimum applies to every single contribution.
+    pub fn violation(&self, amount: &BigDecimal, cumulative_amount: &BigDecimal) -> Option<&'static str> {
+        if let Some(max) = &self.max {
+            if cumulative_amount > max {
+                return Some(VIOLATION_ABOVE_MAX);
+            }
+        }
+        if let Some(min) = &self.min {
+            if amount < min {
+                return Some(VIOLATION_BELOW_MIN);
+            }
+        }
+        None
+    }
+
+    // None means the wallet has no upper limit
+    pub fn remaining_allowance(&self, total: &BigDecimal) -> Option<BigDecimal> {
+        self.max.as_ref().map(|max| {
+            if total >= max {
+                BigDecimal::zero()
+            } else {
+                max.clone() - total.clone()
+            }
+        })
+    }
+}
+
+fn to_big_decimal(amount: &Decimal) -> BigDecimal {
+    BigDecimal::from_str(&amount.to_string()).unwrap_or_default()
+}
+
+// Computes the running total of every sender and flags the records that break a wallet rule.
+pub async fn check_launch_records(rb: &RBatis, limits: &ContributionLimits, records: &[LaunchRecord]) -> anyhow::Result<Vec<LaunchRecordCheck>> {
+    let mut sorted = records.to_vec();
+    // the order get_pre_launch_record counts earlier launches in
+    sorted.sort_by(|a, b| (a.launch_time, &a.launch_tx_hash, a.log_index)
+        .cmp(&(b.launch_time, &b.launch_tx_hash, b.log_index)));
+
+    let mut totals: HashMap<String, BigDecimal> = HashMap::new();
+    let mut checks = vec![];
+    for record in sorted {
+        if !totals.contains_key(&record.address) {
+            let pre_records = db::get_pre_launch_record(rb, &record).await?;
+            let pre_total = pre_records.iter()
+                .fold(BigDecimal::zero(), |acc, r| acc + to_big_decimal(&r.launch_amount));
+            totals.insert(record.address.clone(), pre_total);
+        }
+        let amount = to_big_decimal(&record.launch_amount);
+        let total = totals.get_mut(&record.address).unwrap();
+        *total += amount.clone();
+        let violation = limits.violation(&amount, total);
+        if let Some(violation) = violation {
+            log::warn!("launch record {}:{} of {} breaks wallet rule {violation}, cumulative amount {}",
+                record.launch_tx_hash, record.log_index, record.address, total);
+        }
+        checks.push(LaunchRecordCheck {
+            launch_tx_hash: record.launch_tx_hash.clone(),
+            log_index: record.log_index,
+            address: record.address.clone(),
+            cumulative_amount: Decimal::from_str(&total.to_string()).unwrap(),
+            violation: violation.map(|v| v.to_string()),
+        });
+    }
+    Ok(checks)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_contribution_limits() {
+        let amount = |s: &str| BigDecimal::from_str(s).unwrap();
+        let limits = ContributionLimits { min: Some(amount("1")), max: Some(amount("10")) };
+        assert_eq!(limits.violation(&amount("2"), &amount("2")), None);
+        // a small top-up is below the minimum even when the running total is not
+        assert_eq!(limits.violation(&amount("0.5"), &amount("5.5")), Some(VIOLATION_BELOW_MIN));
+        assert_eq!(limits.violation(&amount("6"), &amount("11")), Some(VIOLATION_ABOVE_MAX));
+        assert_eq!(limits.violation(&amount("10"), &amount("10")), None);
+        assert_eq!(limits.remaining_allowance(&amount("4")), Some(amount("6")));
+        assert_eq!(limits.remaining_allowance(&amount("12")), Some(BigDecimal::zero()));
+        let unlimited = ContributionLimits { min: None, max: None };
+        assert_eq!(unlimited.violation(&amount("0.1"), &amount("1000")), None);
+        assert_eq!(unlimited.remaining_allowance(&amount("4")), None);
+    }
+}
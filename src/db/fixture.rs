@@ -0,0 +1,54 @@
+use std::path::Path;
+use rand::Rng;
+use rbatis::RBatis;
+
+// A fresh database with every migration applied, created on the postgres server of TEST_DATABASE_URL
+// (e.g. postgres://postgres@localhost:5432/postgres) and dropped again when the fixture goes out of scope.
+// Tests that need one are #[ignore]d, run them with `cargo test -- --include-ignored`.
+pub struct TestDb {
+    pub rb: RBatis,
+    url: String,
+    name: String,
+}
+
+pub async fn test_db() -> TestDb {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL is not set");
+    let name = format!("octupus_test_{}", rand::thread_rng().gen::<u32>());
+    let server = RBatis::new();
+    server.init(rbdc_pg::driver::PgDriver {}, &url).unwrap();
+    server.exec(&format!("create database {name}"), vec![]).await.unwrap();
+
+    let (base, _) = url.rsplit_once('/').unwrap();
+    let rb = RBatis::new();
+    rb.init(rbdc_pg::driver::PgDriver {}, &format!("{base}/{name}")).unwrap();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/storage/migrations");
+    let mut migrations = std::fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    migrations.sort();
+    for migration in migrations {
+        let sql = std::fs::read_to_string(migration.join("up.sql")).unwrap();
+        rb.exec(&sql, vec![]).await.unwrap();
+    }
+    TestDb { rb, url, name }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        // the test runtime may already be shutting down, so drop the database from a runtime of our own
+        let url = self.url.clone();
+        let name = self.name.clone();
+        let dropped = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            runtime.block_on(async {
+                let server = RBatis::new();
+                server.init(rbdc_pg::driver::PgDriver {}, &url)?;
+                server.exec(&format!("drop database if exists {name} with (force)"), vec![]).await?;
+                anyhow::Ok(())
+            })
+        }).join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            log::error!("failed to drop test database {}", self.name);
+        }
+    }
+}
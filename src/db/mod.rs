@@ -1,11 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
 use num::ToPrimitive;
 use rbatis::RBatis;
+use rbatis::executor::{Executor, RBatisTxExecutorGuard};
 use rbatis::rbdc::decimal::Decimal;
 use solana_sdk::pubkey::new_rand;
-use crate::db::tables::{Account, AccountEligible, AccountInviteeInfo, ClaimedAccount, LastSyncBlock, LaunchRecord, QueryAccount, UserPoint};
+use crate::address::{AccountAddress, EvmAddress};
+use crate::leaderboard::{BOARD_MINT, BOARD_POINTS, BOARD_REFERRAL};
+use crate::pagination::PageRequest;
+use crate::points::HeldPoint;
+use crate::referral::to_big_decimal;
+use crate::db::tables::{AbuseCandidate, AbuseFlag, Account, AccountEligible, AccountSummary, Allocation, AccountInviteeFilter, AccountInviteeInfo, BindChallenge, BindRequest, FundingSource, InviteCode, ClaimedAccount, EligibilitySnapshot, EligibilitySnapshotEntry, LastSyncBlock, LaunchRecord, LaunchRecordFilter, LeaderboardRank, LaunchRecordCheck, LoginChallenge, MerkleDistribution, MerkleLeaf, OrbiterGasCache, PointAccount, PointEvent, PointEventTotal, PointRank, QueryAccount, RebateBatch, RebateEntry, RebateInviterTotal, RebateSummary, ReferralLaunch, ReferralLevelMint, ReferralNode, RefundRecord, Season, Session, UserPoint};
 
 pub(crate) mod tables;
+#[cfg(test)]
+pub(crate) mod fixture;
 
 pub(crate) async fn upsert_last_sync_block(rb: &mut RBatis, new_block : i64) -> anyhow::Result<()> {
     let block = LastSyncBlock::select_all(rb).await?;
@@ -44,15 +54,9 @@ pub(crate) async fn save_account(rb: &mut RBatis, account: &Account) -> anyhow::
     Ok(())
 }
 
-pub(crate) async fn update_inviter_point(rb: &mut RBatis, inviter: &str,add_point: i64) -> anyhow::Result<()> {
-    rb.exec("update accounts set point = point + 1000 where address = ?",
-            vec![rbs::to_value!(inviter), rbs::to_value!(add_point)]).await?;
-    Ok(())
-}
-
-pub async fn get_query_account_by_address(rb:&RBatis,address: String) -> anyhow::Result<Option<QueryAccount>> {
+pub async fn get_query_account_by_address(rb:&RBatis,address: &EvmAddress) -> anyhow::Result<Option<QueryAccount>> {
     let account: Option<QueryAccount> = rb
-        .query_decode("select * from query_accounts where address = ? limit 1",vec![rbs::to_value!(address)])
+        .query_decode("select * from query_accounts where address = ? limit 1",vec![rbs::to_value!(address.to_db_string())])
         .await?;
     Ok(account)
 }
@@ -64,9 +68,9 @@ pub async fn get_query_account_by_address(rb:&RBatis,address: String) -> anyhow:
 //     Ok(account.map(|a|a.address))
 // }
 
-pub async fn get_user_bind_sol_address(rb:&RBatis,address: &str) -> anyhow::Result<Option<String>> {
+pub async fn get_user_bind_sol_address(rb:&RBatis,address: &EvmAddress) -> anyhow::Result<Option<String>> {
     let account: Option<QueryAccount> = rb
-        .query_decode("select * from query_accounts where address = ? limit 1",vec![rbs::to_value!(address)])
+        .query_decode("select * from query_accounts where address = ? limit 1",vec![rbs::to_value!(address.to_db_string())])
         .await?;
     let sol_address = if let Some(account) = account {
         account.claim_sol_address
@@ -89,25 +93,114 @@ pub async fn get_account_by_inviter_code(rb:&RBatis,code: &str) -> anyhow::Resul
         .await?;
     Ok(account)
 }
-pub async fn get_account_invitees(rb:&RBatis,address: &str,page_no: i32) -> anyhow::Result<(usize,Vec<AccountInviteeInfo>)> {
-    let PAGE_SIZE = 10;
-    let offset = (page_no - 1) * PAGE_SIZE;
-    let invitees: Vec<AccountInviteeInfo> = rb
-        .query_decode("select l.address,sum(l.launch_amount) as mint_amount from accounts a \
-        join launch_records l \
-        on a.address = l.address \
-        where a.inviter = ? group by l.address order by mint_amount desc offset ? limit ?",
-                      vec![rbs::to_value!(address),rbs::to_value!(offset),rbs::to_value!(PAGE_SIZE)])
-        .await?;
-    println!("invitees is {:?}",invitees);
-    let count: HashMap<String,usize> = rb.query_decode("select count(1) from accounts a \
-        join launch_records l \
-        on a.address = l.address \
-        where a.inviter = ?",
-        vec![rbs::to_value!(address)]).await?;
-    let count = count.get("count").unwrap();
-    let pg_count = count / PAGE_SIZE as usize;
-    Ok((pg_count,invitees))
+
+// Replaces the invite code of the account when no account or campaign code owns the new one.
+pub(crate) async fn update_invite_code(rb: &mut RBatis, address: &str, code: &str) -> anyhow::Result<u64> {
+    let ret = rb.exec("update accounts set invite_code = ? where address = ? \
+    and not exists (select 1 from accounts where invite_code = ?) \
+    and not exists (select 1 from invite_codes where code = ?)",
+            vec![rbs::to_value!(code),
+                 rbs::to_value!(address),
+                 rbs::to_value!(code),
+                 rbs::to_value!(code),
+            ]).await?;
+    Ok(ret.rows_affected)
+}
+
+// Every account bound with the invite of the inviter, whether it minted or not. The rebate is what the
+// rebate_entries ledger holds, so it misses launches until the next ledger sync and anything withheld for
+// an abuse flag. /get_referral_tree computes its rebates from the launches instead and shows neither gap.
+const ACCOUNT_INVITEES: &str = "select a.address,a.create_time as bind_time, \
+    coalesce(m.mint_count,0) as mint_count,coalesce(m.mint_amount,0) as mint_amount, \
+    coalesce(r.rebate_amount,0) as rebate_amount,f.status as flag_status from accounts a \
+    left join (select address,count(1) as mint_count,sum(launch_amount) as mint_amount from launch_records \
+    where address in (select address from accounts where inviter = ?) group by address) m \
+    on m.address = a.address \
+    left join (select address,sum(rebate_amount) as rebate_amount from rebate_entries \
+    where inviter = ? and level = 1 group by address) r \
+    on r.address = a.address \
+    left join abuse_flags f on f.address = a.address \
+    where a.inviter = ?";
+
+const ACCOUNT_INVITEES_FILTER: &str = "(?::boolean is null or (mint_count > 0) = ?) \
+    and (?::boolean is null or (coalesce(flag_status,'cleared') <> 'cleared') = ?) \
+    and (?::bigint is null or bind_time >= ?) \
+    and (?::bigint is null or bind_time < ?)";
+
+fn account_invitees_args(address: &str, filter: &AccountInviteeFilter) -> Vec<rbs::Value> {
+    vec![rbs::to_value!(address),
+         rbs::to_value!(address),
+         rbs::to_value!(address),
+         rbs::to_value!(filter.minted),
+         rbs::to_value!(filter.minted),
+         rbs::to_value!(filter.flagged),
+         rbs::to_value!(filter.flagged),
+         rbs::to_value!(filter.start_time),
+         rbs::to_value!(filter.start_time),
+         rbs::to_value!(filter.end_time),
+         rbs::to_value!(filter.end_time)]
+}
+
+// Order and keyset condition of an invitee sort, address breaks ties.
+pub fn account_invitees_sort(sort: &str) -> Option<(&'static str, &'static str)> {
+    match sort {
+        "mint_desc" => Some(("mint_amount desc,address desc", "(mint_amount,address) < (?,?)")),
+        "mint_asc" => Some(("mint_amount asc,address asc", "(mint_amount,address) > (?,?)")),
+        "bind_desc" => Some(("bind_time desc,address desc", "(bind_time,address) < (?,?)")),
+        "bind_asc" => Some(("bind_time asc,address asc", "(bind_time,address) > (?,?)")),
+        "rebate_desc" => Some(("rebate_amount desc,address desc", "(rebate_amount,address) < (?,?)")),
+        "rebate_asc" => Some(("rebate_amount asc,address asc", "(rebate_amount,address) > (?,?)")),
+        _ => None,
+    }
+}
+
+// Cursor key of an invitee under the sort, the columns of its keyset condition.
+pub fn account_invitee_key(sort: &str, invitee: &AccountInviteeInfo) -> Vec<String> {
+    let value = if sort.starts_with("bind") {
+        invitee.bind_time.to_string()
+    } else if sort.starts_with("rebate") {
+        invitee.rebate_amount.to_string()
+    } else {
+        invitee.mint_amount.to_string()
+    };
+    vec![value, invitee.address.clone()]
+}
+
+fn account_invitee_key_args(sort: &str, key: &[String]) -> anyhow::Result<Vec<rbs::Value>> {
+    let [value, address] = key else {
+        anyhow::bail!("Invalid cursor");
+    };
+    let value = if sort.starts_with("bind") {
+        rbs::to_value!(value.parse::<i64>()?)
+    } else {
+        rbs::to_value!(Decimal::from_str(value)?)
+    };
+    Ok(vec![value, rbs::to_value!(address.clone())])
+}
+
+// Returns the invitees matching the filter and the page, fetched one past the page size.
+pub async fn get_account_invitees(rb:&RBatis,address: &str,filter: &AccountInviteeFilter,sort: &str,
+                                  page: &PageRequest,after: Option<&Vec<String>>) -> anyhow::Result<(u64,Vec<AccountInviteeInfo>)> {
+    let (order, after_condition) = account_invitees_sort(sort).ok_or(anyhow::anyhow!("unknown sort {sort}"))?;
+    let mut args = account_invitees_args(address, filter);
+    let mut sql = format!("select * from ({ACCOUNT_INVITEES}) t where {ACCOUNT_INVITEES_FILTER}");
+    // a cursor replaces the offset
+    let offset = match after {
+        Some(key) => {
+            args.extend(account_invitee_key_args(sort, key)?);
+            sql += &format!(" and {after_condition}");
+            0
+        }
+        None => page.offset(),
+    };
+    sql += &format!(" order by {order} offset ? limit ?");
+    args.push(rbs::to_value!(offset));
+    args.push(rbs::to_value!(page.page_size + 1));
+    let invitees: Vec<AccountInviteeInfo> = rb.query_decode(&sql, args).await?;
+    // one row per invitee, unfiltered it agrees with get_account_invitees_count
+    let count: u64 = rb.query_decode(&format!("select count(1) from ({ACCOUNT_INVITEES}) t where {ACCOUNT_INVITEES_FILTER}"),
+        account_invitees_args(address, filter)).await?;
+    Ok((count,invitees))
 }
 
 pub async fn get_account_invitees_count(rb:&RBatis,address: &str) -> anyhow::Result<usize> {
@@ -119,13 +212,46 @@ pub async fn get_account_invitees_count(rb:&RBatis,address: &str) -> anyhow::Res
     Ok(*count)
 }
 
-pub async fn get_account_invitees_total_mint(rb:&RBatis,address: &str) -> anyhow::Result<Decimal> {
-    let total_mint: Decimal = rb
-        .query_decode("select coalesce(sum(lr.launch_amount),0) as total_amount from launch_records lr \
-         join accounts a on lr.address = a.address
-         where a.inviter = ?",vec![rbs::to_value!(address)])
+// Invitees and their mint per level of the referral subtree below address, down to depth levels.
+pub async fn get_referral_levels(rb:&RBatis,address: &str,depth: usize) -> anyhow::Result<Vec<ReferralLevelMint>> {
+    if depth == 0 {
+        return Ok(vec![]);
+    }
+    let levels: Vec<ReferralLevelMint> = rb
+        .query_decode("with recursive tree (address,level) as ( \
+            select address,1 from accounts where inviter = ? \
+            union all \
+            select a.address,t.level + 1 from accounts a join tree t on a.inviter = t.address where t.level < ? \
+        ) \
+        select t.level,count(1) as invitees,coalesce(sum(m.mint_amount),0) as mint_amount from tree t \
+        left join (select address,sum(launch_amount) as mint_amount from launch_records group by address) m \
+        on m.address = t.address \
+        group by t.level order by t.level",
+                      vec![rbs::to_value!(address),rbs::to_value!(depth as i32)])
         .await?;
-    Ok(total_mint)
+    Ok(levels)
+}
+
+// One page of the subtree below address; levels from get_referral_levels supply the node count.
+pub async fn get_referral_nodes(rb:&RBatis,address: &str,depth: usize,levels: &[ReferralLevelMint],page: &PageRequest) -> anyhow::Result<(usize,Vec<ReferralNode>)> {
+    if depth == 0 {
+        return Ok((0,vec![]));
+    }
+    let nodes: Vec<ReferralNode> = rb
+        .query_decode("with recursive tree (address,inviter,level) as ( \
+            select address,inviter,1 from accounts where inviter = ? \
+            union all \
+            select a.address,a.inviter,t.level + 1 from accounts a join tree t on a.inviter = t.address where t.level < ? \
+        ) \
+        select t.address,t.inviter,t.level,coalesce(m.mint_amount,0) as mint_amount from tree t \
+        left join (select address,sum(launch_amount) as mint_amount from launch_records group by address) m \
+        on m.address = t.address \
+        order by t.level,mint_amount desc,t.address offset ? limit ?",
+                      vec![rbs::to_value!(address),rbs::to_value!(depth as i32),
+                           rbs::to_value!(page.offset()),rbs::to_value!(page.page_size)])
+        .await?;
+    let count = levels.iter().map(|l| l.invitees as u64).sum::<u64>();
+    Ok((page.page_count(count),nodes))
 }
 
 pub async fn get_total_mint(rb:&RBatis) -> anyhow::Result<Decimal> {
@@ -136,22 +262,58 @@ pub async fn get_total_mint(rb:&RBatis) -> anyhow::Result<Decimal> {
     Ok(total_mint)
 }
 
-pub async fn get_total_rebate(rb:&RBatis) -> anyhow::Result<Decimal> {
-    let total_mint: Decimal = rb
-        .query_decode("select coalesce(sum(lr.launch_amount),0) as total_amount from launch_records lr \
-         join accounts a on lr.address = a.address
-         where a.inviter is not null",
-                      vec![])
+// Launch records of the subtree below address, down to depth levels.
+pub async fn get_referral_launches(rb:&RBatis,address: &str,depth: usize) -> anyhow::Result<Vec<ReferralLaunch>> {
+    if depth == 0 {
+        return Ok(vec![]);
+    }
+    let launches: Vec<ReferralLaunch> = rb
+        .query_decode("with recursive tree (address,level) as ( \
+            select address,1 from accounts where inviter = ? \
+            union all \
+            select a.address,t.level + 1 from accounts a join tree t on a.inviter = t.address where t.level < ? \
+        ) \
+        select ?::text as inviter,t.address,t.level,l.launch_tx_hash,l.log_index,l.launch_amount,l.launch_time \
+        from tree t join launch_records l on l.address = t.address",
+                      vec![rbs::to_value!(address),rbs::to_value!(depth as i32),rbs::to_value!(address)])
         .await?;
-    Ok(total_mint)
+    Ok(launches)
 }
+
+// Every launch record once per ancestor of the sender, down to depth levels, that has no ledger entry for that
+// ancestor yet.
+pub async fn get_unrecorded_referral_launches(rb:&RBatis,depth: usize) -> anyhow::Result<Vec<ReferralLaunch>> {
+    if depth == 0 {
+        return Ok(vec![]);
+    }
+    let launches: Vec<ReferralLaunch> = rb
+        .query_decode("with recursive chain (address,ancestor,level) as ( \
+            select address,inviter,1 from accounts where inviter is not null \
+            union all \
+            select c.address,a.inviter,c.level + 1 from chain c join accounts a on a.address = c.ancestor \
+            where a.inviter is not null and c.level < ? \
+        ) \
+        select c.ancestor as inviter,c.address,c.level,l.launch_tx_hash,l.log_index,l.launch_amount,l.launch_time \
+        from chain c join launch_records l on l.address = c.address \
+        where not exists (select 1 from rebate_entries e where e.launch_tx_hash = l.launch_tx_hash \
+        and e.log_index = l.log_index and e.inviter = c.ancestor)",
+                      vec![rbs::to_value!(depth as i32)])
+        .await?;
+    Ok(launches)
+}
+// Re-evaluates under new rules, an account that is already bound keeps the amount it was bound with.
 pub(crate) async fn save_query_account(rb: &mut RBatis, query: QueryAccount) -> anyhow::Result<()> {
     println!("query is {:?}",query);
-    rb.exec("insert into query_accounts (address,claimable_amount,query_time) \
-        values (?,?,?) on conflict(address) do nothing",
+    rb.exec("insert into query_accounts (address,claimable_amount,query_time,rules_version,breakdown) \
+        values (?,?,?,?,?) on conflict(address) do update set claimable_amount = excluded.claimable_amount,\
+        query_time = excluded.query_time,rules_version = excluded.rules_version,breakdown = excluded.breakdown \
+        where query_accounts.rules_version is distinct from excluded.rules_version \
+        and query_accounts.claim_sol_address is null",
             vec![rbs::to_value!(query.address),
                  rbs::to_value![query.claimable_amount.clone()],
                  rbs::to_value!(query.query_time.clone()),
+                 rbs::to_value!(query.rules_version.clone()),
+                 rbs::to_value!(query.breakdown.clone()),
             ]).await?;
 
     Ok(())
@@ -164,13 +326,20 @@ pub(crate) async fn update_query_account_sol_address(rb: &mut RBatis, address:&s
             ]).await?;
     Ok(())
 }
-pub async fn get_queried_account(rb: &RBatis,address: &str) ->anyhow::Result<Option<QueryAccount>> {
+pub async fn get_queried_account(rb: &RBatis,address: &AccountAddress) ->anyhow::Result<Option<QueryAccount>> {
     let ret: Option<QueryAccount> = rb
-        .query_decode("select * from query_accounts where address = ? limit 1 ",vec![rbs::to_value!(address)])
+        .query_decode("select * from query_accounts where address = ? limit 1 ",vec![rbs::to_value!(address.to_db_string())])
         .await?;
     println!("get_queried_account ret is {:?}",ret);
     Ok(ret)
 }
+pub async fn get_latest_snapshot_entry(rb: &RBatis,address: &str) ->anyhow::Result<Option<EligibilitySnapshotEntry>> {
+    let ret: Option<EligibilitySnapshotEntry> = rb
+        .query_decode("select * from eligibility_snapshot_entries where address = ? \
+        and version = (select max(version) from eligibility_snapshots) limit 1",vec![rbs::to_value!(address)])
+        .await?;
+    Ok(ret)
+}
 pub async fn get_all_queried_accounts(rb: &RBatis) ->anyhow::Result<Vec<AccountEligible>> {
     let ret: Vec<QueryAccount> = rb
         .query_decode("select * from query_accounts order by address asc",vec![])
@@ -182,6 +351,13 @@ pub async fn get_all_queried_accounts(rb: &RBatis) ->anyhow::Result<Vec<AccountE
     Ok(accounts_eligible)
 }
 
+pub async fn get_bound_query_accounts(rb: &RBatis) ->anyhow::Result<Vec<QueryAccount>> {
+    let ret: Vec<QueryAccount> = rb
+        .query_decode("select * from query_accounts where claim_sol_address is not null order by address asc",vec![])
+        .await?;
+    Ok(ret)
+}
+
 pub(crate) async fn save_claimed_accounts(rb: &mut RBatis, accounts: Vec<ClaimedAccount>) -> anyhow::Result<()> {
     for account in accounts {
         rb.exec("insert into claimed_accounts (address,claimed_time,claimed_amount) \
@@ -212,24 +388,160 @@ pub(crate) async fn save_launch_records(rb: &mut RBatis, records: &Vec<LaunchRec
     Ok(())
 }
 
-pub async fn get_launch_records(rb: &RBatis,page_no:i32) -> anyhow::Result<(usize,Vec<LaunchRecord>)> {
-    let PAGE_SIZE = 10;
-    let offset = (page_no - 1) * PAGE_SIZE;
+// Order of get_launch_records per sort and the keyset condition for rows after a cursor. Equal amounts
+// list the newest launch first in both amount orders, so amount_asc compares the amount on its own and the
+// rest of the cursor row as one tuple.
+pub fn launch_records_sort(sort: &str) -> Option<(&'static str, &'static str)> {
+    match sort {
+        "time_desc" => Some(("launch_time desc,launch_tx_hash desc,log_index desc",
+                             "(launch_time,launch_tx_hash,log_index) < (?,?,?)")),
+        "time_asc" => Some(("launch_time asc,launch_tx_hash asc,log_index asc",
+                            "(launch_time,launch_tx_hash,log_index) > (?,?,?)")),
+        "slot_desc" => Some(("launch_block desc,launch_tx_hash desc,log_index desc",
+                             "(launch_block,launch_tx_hash,log_index) < (?,?,?)")),
+        "slot_asc" => Some(("launch_block asc,launch_tx_hash asc,log_index asc",
+                            "(launch_block,launch_tx_hash,log_index) > (?,?,?)")),
+        "amount_desc" => Some(("launch_amount desc,launch_time desc,launch_tx_hash desc,log_index desc",
+                               "(launch_amount,launch_time,launch_tx_hash,log_index) < (?,?,?,?)")),
+        "amount_asc" => Some(("launch_amount asc,launch_time desc,launch_tx_hash desc,log_index desc",
+                              "(launch_amount > ? or launch_amount = ? and (launch_time,launch_tx_hash,log_index) < (?,?,?))")),
+        _ => None,
+    }
+}
+
+// Cursor key of a record under the sort, the columns of its keyset condition.
+pub fn launch_record_key(sort: &str, record: &LaunchRecord) -> Vec<String> {
+    let mut key = if sort.starts_with("amount") {
+        vec![record.launch_amount.to_string(), record.launch_time.to_string()]
+    } else if sort.starts_with("slot") {
+        vec![record.launch_block.to_string()]
+    } else {
+        vec![record.launch_time.to_string()]
+    };
+    key.push(record.launch_tx_hash.clone());
+    key.push(record.log_index.to_string());
+    key
+}
+
+fn launch_record_key_args(sort: &str, key: &[String]) -> anyhow::Result<Vec<rbs::Value>> {
+    let mut args = vec![];
+    let rest = match (sort.starts_with("amount"), key) {
+        (true, [amount, time, rest @ ..]) => {
+            let amount = Decimal::from_str(amount)?;
+            if sort == "amount_asc" {
+                args.push(rbs::to_value!(amount.clone()));
+            }
+            args.push(rbs::to_value!(amount));
+            args.push(rbs::to_value!(time.parse::<i64>()?));
+            rest
+        }
+        (false, [first, rest @ ..]) => {
+            args.push(rbs::to_value!(first.parse::<i64>()?));
+            rest
+        }
+        _ => anyhow::bail!("Invalid cursor"),
+    };
+    let [tx_hash, log_index] = rest else {
+        anyhow::bail!("Invalid cursor");
+    };
+    args.push(rbs::to_value!(tx_hash.clone()));
+    args.push(rbs::to_value!(log_index.parse::<i32>()?));
+    Ok(args)
+}
+
+const LAUNCH_RECORDS_FILTER: &str = "(?::text is null or address = ?) \
+    and (?::bigint is null or launch_time >= ?) and (?::bigint is null or launch_time < ?) \
+    and (?::bigint is null or launch_block >= ?) and (?::bigint is null or launch_block < ?) \
+    and (?::numeric is null or launch_amount >= ?)";
+
+fn launch_records_filter_args(filter: &LaunchRecordFilter) -> Vec<rbs::Value> {
+    let mut args = vec![];
+    for value in [rbs::to_value!(filter.address.clone()),
+                  rbs::to_value!(filter.start_time),
+                  rbs::to_value!(filter.end_time),
+                  rbs::to_value!(filter.start_slot),
+                  rbs::to_value!(filter.end_slot),
+                  rbs::to_value!(filter.min_amount.clone())] {
+        args.push(value.clone());
+        args.push(value);
+    }
+    args
+}
+
+// Returns the filtered row count and the page, fetched one row past the page size so the caller can
+// tell if there is a next page. A cursor from the same sort replaces the offset.
+pub async fn get_launch_records(rb: &RBatis,filter: &LaunchRecordFilter,sort: &str,page: &PageRequest,
+                                after: Option<&Vec<String>>) -> anyhow::Result<(u64,Vec<LaunchRecord>)> {
+    let (order, after_condition) = launch_records_sort(sort).ok_or(anyhow::anyhow!("unknown sort {sort}"))?;
+    let mut args = launch_records_filter_args(filter);
+    let mut sql = format!("select * from launch_records where {LAUNCH_RECORDS_FILTER}");
+    let offset = match after {
+        Some(key) => {
+            args.extend(launch_record_key_args(sort, key)?);
+            sql += &format!(" and {after_condition}");
+            0
+        }
+        None => page.offset(),
+    };
+    sql += &format!(" order by {order} offset ? limit ?");
+    args.push(rbs::to_value!(offset));
+    args.push(rbs::to_value!(page.page_size + 1));
     let ret: Vec<LaunchRecord> = rb
-        .query_decode("select * from launch_records order by launch_time desc offset ? limit ? ",
-                      vec![rbs::to_value!(offset),rbs::to_value!(PAGE_SIZE)])
+        .query_decode(&sql, args)
+        .await?;
+    let count: u64 = rb
+        .query_decode(&format!("select count(1) from launch_records where {LAUNCH_RECORDS_FILTER}"),
+                      launch_records_filter_args(filter)).await?;
+    Ok((count,ret))
+}
+
+pub async fn get_all_launch_records(rb: &RBatis) -> anyhow::Result<Vec<LaunchRecord>> {
+    let ret: Vec<LaunchRecord> = rb
+        .query_decode("select * from launch_records order by launch_block asc,launch_tx_hash asc,log_index asc",
+                      vec![])
         .await?;
-    let count: HashMap<String,usize> = rb
-        .query_decode("select count(1) from launch_records",vec![]).await?;
-    let count = count.get("count").unwrap();
-    let pg_count = count / PAGE_SIZE as usize;
-    Ok((pg_count,ret))
+    Ok(ret)
 }
 
-pub async fn get_pre_launch_record(rb: &RBatis,address: &str,launch_time:i64) -> anyhow::Result<Vec<LaunchRecord>> {
+// Launches of the sender that come before record, launches in the same second are ordered by tx hash and log index.
+pub async fn get_pre_launch_record(rb: &RBatis,record: &LaunchRecord) -> anyhow::Result<Vec<LaunchRecord>> {
     let ret: Vec<LaunchRecord> = rb
-        .query_decode("select *  from launch_records where address = ? and launch_time < ? ",
-                      vec![rbs::to_value!(address),rbs::to_value!(launch_time)])
+        .query_decode("select *  from launch_records where address = ? \
+        and (launch_time,launch_tx_hash,log_index) < (?,?,?) ",
+                      vec![rbs::to_value!(record.address.clone()),
+                           rbs::to_value!(record.launch_time),
+                           rbs::to_value!(record.launch_tx_hash.clone()),
+                           rbs::to_value!(record.log_index)])
+        .await?;
+    Ok(ret)
+}
+
+pub(crate) async fn save_launch_record_checks(rb: &mut RBatis, checks: &Vec<LaunchRecordCheck>) -> anyhow::Result<()> {
+    for check in checks {
+        rb.exec("insert into launch_record_checks (launch_tx_hash,log_index,address,cumulative_amount,violation) \
+        values (?,?,?,?,?) on conflict (launch_tx_hash,log_index) do nothing",
+                vec![rbs::to_value!(check.launch_tx_hash.clone()),
+                     rbs::to_value!(check.log_index),
+                     rbs::to_value!(check.address.clone()),
+                     rbs::to_value!(check.cumulative_amount.clone()),
+                     rbs::to_value!(check.violation.clone()),
+                ]).await?;
+    }
+    Ok(())
+}
+
+pub async fn get_account_total_mint(rb: &RBatis, address: &str) -> anyhow::Result<Decimal> {
+    let total_mint: Decimal = rb
+        .query_decode("select coalesce(sum(launch_amount),0) as total_amount from launch_records where address = ?",
+                      vec![rbs::to_value!(address)])
+        .await?;
+    Ok(total_mint)
+}
+
+pub async fn get_account_violations(rb: &RBatis, address: &str) -> anyhow::Result<Vec<LaunchRecordCheck>> {
+    let ret: Vec<LaunchRecordCheck> = rb
+        .query_decode("select * from launch_record_checks where address = ? and violation is not null",
+                      vec![rbs::to_value!(address)])
         .await?;
     Ok(ret)
 }
@@ -247,7 +559,7 @@ pub async fn get_accounts(rb: &RBatis,addresses: Vec<String>) ->anyhow::Result<V
     Ok(ret)
 }
 
-pub(crate) async fn update_user_points(rb: &mut RBatis, records: Vec<UserPoint>) -> anyhow::Result<()> {
+pub(crate) async fn update_user_points(rb: &dyn Executor, records: Vec<UserPoint>) -> anyhow::Result<()> {
     if records.is_empty() {
         return Ok(());
     }
@@ -262,9 +574,33 @@ pub(crate) async fn update_user_points(rb: &mut RBatis, records: Vec<UserPoint>)
     Ok(())
 }
 
-pub(crate) async fn db_bind_sol_address(rb: &mut RBatis, query_account: Option<QueryAccount>,
-                                        new_account: Option<Account>,inviter_address: Option<String>) -> anyhow::Result<()> {
-    let tx = rb.acquire_begin().await?;
+// The new account gets the first of invite_codes that no account or campaign code owns, its invite_code field
+// is ignored. The bind points of the new account are appended and added to the point cache only when the
+// account is created here.
+// Returns the invite code the new account was saved with.
+pub(crate) async fn db_bind_sol_address(rb: &mut RBatis, nonce: &str, now: i64, query_account: Option<QueryAccount>,
+                                        new_account: Option<(Account,Vec<PointEvent>)>,invite_codes: &[String],
+                                        campaign_code: Option<String>) -> anyhow::Result<Option<String>> {
+    let tx = begin_tx(rb).await?;
+    //0.consume the challenge, a replayed or expired nonce binds nothing
+    let ret = tx.exec("update bind_challenges set consumed = true where nonce = ? and consumed = false and expire_time > ?",
+                      vec![rbs::to_value!(nonce),rbs::to_value!(now)]).await?;
+    if ret.rows_affected == 0 {
+        tx.rollback().await?;
+        return Err(BindChallengeUnavailable(nonce.to_string()).into());
+    }
+    //0.use the campaign code at the create time of the account, the last free use can only be taken once
+    if let Some(code) = campaign_code {
+        let now = new_account.as_ref().map(|(a,_)| a.create_time).unwrap_or_default();
+        let ret = tx.exec("update invite_codes set used_count = used_count + 1 where code = ? and revoked = false \
+        and (max_uses is null or used_count < max_uses) \
+        and (start_time is null or start_time <= ?) and (end_time is null or end_time > ?)",
+                          vec![rbs::to_value!(&code),rbs::to_value!(now),rbs::to_value!(now)]).await?;
+        if ret.rows_affected == 0 {
+            tx.rollback().await?;
+            return Err(InviteCodeUnavailable(code).into());
+        }
+    }
     //1.update the sol address of query account
     if let Some(query_account) = query_account {
         tx.exec("update query_accounts set claim_sol_address = ? where address = ? ",
@@ -272,24 +608,75 @@ pub(crate) async fn db_bind_sol_address(rb: &mut RBatis, query_account: Option<Q
                      rbs::to_value!(query_account.address),
                 ]).await?;
     }
-    //2.save new account
-    if let Some(new_account) = new_account {
-        tx.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,?,?) on conflict (address) do nothing",
-                vec![rbs::to_value!(new_account.address.clone()),
-                     rbs::to_value!(new_account.invite_code.clone()),
-                     rbs::to_value!(new_account.inviter.clone()),
-                     rbs::to_value!(new_account.create_time),
-                     rbs::to_value!(new_account.point),
-                ]).await?;
-    }
-    //3.update inviter's point
-    if let Some(inviter_address) = inviter_address {
-        tx.exec("update accounts set point = point + ? where address = ?",
-                vec![ rbs::to_value!(1000),rbs::to_value!(inviter_address)]).await?;
+    //2.save new account, a candidate code taken by a concurrent bind moves on to the next one
+    let mut saved_code = None;
+    if let Some((new_account,point_events)) = new_account {
+        if !point_events.is_empty() {
+            lock_point_ledger(&tx).await?;
+        }
+        for code in invite_codes {
+            let ret = tx.exec("insert into accounts (address,invite_code,inviter,create_time,point,invite_code_used) \
+            select ?,?,?,?,?,? where not exists (select 1 from invite_codes where code = ?) on conflict do nothing",
+                    vec![rbs::to_value!(new_account.address.clone()),
+                         rbs::to_value!(code),
+                         rbs::to_value!(new_account.inviter.clone()),
+                         rbs::to_value!(new_account.create_time),
+                         rbs::to_value!(new_account.point),
+                         rbs::to_value!(new_account.invite_code_used.clone()),
+                         rbs::to_value!(code),
+                    ]).await?;
+            if ret.rows_affected > 0 {
+                insert_point_events(&tx, &point_events).await?;
+                for event in &point_events {
+                    tx.exec("update accounts set point = point + ? where address = ?",
+                            vec![rbs::to_value!(event.delta),rbs::to_value!(event.address.clone())]).await?;
+                }
+                saved_code = Some(code.clone());
+                break;
+            }
+            // the address itself may have been bound meanwhile, it keeps the code it got
+            let account: Option<Account> = tx
+                .query_decode("select * from accounts where address = ? limit 1",
+                              vec![rbs::to_value!(new_account.address.clone())])
+                .await?;
+            if let Some(account) = account {
+                saved_code = Some(account.invite_code);
+                break;
+            }
+            log::info!("invite code {code} of {} is taken",new_account.address);
+        }
+        if saved_code.is_none() {
+            tx.rollback().await?;
+            anyhow::bail!("no free invite code for {} after {} candidates",new_account.address,invite_codes.len());
+        }
     }
     tx.commit().await?;
-    Ok(())
+    Ok(saved_code)
+}
+// Returned by db_bind_sol_address when the challenge was consumed or expired since it was checked.
+#[derive(Debug)]
+pub struct BindChallengeUnavailable(pub String);
+
+impl std::fmt::Display for BindChallengeUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bind challenge {} is consumed or expired", self.0)
+    }
+}
+
+impl std::error::Error for BindChallengeUnavailable {}
+
+// Returned by db_bind_sol_address when the campaign code ran out or closed since it was checked.
+#[derive(Debug)]
+pub struct InviteCodeUnavailable(pub String);
+
+impl std::fmt::Display for InviteCodeUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invite code {} is exhausted, expired or revoked", self.0)
+    }
 }
+
+impl std::error::Error for InviteCodeUnavailable {}
+
 pub async fn db_get_queried_addresses_number(rb:&RBatis) -> anyhow::Result<u64> {
     let queried_number: u64 = rb
         .query_decode("select count(1) from query_accounts",vec![])
@@ -307,4 +694,1429 @@ pub async fn db_get_total_claimed_amount(rb:&RBatis) -> anyhow::Result<Decimal>
         .query_decode("select sum(claimed_amount) from claimed_accounts",vec![])
         .await?;
     Ok(claimed_number)
-}
\ No newline at end of file
+}
+pub(crate) async fn save_refund_records(rb: &mut RBatis, records: &Vec<RefundRecord>) -> anyhow::Result<()> {
+    for record in records {
+        rb.exec("insert into refund_records (launch_tx_hash,log_index,address,refund_amount,refund_lamports,status,update_time) \
+        values (?,?,?,?,?,?,?) on conflict (launch_tx_hash,log_index) do nothing",
+                vec![rbs::to_value!(record.launch_tx_hash.clone()),
+                     rbs::to_value!(record.log_index),
+                     rbs::to_value!(record.address.clone()),
+                     rbs::to_value!(record.refund_amount.clone()),
+                     rbs::to_value!(record.refund_lamports),
+                     rbs::to_value!(record.status.clone()),
+                     rbs::to_value!(record.update_time),
+                ]).await?;
+    }
+    Ok(())
+}
+
+pub async fn get_unbatched_refund_records(rb: &RBatis) -> anyhow::Result<Vec<RefundRecord>> {
+    let ret: Vec<RefundRecord> = rb
+        .query_decode("select * from refund_records where batch_id is null and status = 'pending' order by address asc",
+                      vec![])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_refund_records_by_status(rb: &RBatis, status: &str) -> anyhow::Result<Vec<RefundRecord>> {
+    let ret: Vec<RefundRecord> = rb
+        .query_decode("select * from refund_records where status = ? order by batch_id asc,address asc",
+                      vec![rbs::to_value!(status)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_next_refund_batch_id(rb: &RBatis) -> anyhow::Result<i64> {
+    let batch_id: i64 = rb
+        .query_decode("select nextval('refund_batch_id_seq')", vec![])
+        .await?;
+    Ok(batch_id)
+}
+
+// Begins a transaction that is rolled back if it is dropped without a commit,
+// so an early return on error does not hand the connection back to the pool mid-transaction.
+pub(crate) async fn begin_tx(rb: &RBatis) -> anyhow::Result<RBatisTxExecutorGuard> {
+    let tx = rb.acquire_begin().await?;
+    Ok(tx.defer_async(|tx| async move {
+        if !tx.done() {
+            if let Err(e) = tx.rollback().await {
+                log::error!("rollback failed,{e}");
+            }
+        }
+    }))
+}
+
+pub(crate) async fn assign_refund_batch(rb: &mut RBatis, batch_id: i64, addresses: &Vec<String>, update_time: i64) -> anyhow::Result<()> {
+    let tx = begin_tx(rb).await?;
+    for address in addresses {
+        tx.exec("update refund_records set batch_id = ?,update_time = ? \
+        where address = ? and batch_id is null and status = 'pending'",
+                vec![rbs::to_value!(batch_id),
+                     rbs::to_value!(update_time),
+                     rbs::to_value!(address),
+                ]).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub(crate) async fn update_refund_batch_signature(rb: &mut RBatis, batch_id: i64, signature: &str, update_time: i64) -> anyhow::Result<u64> {
+    let ret = rb.exec("update refund_records set tx_signature = ?,status = 'sent',update_time = ? \
+    where batch_id = ? and status = 'pending'",
+            vec![rbs::to_value!(signature),
+                 rbs::to_value!(update_time),
+                 rbs::to_value!(batch_id),
+            ]).await?;
+    Ok(ret.rows_affected)
+}
+
+pub(crate) async fn update_refund_status_by_signature(rb: &mut RBatis, signature: &str, status: &str, update_time: i64) -> anyhow::Result<()> {
+    rb.exec("update refund_records set status = ?,update_time = ?, \
+    tx_signature = case when ? = 'pending' then null else tx_signature end \
+    where tx_signature = ?",
+            vec![rbs::to_value!(status),
+                 rbs::to_value!(update_time),
+                 rbs::to_value!(status),
+                 rbs::to_value!(signature),
+            ]).await?;
+    Ok(())
+}
+
+pub(crate) async fn replace_allocations(rb: &mut RBatis, allocations: &Vec<Allocation>) -> anyhow::Result<()> {
+    let tx = begin_tx(rb).await?;
+    tx.exec("delete from allocations", vec![]).await?;
+    for allocation in allocations {
+        tx.exec("insert into allocations (address,contributed_amount,token_amount,pricing_rule,update_time) \
+        values (?,?,?,?,?)",
+                vec![rbs::to_value!(allocation.address.clone()),
+                     rbs::to_value!(allocation.contributed_amount.clone()),
+                     rbs::to_value!(allocation.token_amount.clone()),
+                     rbs::to_value!(allocation.pricing_rule.clone()),
+                     rbs::to_value!(allocation.update_time),
+                ]).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_allocation_by_address(rb: &RBatis, address: &str) -> anyhow::Result<Option<Allocation>> {
+    let ret: Option<Allocation> = rb
+        .query_decode("select * from allocations where address = ? limit 1", vec![rbs::to_value!(address)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_all_allocations(rb: &RBatis) -> anyhow::Result<Vec<Allocation>> {
+    let ret: Vec<Allocation> = rb
+        .query_decode("select * from allocations order by address asc", vec![])
+        .await?;
+    Ok(ret)
+}
+
+pub(crate) async fn save_merkle_distribution(rb: &mut RBatis, distribution: &MerkleDistribution,
+                                             leaves: &[MerkleLeaf]) -> anyhow::Result<i64> {
+    let tx = begin_tx(rb).await?;
+    let version: i64 = tx
+        .query_decode("insert into merkle_distributions (root,total_amount,num_nodes,create_time) \
+        values (?,?,?,?) returning version",
+                      vec![rbs::to_value!(distribution.root.clone()),
+                           rbs::to_value!(distribution.total_amount.clone()),
+                           rbs::to_value!(distribution.num_nodes),
+                           rbs::to_value!(distribution.create_time),
+                      ]).await?;
+    for chunk in leaves.chunks(1000) {
+        let mut sql_str = "insert into merkle_leaves (version,idx,claimant,amount,proof) values ".to_string();
+        for leaf in chunk {
+            sql_str += &format!("({},{},'{}',{},'{}'),", version, leaf.idx, leaf.claimant, leaf.amount, leaf.proof);
+        }
+        sql_str.truncate(sql_str.len() - 1);
+        tx.exec(&sql_str, vec![]).await?;
+    }
+    tx.commit().await?;
+    Ok(version)
+}
+
+pub async fn get_merkle_distribution(rb: &RBatis, version: i64) -> anyhow::Result<Option<MerkleDistribution>> {
+    let ret: Option<MerkleDistribution> = rb
+        .query_decode("select * from merkle_distributions where version = ? limit 1", vec![rbs::to_value!(version)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_latest_merkle_distribution(rb: &RBatis) -> anyhow::Result<Option<MerkleDistribution>> {
+    let ret: Option<MerkleDistribution> = rb
+        .query_decode("select * from merkle_distributions order by version desc limit 1", vec![])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_merkle_leaves(rb: &RBatis, version: i64) -> anyhow::Result<Vec<MerkleLeaf>> {
+    let ret: Vec<MerkleLeaf> = rb
+        .query_decode("select * from merkle_leaves where version = ? order by idx asc", vec![rbs::to_value!(version)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_merkle_leaf_by_claimant(rb: &RBatis, version: i64, claimant: &str) -> anyhow::Result<Option<MerkleLeaf>> {
+    let ret: Option<MerkleLeaf> = rb
+        .query_decode("select * from merkle_leaves where version = ? and claimant = ? limit 1",
+                      vec![rbs::to_value!(version), rbs::to_value!(claimant)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_latest_snapshot_version(rb: &RBatis) -> anyhow::Result<Option<i64>> {
+    let ret: Option<EligibilitySnapshot> = rb
+        .query_decode("select * from eligibility_snapshots order by version desc limit 1", vec![])
+        .await?;
+    Ok(ret.map(|s| s.version))
+}
+
+pub async fn get_snapshot_entries(rb: &RBatis, version: i64) -> anyhow::Result<Vec<EligibilitySnapshotEntry>> {
+    let ret: Vec<EligibilitySnapshotEntry> = rb
+        .query_decode("select * from eligibility_snapshot_entries where version = ?", vec![rbs::to_value!(version)])
+        .await?;
+    Ok(ret)
+}
+
+// Writes the snapshot, re-evaluates its accounts and resets the ones it dropped in one transaction, so a failed
+// import leaves no partial version. Bound accounts keep their amount, claims only go to bound accounts.
+pub(crate) async fn save_eligibility_snapshot(rb: &mut RBatis, snapshot: &EligibilitySnapshot, entries: &[EligibilitySnapshotEntry],
+                                              accounts: &[QueryAccount], removed: &[String]) -> anyhow::Result<i64> {
+    let tx = begin_tx(rb).await?;
+    let version: i64 = tx
+        .query_decode("insert into eligibility_snapshots (source,row_count,total_amount,create_time) \
+        values (?,?,?,?) returning version",
+                      vec![rbs::to_value!(snapshot.source.clone()),
+                           rbs::to_value!(snapshot.row_count),
+                           rbs::to_value!(snapshot.total_amount.clone()),
+                           rbs::to_value!(snapshot.create_time),
+                      ]).await?;
+    for chunk in entries.chunks(1000) {
+        let mut sql_str = "insert into eligibility_snapshot_entries (version,address,amount,reason) values ".to_string();
+        let mut args = vec![];
+        for entry in chunk {
+            sql_str += "(?,?,?,?),";
+            args.push(rbs::to_value!(version));
+            args.push(rbs::to_value!(entry.address.clone()));
+            args.push(rbs::to_value!(entry.amount.clone()));
+            args.push(rbs::to_value!(entry.reason.clone()));
+        }
+        sql_str.truncate(sql_str.len() - 1);
+        tx.exec(&sql_str, args).await?;
+    }
+    for chunk in accounts.chunks(1000) {
+        let mut sql_str = "insert into query_accounts (address,claimable_amount,query_time,rules_version,breakdown) values ".to_string();
+        let mut args = vec![];
+        for account in chunk {
+            sql_str += "(?,?,?,?,?),";
+            args.push(rbs::to_value!(account.address.clone()));
+            args.push(rbs::to_value!(account.claimable_amount.clone()));
+            args.push(rbs::to_value!(account.query_time));
+            args.push(rbs::to_value!(account.rules_version.clone()));
+            args.push(rbs::to_value!(account.breakdown.clone()));
+        }
+        sql_str.truncate(sql_str.len() - 1);
+        sql_str += " on conflict(address) do update set claimable_amount = excluded.claimable_amount,\
+        query_time = excluded.query_time,rules_version = excluded.rules_version,breakdown = excluded.breakdown \
+        where query_accounts.claim_sol_address is null";
+        tx.exec(&sql_str, args).await?;
+    }
+    // the next get_eligible of dropped addresses evaluates the rules again
+    for chunk in removed.chunks(1000) {
+        let mut sql_str = "update query_accounts set rules_version = null where claim_sol_address is null and address in (".to_string();
+        let mut args = vec![];
+        for address in chunk {
+            sql_str += "?,";
+            args.push(rbs::to_value!(address.clone()));
+        }
+        sql_str.truncate(sql_str.len() - 1);
+        sql_str += ")";
+        tx.exec(&sql_str, args).await?;
+    }
+    tx.commit().await?;
+    Ok(version)
+}
+
+pub async fn get_orbiter_gas_cache(rb: &RBatis, address: &str) -> anyhow::Result<Option<OrbiterGasCache>> {
+    let ret: Option<OrbiterGasCache> = rb
+        .query_decode("select * from orbiter_gas_cache where address = ? limit 1", vec![rbs::to_value!(address)])
+        .await?;
+    Ok(ret)
+}
+
+pub(crate) async fn save_orbiter_gas_cache(rb: &mut RBatis, cache: &OrbiterGasCache) -> anyhow::Result<()> {
+    rb.exec("insert into orbiter_gas_cache (address,gas,fetch_time) values (?,?,?) \
+    on conflict (address) do update set gas = excluded.gas,fetch_time = excluded.fetch_time",
+            vec![rbs::to_value!(cache.address.clone()),
+                 rbs::to_value!(cache.gas.clone()),
+                 rbs::to_value!(cache.fetch_time),
+            ]).await?;
+    Ok(())
+}
+
+pub(crate) async fn save_bind_challenge(rb: &mut RBatis, challenge: &BindChallenge) -> anyhow::Result<()> {
+    rb.exec("insert into bind_challenges (nonce,address,sol_address,message,expire_time,consumed,create_time) \
+    values (?,?,?,?,?,?,?)",
+            vec![rbs::to_value!(challenge.nonce.clone()),
+                 rbs::to_value!(challenge.address.clone()),
+                 rbs::to_value!(challenge.sol_address.clone()),
+                 rbs::to_value!(challenge.message.clone()),
+                 rbs::to_value!(challenge.expire_time),
+                 rbs::to_value!(challenge.consumed),
+                 rbs::to_value!(challenge.create_time),
+            ]).await?;
+    Ok(())
+}
+
+pub async fn get_bind_challenge(rb: &RBatis, nonce: &str) -> anyhow::Result<Option<BindChallenge>> {
+    let ret: Option<BindChallenge> = rb
+        .query_decode("select * from bind_challenges where nonce = ? limit 1", vec![rbs::to_value!(nonce)])
+        .await?;
+    Ok(ret)
+}
+
+pub(crate) async fn save_login_challenge(rb: &mut RBatis, challenge: &LoginChallenge) -> anyhow::Result<()> {
+    rb.exec("insert into login_challenges (nonce,address,message,expire_time,consumed,create_time) values (?,?,?,?,?,?)",
+            vec![rbs::to_value!(challenge.nonce.clone()),
+                 rbs::to_value!(challenge.address.clone()),
+                 rbs::to_value!(challenge.message.clone()),
+                 rbs::to_value!(challenge.expire_time),
+                 rbs::to_value!(challenge.consumed),
+                 rbs::to_value!(challenge.create_time),
+            ]).await?;
+    Ok(())
+}
+
+// Marks the challenge consumed and returns it, None when it is unknown, used or expired.
+pub(crate) async fn consume_login_challenge(rb: &mut RBatis, nonce: &str, now: i64) -> anyhow::Result<Option<LoginChallenge>> {
+    let ret: Vec<LoginChallenge> = rb
+        .query_decode("update login_challenges set consumed = true \
+        where nonce = ? and consumed = false and expire_time >= ? returning *",
+                      vec![rbs::to_value!(nonce), rbs::to_value!(now)])
+        .await?;
+    Ok(ret.into_iter().next())
+}
+
+pub(crate) async fn save_session(rb: &mut RBatis, session: &Session) -> anyhow::Result<()> {
+    rb.exec("insert into sessions (token_hash,address,expire_time,create_time) values (?,?,?,?)",
+            vec![rbs::to_value!(session.token_hash.clone()),
+                 rbs::to_value!(session.address.clone()),
+                 rbs::to_value!(session.expire_time),
+                 rbs::to_value!(session.create_time),
+            ]).await?;
+    rb.exec("delete from sessions where expire_time < ?", vec![rbs::to_value!(session.create_time)]).await?;
+    Ok(())
+}
+
+pub async fn get_session(rb: &RBatis, token_hash: &str, now: i64) -> anyhow::Result<Option<Session>> {
+    let ret: Option<Session> = rb
+        .query_decode("select * from sessions where token_hash = ? and expire_time >= ? limit 1",
+                      vec![rbs::to_value!(token_hash), rbs::to_value!(now)])
+        .await?;
+    Ok(ret)
+}
+
+pub(crate) async fn delete_session(rb: &mut RBatis, token_hash: &str) -> anyhow::Result<()> {
+    rb.exec("delete from sessions where token_hash = ?", vec![rbs::to_value!(token_hash)]).await?;
+    Ok(())
+}
+
+// Entries are only ever added, a policy change does not rewrite what was already accrued.
+pub(crate) async fn save_rebate_entries(rb: &mut RBatis, entries: &Vec<RebateEntry>) -> anyhow::Result<u64> {
+    let mut saved = 0;
+    for entry in entries {
+        let ret = rb.exec("insert into rebate_entries (launch_tx_hash,log_index,inviter,address,level,launch_amount,rate,rebate_amount,create_time) \
+        values (?,?,?,?,?,?,?,?,?) on conflict (launch_tx_hash,log_index,inviter) do nothing",
+                vec![rbs::to_value!(entry.launch_tx_hash.clone()),
+                     rbs::to_value!(entry.log_index),
+                     rbs::to_value!(entry.inviter.clone()),
+                     rbs::to_value!(entry.address.clone()),
+                     rbs::to_value!(entry.level),
+                     rbs::to_value!(entry.launch_amount.clone()),
+                     rbs::to_value!(entry.rate.clone()),
+                     rbs::to_value!(entry.rebate_amount.clone()),
+                     rbs::to_value!(entry.create_time),
+                ]).await?;
+        saved += ret.rows_affected;
+    }
+    Ok(saved)
+}
+
+// Everything accrued in the ledger, paid or not.
+pub async fn get_total_rebate(rb: &RBatis) -> anyhow::Result<Decimal> {
+    let total_rebate: Decimal = rb
+        .query_decode("select coalesce(sum(rebate_amount),0) as total_rebate from rebate_entries", vec![])
+        .await?;
+    Ok(total_rebate)
+}
+
+// What every inviter has accrued so far, the cap of the next ledger sync starts from here.
+pub async fn get_recorded_rebate_totals(rb: &RBatis) -> anyhow::Result<Vec<RebateInviterTotal>> {
+    let ret: Vec<RebateInviterTotal> = rb
+        .query_decode("select inviter,sum(rebate_amount) as amount from rebate_entries group by inviter order by inviter", vec![])
+        .await?;
+    Ok(ret)
+}
+
+// Flagged inviters are left out until their flag is cleared.
+pub async fn get_unbatched_rebate_totals(rb: &RBatis) -> anyhow::Result<Vec<RebateInviterTotal>> {
+    let ret: Vec<RebateInviterTotal> = rb
+        .query_decode("select inviter,sum(rebate_amount) as amount from rebate_entries \
+        where batch_id is null and inviter not in (select address from abuse_flags where status <> 'cleared') \
+        group by inviter having sum(rebate_amount) > 0 order by inviter", vec![])
+        .await?;
+    Ok(ret)
+}
+
+// Creates the batch and moves the unbatched entries of the inviters into it. carry gives the part of what an
+// inviter is owed that the transfer can not move, it leaves the batch as a pair of entries and stays unbatched.
+pub(crate) async fn create_rebate_batch(rb: &mut RBatis, inviters: &Vec<String>, carry: impl Fn(&BigDecimal) -> anyhow::Result<BigDecimal>,
+                                        create_time: i64) -> anyhow::Result<RebateBatch> {
+    let tx = begin_tx(rb).await?;
+    let batch_id: i64 = tx
+        .query_decode("insert into rebate_batches (status,total_amount,create_time,update_time) \
+        values ('pending',0,?,?) returning batch_id",
+                      vec![rbs::to_value!(create_time),rbs::to_value!(create_time)])
+        .await?;
+    for inviter in inviters {
+        tx.exec("update rebate_entries set batch_id = ? where inviter = ? and batch_id is null",
+                vec![rbs::to_value!(batch_id),rbs::to_value!(inviter)]).await?;
+        let owed: Decimal = tx
+            .query_decode("select coalesce(sum(rebate_amount),0) from rebate_entries where batch_id = ? and inviter = ?",
+                          vec![rbs::to_value!(batch_id),rbs::to_value!(inviter)])
+            .await?;
+        let remainder = carry(&to_big_decimal(&owed))?;
+        if remainder.is_zero() {
+            continue;
+        }
+        let carry_tx_hash = format!("carry:{batch_id}");
+        for (log_index,amount,entry_batch_id) in [(0,-remainder.clone(),Some(batch_id)),(1,remainder,None)] {
+            let amount = Decimal::from_str(&amount.to_string())?;
+            tx.exec("insert into rebate_entries (launch_tx_hash,log_index,inviter,address,level,launch_amount,rate,rebate_amount,batch_id,create_time) \
+            values (?,?,?,?,0,0,0,?,?,?)",
+                    vec![rbs::to_value!(carry_tx_hash.clone()),
+                         rbs::to_value!(log_index),
+                         rbs::to_value!(inviter),
+                         rbs::to_value!(inviter),
+                         rbs::to_value!(amount),
+                         rbs::to_value!(entry_batch_id),
+                         rbs::to_value!(create_time),
+                    ]).await?;
+        }
+    }
+    let batch: RebateBatch = tx
+        .query_decode("update rebate_batches set total_amount = \
+        (select coalesce(sum(rebate_amount),0) from rebate_entries where batch_id = ?) \
+        where batch_id = ? returning *",
+                      vec![rbs::to_value!(batch_id),rbs::to_value!(batch_id)])
+        .await?;
+    tx.commit().await?;
+    Ok(batch)
+}
+
+pub async fn get_rebate_batch(rb: &RBatis, batch_id: i64) -> anyhow::Result<Option<RebateBatch>> {
+    let ret: Option<RebateBatch> = rb
+        .query_decode("select * from rebate_batches where batch_id = ? limit 1", vec![rbs::to_value!(batch_id)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_rebate_batches_by_status(rb: &RBatis, status: &str) -> anyhow::Result<Vec<RebateBatch>> {
+    let ret: Vec<RebateBatch> = rb
+        .query_decode("select * from rebate_batches where status = ? order by batch_id", vec![rbs::to_value!(status)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_rebate_batch_totals(rb: &RBatis, batch_id: i64) -> anyhow::Result<Vec<RebateInviterTotal>> {
+    let ret: Vec<RebateInviterTotal> = rb
+        .query_decode("select inviter,sum(rebate_amount) as amount from rebate_entries \
+        where batch_id = ? group by inviter order by inviter", vec![rbs::to_value!(batch_id)])
+        .await?;
+    Ok(ret)
+}
+
+pub(crate) async fn update_rebate_batch_signature(rb: &mut RBatis, batch_id: i64, signature: &str, update_time: i64) -> anyhow::Result<u64> {
+    let ret = rb.exec("update rebate_batches set tx_signature = ?,status = 'sent',update_time = ? \
+    where batch_id = ? and status = 'pending'",
+            vec![rbs::to_value!(signature),
+                 rbs::to_value!(update_time),
+                 rbs::to_value!(batch_id),
+            ]).await?;
+    Ok(ret.rows_affected)
+}
+
+// Going back to pending forgets the signature so the batch can be paid again.
+pub(crate) async fn update_rebate_batch_status(rb: &mut RBatis, batch_id: i64, status: &str, update_time: i64) -> anyhow::Result<u64> {
+    let ret = rb.exec("update rebate_batches set status = ?,update_time = ?, \
+    tx_signature = case when ? = 'pending' then null else tx_signature end \
+    where batch_id = ?",
+            vec![rbs::to_value!(status),
+                 rbs::to_value!(update_time),
+                 rbs::to_value!(status),
+                 rbs::to_value!(batch_id),
+            ]).await?;
+    Ok(ret.rows_affected)
+}
+
+pub async fn get_rebate_summary(rb: &RBatis, inviter: &str) -> anyhow::Result<RebateSummary> {
+    let ret: RebateSummary = rb
+        .query_decode("select coalesce(sum(e.rebate_amount),0) as accrued, \
+        coalesce(sum(e.rebate_amount) filter (where b.status = 'confirmed'),0) as paid, \
+        coalesce(sum(e.rebate_amount) filter (where b.status in ('pending','sent')),0) as in_payout \
+        from rebate_entries e left join rebate_batches b on b.batch_id = e.batch_id \
+        where e.inviter = ?", vec![rbs::to_value!(inviter)])
+        .await?;
+    Ok(ret)
+}
+
+// Reads the profile of the account in one repeatable read transaction, so the parts agree with each other
+// while the watcher keeps writing.
+pub async fn get_account_summary(rb: &RBatis, address: &str) -> anyhow::Result<AccountSummary> {
+    let tx = begin_tx(rb).await?;
+    tx.exec("set transaction isolation level repeatable read read only", vec![]).await?;
+    let account: Option<Account> = tx
+        .query_decode("select * from accounts where address = ? limit 1", vec![rbs::to_value!(address)])
+        .await?;
+    let bound_accounts: Vec<QueryAccount> = tx
+        .query_decode("select * from query_accounts where claim_sol_address = ? order by address",
+                      vec![rbs::to_value!(address)])
+        .await?;
+    let total_mint: Decimal = tx
+        .query_decode("select coalesce(sum(launch_amount),0) as total_amount from launch_records where address = ?",
+                      vec![rbs::to_value!(address)])
+        .await?;
+    let invitee_count: u64 = tx
+        .query_decode("select count(1) from accounts where inviter = ?", vec![rbs::to_value!(address)])
+        .await?;
+    let rebate: RebateSummary = tx
+        .query_decode("select coalesce(sum(e.rebate_amount),0) as accrued, \
+        coalesce(sum(e.rebate_amount) filter (where b.status = 'confirmed'),0) as paid, \
+        coalesce(sum(e.rebate_amount) filter (where b.status in ('pending','sent')),0) as in_payout \
+        from rebate_entries e left join rebate_batches b on b.batch_id = e.batch_id \
+        where e.inviter = ?", vec![rbs::to_value!(address)])
+        .await?;
+    let claim_leaf: Option<MerkleLeaf> = tx
+        .query_decode("select * from merkle_leaves where claimant = ? \
+        and version = (select max(version) from merkle_distributions) limit 1", vec![rbs::to_value!(address)])
+        .await?;
+    let claimed: Vec<ClaimedAccount> = tx
+        .query_decode("select * from claimed_accounts where address = ? \
+        or address in (select address from query_accounts where claim_sol_address = ?) order by claimed_time",
+                      vec![rbs::to_value!(address),rbs::to_value!(address)])
+        .await?;
+    tx.commit().await?;
+    Ok(AccountSummary {
+        account,
+        bound_accounts,
+        total_mint,
+        invitee_count,
+        rebate,
+        claim_leaf,
+        claimed,
+    })
+}
+
+pub(crate) async fn save_invite_code(rb: &mut RBatis, code: &InviteCode) -> anyhow::Result<u64> {
+    let ret = rb.exec("insert into invite_codes (code,owner,max_uses,used_count,start_time,end_time,campaign,bonus_point,revoked,create_time) \
+    select ?,?,?,0,?,?,?,?,false,? where not exists (select 1 from accounts where invite_code = ?) \
+    on conflict (code) do nothing",
+            vec![rbs::to_value!(code.code.clone()),
+                 rbs::to_value!(code.owner.clone()),
+                 rbs::to_value!(code.max_uses),
+                 rbs::to_value!(code.start_time),
+                 rbs::to_value!(code.end_time),
+                 rbs::to_value!(code.campaign.clone()),
+                 rbs::to_value!(code.bonus_point),
+                 rbs::to_value!(code.create_time),
+                 rbs::to_value!(code.code.clone()),
+            ]).await?;
+    Ok(ret.rows_affected)
+}
+
+pub async fn get_invite_code(rb: &RBatis, code: &str) -> anyhow::Result<Option<InviteCode>> {
+    let ret: Option<InviteCode> = rb
+        .query_decode("select * from invite_codes where code = ? limit 1", vec![rbs::to_value!(code)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_invite_codes(rb: &RBatis, campaign: Option<&str>, page: &PageRequest) -> anyhow::Result<(usize,Vec<InviteCode>)> {
+    let codes: Vec<InviteCode> = rb
+        .query_decode("select * from invite_codes where (?::text is null or campaign = ?) \
+        order by create_time desc, code offset ? limit ?",
+                      vec![rbs::to_value!(campaign),
+                           rbs::to_value!(campaign),
+                           rbs::to_value!(page.offset()),
+                           rbs::to_value!(page.page_size),
+                      ])
+        .await?;
+    let count: u64 = rb
+        .query_decode("select count(1) from invite_codes where (?::text is null or campaign = ?)",
+                      vec![rbs::to_value!(campaign),rbs::to_value!(campaign)])
+        .await?;
+    Ok((page.page_count(count),codes))
+}
+
+pub(crate) async fn revoke_invite_code(rb: &mut RBatis, code: &str) -> anyhow::Result<u64> {
+    let ret = rb.exec("update invite_codes set revoked = true where code = ? and revoked = false",
+                      vec![rbs::to_value!(code)]).await?;
+    Ok(ret.rows_affected)
+}
+
+pub(crate) async fn save_bind_request(rb: &mut RBatis, request: &BindRequest) -> anyhow::Result<()> {
+    rb.exec("insert into bind_requests (address,ip,create_time) values (?,?,?) on conflict (address) do nothing",
+            vec![rbs::to_value!(request.address.clone()),
+                 rbs::to_value!(request.ip.clone()),
+                 rbs::to_value!(request.create_time),
+            ]).await?;
+    Ok(())
+}
+
+// The first funder seen for an address is kept.
+pub(crate) async fn save_funding_sources(rb: &mut RBatis, sources: &[FundingSource]) -> anyhow::Result<()> {
+    for source in sources {
+        rb.exec("insert into funding_sources (address,funder,tx_hash,fund_time,resolve_time) values (?,?,?,?,?) \
+        on conflict (address) do nothing",
+                vec![rbs::to_value!(source.address.clone()),
+                     rbs::to_value!(source.funder.clone()),
+                     rbs::to_value!(source.tx_hash.clone()),
+                     rbs::to_value!(source.fund_time),
+                     rbs::to_value!(source.resolve_time),
+                ]).await?;
+    }
+    Ok(())
+}
+
+// Invitees funded by an inviter up to depth levels above them, by the wallet that funded such an inviter unless
+// that wallet is in the comma separated allowlist, or that funded such an inviter themselves.
+pub async fn get_self_referral_candidates(rb: &RBatis, depth: usize, allowlist: &str) -> anyhow::Result<Vec<AbuseCandidate>> {
+    if depth == 0 {
+        return Ok(vec![]);
+    }
+    let ret: Vec<AbuseCandidate> = rb
+        .query_decode("with recursive chain (address,ancestor,level,path) as ( \
+            select address,inviter,1,array[address,inviter] from accounts where inviter is not null \
+            union all \
+            select c.address,a.inviter,c.level + 1,c.path || a.inviter from chain c join accounts a on a.address = c.ancestor \
+            where a.inviter is not null and c.level < ? and a.inviter <> all(c.path) \
+        ) \
+        select distinct on (c.address) c.address, \
+        case when f.funder = c.ancestor then 'funded by inviter ' || c.ancestor \
+        when fa.funder = c.address then 'funded inviter ' || c.ancestor \
+        else 'funded by ' || f.funder || ' like inviter ' || c.ancestor end || ' at level ' || c.level as detail \
+        from chain c left join funding_sources f on f.address = c.address \
+        left join funding_sources fa on fa.address = c.ancestor \
+        where f.funder = c.ancestor or fa.funder = c.address \
+        or (f.funder = fa.funder and f.funder <> all(string_to_array(replace(?,' ',''),','))) \
+        order by c.address,c.level",
+                      vec![rbs::to_value!(depth as i32),rbs::to_value!(allowlist)])
+        .await?;
+    Ok(ret)
+}
+
+// Invitees sharing a funding wallet with at least threshold - 1 other invitees, wallets in the comma separated
+// allowlist are left out.
+pub async fn get_shared_funder_candidates(rb: &RBatis, threshold: i64, allowlist: &str) -> anyhow::Result<Vec<AbuseCandidate>> {
+    let ret: Vec<AbuseCandidate> = rb
+        .query_decode("with funded as ( \
+        select a.address,f.funder,count(1) over (partition by f.funder) as invitees from accounts a \
+        join funding_sources f on f.address = a.address \
+        where a.inviter is not null and f.funder is not null \
+        and f.funder <> all(string_to_array(replace(?,' ',''),','))) \
+        select address,'funder ' || funder || ' funded ' || invitees || ' invitees' as detail \
+        from funded where invitees >= ?", vec![rbs::to_value!(allowlist),rbs::to_value!(threshold)])
+        .await?;
+    Ok(ret)
+}
+
+// Accounts bound from an ip that bound at least threshold accounts within window seconds around them.
+pub async fn get_ip_burst_candidates(rb: &RBatis, threshold: i64, window: i64) -> anyhow::Result<Vec<AbuseCandidate>> {
+    let ret: Vec<AbuseCandidate> = rb
+        .query_decode("select address,'ip ' || ip || ' bound ' || binds || ' accounts' as detail from ( \
+        select b.address,b.ip,(select count(1) from bind_requests o where o.ip = b.ip \
+        and o.create_time between b.create_time - ? and b.create_time + ?) as binds \
+        from bind_requests b) t where binds >= ?",
+                      vec![rbs::to_value!(window),rbs::to_value!(window),rbs::to_value!(threshold)])
+        .await?;
+    Ok(ret)
+}
+
+// Returns false when the account already has a flag, reviewed accounts are not flagged again.
+// The points engine holds back the points of flagged accounts on its next run.
+pub(crate) async fn flag_account(rb: &mut RBatis, address: &str, reasons: &str, detail: &str, now: i64) -> anyhow::Result<bool> {
+    let ret = rb.exec("insert into abuse_flags (address,reasons,detail,status,inviter,create_time) \
+    values (?,?,?,'open',(select inviter from accounts where address = ?),?) on conflict (address) do nothing",
+                      vec![rbs::to_value!(address),
+                           rbs::to_value!(reasons),
+                           rbs::to_value!(detail),
+                           rbs::to_value!(address),
+                           rbs::to_value!(now),
+                      ]).await?;
+    Ok(ret.rows_affected > 0)
+}
+
+pub async fn get_abuse_flags(rb: &RBatis, status: Option<&str>, page: &PageRequest) -> anyhow::Result<(usize,Vec<AbuseFlag>)> {
+    let flags: Vec<AbuseFlag> = rb
+        .query_decode("select * from abuse_flags where (?::text is null or status = ?) \
+        order by create_time desc, address offset ? limit ?",
+                      vec![rbs::to_value!(status),
+                           rbs::to_value!(status),
+                           rbs::to_value!(page.offset()),
+                           rbs::to_value!(page.page_size),
+                      ])
+        .await?;
+    let count: u64 = rb
+        .query_decode("select count(1) from abuse_flags where (?::text is null or status = ?)",
+                      vec![rbs::to_value!(status),rbs::to_value!(status)])
+        .await?;
+    Ok((page.page_count(count),flags))
+}
+
+// Clearing releases the held points and rebates on the next engine run, confirming forfeits them.
+pub(crate) async fn review_abuse_flag(rb: &mut RBatis, address: &str, status: &str, now: i64) -> anyhow::Result<u64> {
+    let ret = rb.exec("update abuse_flags set status = ?,review_time = ? where address = ? and status = 'open'",
+                      vec![rbs::to_value!(status),rbs::to_value!(now),rbs::to_value!(address)]).await?;
+    Ok(ret.rows_affected)
+}
+
+// Accounts kept out of points and rebates: flagged and not cleared.
+pub async fn get_withheld_addresses(rb: &RBatis) -> anyhow::Result<Vec<String>> {
+    let ret: Vec<HashMap<String,String>> = rb
+        .query_decode("select address from abuse_flags where status <> 'cleared'", vec![])
+        .await?;
+    Ok(ret.into_iter().filter_map(|mut r| r.remove("address")).collect())
+}
+
+pub async fn get_point_accounts(rb: &dyn Executor) -> anyhow::Result<Vec<PointAccount>> {
+    let ret: Vec<PointAccount> = rbatis::decode(rb
+        .query("select a.address,a.inviter,a.invite_code_used,coalesce(c.bonus_point,0) as bonus_point,a.create_time \
+        from accounts a left join invite_codes c on c.code = a.invite_code_used order by a.address", vec![])
+        .await?)?;
+    Ok(ret)
+}
+
+// Records what the points engine held back from every account that is not cleared.
+pub(crate) async fn update_abuse_held_points(rb: &mut RBatis, held: &BTreeMap<String,HeldPoint>) -> anyhow::Result<()> {
+    let tx = begin_tx(rb).await?;
+    tx.exec("update abuse_flags set held_point = 0,inviter_held_point = 0 where status <> 'cleared'", vec![]).await?;
+    for (address, point) in held {
+        tx.exec("update abuse_flags set held_point = ?,inviter_held_point = ? where address = ? and status <> 'cleared'",
+                vec![rbs::to_value!(point.held_point),
+                     rbs::to_value!(point.inviter_held_point),
+                     rbs::to_value!(address),
+                ]).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_point_event_totals(rb: &dyn Executor) -> anyhow::Result<Vec<PointEventTotal>> {
+    let ret: Vec<PointEventTotal> = rbatis::decode(rb
+        .query("select address,reason,source,sum(delta)::bigint as point,min(create_time) as create_time \
+        from point_events group by address,reason,source", vec![])
+        .await?)?;
+    Ok(ret)
+}
+
+// Advisory lock key every writer of point_events takes, held until its transaction ends.
+const POINT_LEDGER_LOCK: i64 = 0x706f696e7473;
+
+async fn lock_point_ledger(tx: &dyn Executor) -> anyhow::Result<()> {
+    tx.exec("select pg_advisory_xact_lock(?)", vec![rbs::to_value!(POINT_LEDGER_LOCK)]).await?;
+    Ok(())
+}
+
+async fn insert_point_events(tx: &dyn Executor, events: &[PointEvent]) -> anyhow::Result<()> {
+    for event in events {
+        tx.exec("insert into point_events (address,delta,reason,source,create_time) values (?,?,?,?,?)",
+                vec![rbs::to_value!(event.address.clone()),
+                     rbs::to_value!(event.delta),
+                     rbs::to_value!(event.reason.clone()),
+                     rbs::to_value!(event.source.clone()),
+                     rbs::to_value!(event.create_time),
+                ]).await?;
+    }
+    Ok(())
+}
+
+// Reads the accounts and the ledger totals, appends the events computed from them and refreshes the
+// accounts.point cache in one transaction under the ledger lock. Two syncs running at once can not append
+// the same correction twice and a bind can not add events between the read and the insert.
+// Returns the number of events appended.
+pub(crate) async fn sync_point_events(rb: &mut RBatis,
+                                      events: impl FnOnce(&[PointAccount], &[PointEventTotal]) -> Vec<PointEvent>) -> anyhow::Result<usize> {
+    let tx = begin_tx(rb).await?;
+    lock_point_ledger(&tx).await?;
+    let accounts = get_point_accounts(&tx).await?;
+    let totals = get_point_event_totals(&tx).await?;
+    let events = events(&accounts, &totals);
+    insert_point_events(&tx, &events).await?;
+    for chunk in get_point_balances(&tx).await?.chunks(1000) {
+        update_user_points(&tx, chunk.to_vec()).await?;
+    }
+    tx.commit().await?;
+    Ok(events.len())
+}
+
+// Ledger balance of every account, accounts without events get 0.
+pub async fn get_point_balances(rb: &dyn Executor) -> anyhow::Result<Vec<UserPoint>> {
+    let ret: Vec<UserPoint> = rbatis::decode(rb
+        .query("select a.address,coalesce(sum(e.delta),0)::bigint as point from accounts a \
+        left join point_events e on e.address = a.address group by a.address order by a.address", vec![])
+        .await?)?;
+    Ok(ret)
+}
+
+// Events of the account, only those dated inside the season when one is given.
+pub async fn get_point_history(rb: &RBatis, address: &str, season: Option<&Season>, page: &PageRequest) -> anyhow::Result<(usize,Vec<PointEvent>)> {
+    let start_time = season.map(|s| s.start_time);
+    let end_time = season.map(|s| s.end_time);
+    let events: Vec<PointEvent> = rb
+        .query_decode("select * from point_events where address = ? \
+        and (?::bigint is null or create_time >= ?) and (?::bigint is null or create_time < ?) \
+        order by event_id desc offset ? limit ?",
+                      vec![rbs::to_value!(address),
+                           rbs::to_value!(start_time),
+                           rbs::to_value!(start_time),
+                           rbs::to_value!(end_time),
+                           rbs::to_value!(end_time),
+                           rbs::to_value!(page.offset()),
+                           rbs::to_value!(page.page_size),
+                      ])
+        .await?;
+    let count: u64 = rb
+        .query_decode("select count(1) from point_events where address = ? \
+        and (?::bigint is null or create_time >= ?) and (?::bigint is null or create_time < ?)",
+                      vec![rbs::to_value!(address),
+                           rbs::to_value!(start_time),
+                           rbs::to_value!(start_time),
+                           rbs::to_value!(end_time),
+                           rbs::to_value!(end_time),
+                      ])
+        .await?;
+    Ok((page.page_count(count),events))
+}
+
+// Seasons can not overlap, so a point event counts in one season at most. Returns None on an overlap.
+pub(crate) async fn create_season(rb: &mut RBatis, name: &str, start_time: i64, end_time: i64, create_time: i64) -> anyhow::Result<Option<Season>> {
+    let ret: Option<Season> = rb
+        .query_decode("insert into seasons (name,start_time,end_time,create_time) select ?,?,?,? \
+        where not exists (select 1 from seasons where start_time < ? and end_time > ?) returning *",
+                      vec![rbs::to_value!(name),
+                           rbs::to_value!(start_time),
+                           rbs::to_value!(end_time),
+                           rbs::to_value!(create_time),
+                           rbs::to_value!(end_time),
+                           rbs::to_value!(start_time),
+                      ])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_season(rb: &RBatis, season_id: i64) -> anyhow::Result<Option<Season>> {
+    let ret: Option<Season> = rb
+        .query_decode("select * from seasons where season_id = ? limit 1", vec![rbs::to_value!(season_id)])
+        .await?;
+    Ok(ret)
+}
+
+pub async fn get_seasons(rb: &RBatis) -> anyhow::Result<Vec<Season>> {
+    let ret: Vec<Season> = rb
+        .query_decode("select * from seasons order by start_time desc", vec![])
+        .await?;
+    Ok(ret)
+}
+
+// Ended seasons whose snapshot is not frozen yet.
+pub async fn get_unfrozen_seasons(rb: &RBatis, before: i64) -> anyhow::Result<Vec<Season>> {
+    let ret: Vec<Season> = rb
+        .query_decode("select * from seasons where end_time <= ? and snapshot_time is null order by end_time",
+                      vec![rbs::to_value!(before)])
+        .await?;
+    Ok(ret)
+}
+
+// Season points ranked live from the ledger events dated inside the season.
+const SEASON_LEDGER_RANKS: &str = "select address,point,rank() over (order by point desc) as rank from \
+    (select address,sum(delta)::bigint as point from point_events where create_time >= ? and create_time < ? \
+    group by address) t where point > 0";
+
+// A frozen season reads its snapshot, a running one is ranked from the ledger.
+fn point_ranks_sql(season: &Season) -> (&'static str, Vec<rbs::Value>) {
+    if season.snapshot_time.is_some() {
+        ("select address,point,rank from season_snapshots where season_id = ?",
+         vec![rbs::to_value!(season.season_id)])
+    } else {
+        (SEASON_LEDGER_RANKS, vec![rbs::to_value!(season.start_time), rbs::to_value!(season.end_time)])
+    }
+}
+
+pub async fn get_point_ranks(rb: &RBatis, season: &Season, page: &PageRequest) -> anyhow::Result<(usize,Vec<PointRank>)> {
+    let (ranks_sql, args) = point_ranks_sql(season);
+    let mut page_args = args.clone();
+    page_args.push(rbs::to_value!(page.offset()));
+    page_args.push(rbs::to_value!(page.page_size));
+    let ranks: Vec<PointRank> = rb
+        .query_decode(&format!("select * from ({ranks_sql}) r order by rank, address offset ? limit ?"), page_args)
+        .await?;
+    let count: u64 = rb
+        .query_decode(&format!("select count(1) from ({ranks_sql}) r"), args)
+        .await?;
+    Ok((page.page_count(count),ranks))
+}
+
+pub async fn get_point_rank(rb: &RBatis, season: &Season, address: &str) -> anyhow::Result<Option<PointRank>> {
+    let (ranks_sql, mut args) = point_ranks_sql(season);
+    args.push(rbs::to_value!(address));
+    let ret: Option<PointRank> = rb
+        .query_decode(&format!("select * from ({ranks_sql}) r where address = ? limit 1"), args)
+        .await?;
+    Ok(ret)
+}
+
+// Freezes the season points once, returns the number of ranked accounts or None when it was frozen before.
+pub(crate) async fn freeze_season_snapshot(rb: &mut RBatis, season: &Season, snapshot_time: i64) -> anyhow::Result<Option<u64>> {
+    let tx = begin_tx(rb).await?;
+    let ret = tx.exec("update seasons set snapshot_time = ? where season_id = ? and snapshot_time is null",
+                      vec![rbs::to_value!(snapshot_time),rbs::to_value!(season.season_id)]).await?;
+    if ret.rows_affected == 0 {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+    let ret = tx.exec(&format!("insert into season_snapshots (season_id,address,point,rank) \
+    select ?,address,point,rank from ({SEASON_LEDGER_RANKS}) r"),
+                      vec![rbs::to_value!(season.season_id),
+                           rbs::to_value!(season.start_time),
+                           rbs::to_value!(season.end_time),
+                      ]).await?;
+    tx.commit().await?;
+    Ok(Some(ret.rows_affected))
+}
+
+pub async fn get_season_snapshot(rb: &RBatis, season_id: i64) -> anyhow::Result<Vec<PointRank>> {
+    let ret: Vec<PointRank> = rb
+        .query_decode("select address,point,rank from season_snapshots where season_id = ? order by rank, address",
+                      vec![rbs::to_value!(season_id)])
+        .await?;
+    Ok(ret)
+}
+
+// What every account is ranked by on the board.
+fn leaderboard_values_sql(board: &str) -> anyhow::Result<&'static str> {
+    match board {
+        BOARD_POINTS => Ok("select address,point as value from accounts"),
+        BOARD_MINT => Ok("select address,sum(launch_amount) as value from launch_records group by address"),
+        BOARD_REFERRAL => Ok("select a.inviter as address,sum(l.launch_amount) as value from launch_records l \
+        join accounts a on a.address = l.address where a.inviter is not null group by a.inviter"),
+        _ => anyhow::bail!("unknown leaderboard {board}"),
+    }
+}
+
+// Replaces the ranks of the board in one transaction, flagged accounts stay off the board until cleared.
+pub(crate) async fn refresh_leaderboard(rb: &mut RBatis, board: &str, refresh_time: i64) -> anyhow::Result<u64> {
+    let values_sql = leaderboard_values_sql(board)?;
+    let tx = begin_tx(rb).await?;
+    tx.exec("delete from leaderboard_ranks where board = ?", vec![rbs::to_value!(board)]).await?;
+    let ret = tx.exec(&format!("insert into leaderboard_ranks (board,address,value,rank,refresh_time) \
+    select ?,address,value,rank() over (order by value desc),? from ({values_sql}) v \
+    where value > 0 and address not in (select address from abuse_flags where status <> 'cleared')"),
+                      vec![rbs::to_value!(board),rbs::to_value!(refresh_time)]).await?;
+    tx.commit().await?;
+    Ok(ret.rows_affected)
+}
+
+pub async fn get_leaderboard_ranks(rb: &RBatis, board: &str, page: &PageRequest) -> anyhow::Result<(usize,Vec<LeaderboardRank>)> {
+    let ranks: Vec<LeaderboardRank> = rb
+        .query_decode("select * from leaderboard_ranks where board = ? order by rank, address offset ? limit ?",
+                      vec![rbs::to_value!(board),
+                           rbs::to_value!(page.offset()),
+                           rbs::to_value!(page.page_size),
+                      ])
+        .await?;
+    let count: u64 = rb
+        .query_decode("select count(1) from leaderboard_ranks where board = ?", vec![rbs::to_value!(board)])
+        .await?;
+    Ok((page.page_count(count),ranks))
+}
+
+pub async fn get_leaderboard_rank(rb: &RBatis, board: &str, address: &str) -> anyhow::Result<Option<LeaderboardRank>> {
+    let ret: Option<LeaderboardRank> = rb
+        .query_decode("select * from leaderboard_ranks where board = ? and address = ? limit 1",
+                      vec![rbs::to_value!(board),rbs::to_value!(address)])
+        .await?;
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::fixture::test_db;
+
+    fn launch(address: &str, amount: &str, tx_hash: &str, launch_time: i64) -> LaunchRecord {
+        LaunchRecord {
+            address: address.to_string(),
+            launch_amount: Decimal::from_str(amount).unwrap(),
+            launch_block: launch_time,
+            launch_tx_hash: tx_hash.to_string(),
+            log_index: 0,
+            launch_time,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_get_pre_launch_record() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let records = vec![launch("a", "1", "tx1", 100), launch("a", "2", "tx2", 100), launch("a", "3", "tx3", 101)];
+        save_launch_records(&mut rb, &records).await.unwrap();
+        let pre = |i: usize| get_pre_launch_record(&rb, &records[i]);
+        assert!(pre(0).await.unwrap().is_empty());
+        // launches in the same second still count
+        assert_eq!(pre(1).await.unwrap().len(), 1);
+        assert_eq!(pre(2).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_bind_consumes_live_challenge_once() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        for (nonce, expire_time) in [("live", 200), ("expired", 100)] {
+            save_bind_challenge(&mut rb, &BindChallenge {
+                nonce: nonce.to_string(),
+                address: "evm".to_string(),
+                sol_address: "sol".to_string(),
+                message: String::new(),
+                expire_time,
+                consumed: false,
+                create_time: 0,
+            }).await.unwrap();
+        }
+        let bind = |nonce: &'static str| {
+            let mut rb = rb.clone();
+            async move { db_bind_sol_address(&mut rb, nonce, 150, None, None, &[], None).await }
+        };
+        assert!(bind("live").await.is_ok());
+        assert!(bind("live").await.unwrap_err().downcast_ref::<BindChallengeUnavailable>().is_some());
+        assert!(bind("expired").await.unwrap_err().downcast_ref::<BindChallengeUnavailable>().is_some());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_bind_skips_taken_invite_codes() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        for nonce in ["n1", "n2"] {
+            save_bind_challenge(&mut rb, &BindChallenge {
+                nonce: nonce.to_string(),
+                address: "evm".to_string(),
+                sol_address: "sol".to_string(),
+                message: String::new(),
+                expire_time: 200,
+                consumed: false,
+                create_time: 0,
+            }).await.unwrap();
+        }
+        rb.exec("insert into accounts (address,invite_code,create_time,point) values ('other','C0',0,0)", vec![]).await.unwrap();
+        rb.exec("insert into invite_codes (code,create_time) values ('C1',0)", vec![]).await.unwrap();
+        let account = Account {
+            address: "sol".to_string(),
+            invite_code: String::new(),
+            inviter: None,
+            create_time: 100,
+            point: 0,
+            invite_code_used: None,
+        };
+        let codes = ["C0", "C1", "C2"].map(String::from);
+        let events = vec![PointEvent {
+            event_id: None,
+            address: "sol".to_string(),
+            delta: 500,
+            reason: "invited".to_string(),
+            source: "other".to_string(),
+            create_time: 100,
+        }];
+        let saved = db_bind_sol_address(&mut rb, "n1", 150, None, Some((account.clone(), events.clone())), &codes, None).await.unwrap();
+        assert_eq!(saved.as_deref(), Some("C2"));
+        // an address bound meanwhile keeps its code and earns its bind points once
+        let saved = db_bind_sol_address(&mut rb, "n2", 150, None, Some((account, events.to_vec())), &["C3".to_string()], None).await.unwrap();
+        assert_eq!(saved.as_deref(), Some("C2"));
+        let point: i64 = rb.query_decode("select point from accounts where address = 'sol'", vec![]).await.unwrap();
+        assert_eq!(point, 500);
+        assert_eq!(get_point_balances(&rb).await.unwrap().iter().find(|b| b.address == "sol").unwrap().point, 500);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_funder_candidates_skip_allowlist() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let mut sources = vec![];
+        for (address, inviter, funder) in [("inviter", None, "exchange"), ("a", Some("inviter"), "exchange"),
+                                           ("b", Some("inviter"), "exchange"), ("c", Some("inviter"), "farm"),
+                                           ("d", Some("inviter"), "farm")] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter)]).await.unwrap();
+            sources.push(FundingSource {
+                address: address.to_string(),
+                funder: Some(funder.to_string()),
+                tx_hash: None,
+                fund_time: None,
+                resolve_time: 0,
+            });
+        }
+        save_funding_sources(&mut rb, &sources).await.unwrap();
+        let addresses = |candidates: Vec<AbuseCandidate>| {
+            let mut addresses = candidates.into_iter().map(|c| c.address).collect::<Vec<_>>();
+            addresses.sort();
+            addresses
+        };
+        assert_eq!(addresses(get_shared_funder_candidates(&rb, 2, "").await.unwrap()), ["a", "b", "c", "d"]);
+        assert_eq!(addresses(get_shared_funder_candidates(&rb, 2, "other, exchange").await.unwrap()), ["c", "d"]);
+        assert_eq!(addresses(get_self_referral_candidates(&rb, 1, "").await.unwrap()), ["a", "b"]);
+        assert!(get_self_referral_candidates(&rb, 1, "exchange").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_self_referral_through_chains() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let mut sources = vec![];
+        // farm funds root and, two levels below it, leaf; loop is funded by its own invitee
+        for (address, inviter, funder) in [("root", None, "farm"), ("mid", Some("root"), "mid_funder"),
+                                           ("leaf", Some("mid"), "farm"), ("loop", None, "looped"),
+                                           ("looped", Some("loop"), "looped_funder")] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter)]).await.unwrap();
+            sources.push(FundingSource {
+                address: address.to_string(),
+                funder: Some(funder.to_string()),
+                tx_hash: None,
+                fund_time: None,
+                resolve_time: 0,
+            });
+        }
+        save_funding_sources(&mut rb, &sources).await.unwrap();
+        let candidates = get_self_referral_candidates(&rb, 2, "").await.unwrap();
+        let found = candidates.iter().map(|c| (c.address.as_str(), c.detail.as_str())).collect::<Vec<_>>();
+        assert_eq!(found, [("leaf", "funded by farm like inviter root at level 2"),
+                           ("looped", "funded inviter loop at level 1")]);
+        // the chain is only followed as deep as rebates are paid
+        let candidates = get_self_referral_candidates(&rb, 1, "").await.unwrap();
+        assert_eq!(candidates.iter().map(|c| c.address.as_str()).collect::<Vec<_>>(), ["looped"]);
+        assert!(get_self_referral_candidates(&rb, 0, "").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_save_query_account_keeps_bound_amount() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let query = |amount: &str, version: &str| QueryAccount {
+            address: "evm".to_string(),
+            claimable_amount: Decimal::from_str(amount).unwrap(),
+            query_time: 1,
+            claim_sol_address: None,
+            rules_version: Some(version.to_string()),
+            breakdown: None,
+        };
+        save_query_account(&mut rb, query("10", "v1")).await.unwrap();
+        save_query_account(&mut rb, query("20", "v2")).await.unwrap();
+        let reader = rb.clone();
+        let amount = || async {
+            let account: QueryAccount = reader.query_decode("select * from query_accounts where address = 'evm'", vec![]).await.unwrap();
+            account.claimable_amount.to_string()
+        };
+        assert_eq!(amount().await, "20");
+        rb.exec("update query_accounts set claim_sol_address = 'sol'", vec![]).await.unwrap();
+        save_query_account(&mut rb, query("30", "v3")).await.unwrap();
+        assert_eq!(amount().await, "20");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_get_referral_nodes() {
+        let db = test_db().await;
+        let rb = db.rb.clone();
+        // root <- a <- b, root <- c
+        for (address, inviter) in [("root", None), ("a", Some("root")), ("b", Some("a")), ("c", Some("root"))] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter)]).await.unwrap();
+        }
+        let levels = get_referral_levels(&rb, "root", 2).await.unwrap();
+        assert_eq!(levels.iter().map(|l| (l.level, l.invitees)).collect::<Vec<_>>(), vec![(1, 2), (2, 1)]);
+        let page = PageRequest { page_no: 2, page_size: 2 };
+        let (page_count, nodes) = get_referral_nodes(&rb, "root", 2, &levels, &page).await.unwrap();
+        assert_eq!(page_count, 2);
+        assert_eq!(nodes.iter().map(|n| n.address.as_str()).collect::<Vec<_>>(), vec!["b"]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_refresh_leaderboard() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        // a <- b, a <- c, c is flagged
+        for (address, inviter, point) in [("a", None, 30), ("b", Some("a"), 30), ("c", Some("a"), 50)] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,?)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter), rbs::to_value!(point)]).await.unwrap();
+        }
+        rb.exec("insert into abuse_flags (address,reasons,detail,status,inviter,create_time) values ('c','ip_burst','','open','a',0)",
+                vec![]).await.unwrap();
+        save_launch_records(&mut rb, &vec![launch("b", "10", "tx1", 1), launch("c", "20", "tx2", 2), launch("a", "5", "tx3", 3)])
+            .await.unwrap();
+        let reader = rb.clone();
+        let ranks = |board: &'static str| {
+            let rb = reader.clone();
+            async move {
+                let page = PageRequest { page_no: 1, page_size: 10 };
+                let (_, ranks) = get_leaderboard_ranks(&rb, board, &page).await.unwrap();
+                ranks.into_iter().map(|r| (r.address, r.value.to_string(), r.rank)).collect::<Vec<_>>()
+            }
+        };
+        let row = |address: &str, value: &str, rank: i64| (address.to_string(), value.to_string(), rank);
+
+        assert_eq!(refresh_leaderboard(&mut rb, BOARD_POINTS, 100).await.unwrap(), 2);
+        assert_eq!(ranks(BOARD_POINTS).await, vec![row("a", "30", 1), row("b", "30", 1)]);
+        assert_eq!(refresh_leaderboard(&mut rb, BOARD_MINT, 100).await.unwrap(), 2);
+        assert_eq!(ranks(BOARD_MINT).await, vec![row("b", "10", 1), row("a", "5", 2)]);
+        // launches of a flagged invitee still count for its inviter
+        assert_eq!(refresh_leaderboard(&mut rb, BOARD_REFERRAL, 100).await.unwrap(), 1);
+        assert_eq!(ranks(BOARD_REFERRAL).await, vec![row("a", "30", 1)]);
+
+        // a refresh replaces the board
+        save_launch_records(&mut rb, &vec![launch("a", "10", "tx4", 4)]).await.unwrap();
+        refresh_leaderboard(&mut rb, BOARD_MINT, 200).await.unwrap();
+        assert_eq!(ranks(BOARD_MINT).await, vec![row("a", "15", 1), row("b", "10", 2)]);
+        assert_eq!(get_leaderboard_rank(&rb, BOARD_MINT, "b").await.unwrap().unwrap().refresh_time, 200);
+        assert!(get_leaderboard_rank(&rb, BOARD_MINT, "c").await.unwrap().is_none());
+        assert!(refresh_leaderboard(&mut rb, "unknown", 200).await.is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_get_account_summary() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        // claimed_accounts has no migration here, the claim indexer owns it
+        rb.exec("create table claimed_accounts (address text primary key,claimed_time bigint not null,claimed_amount numeric not null)",
+                vec![]).await.unwrap();
+        for (address, inviter) in [("sol", None), ("a", Some("sol")), ("b", Some("sol"))] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter)]).await.unwrap();
+        }
+        for (evm, amount) in [("0xb", "20"), ("0xa", "10")] {
+            rb.exec("insert into query_accounts (address,claimable_amount,query_time,claim_sol_address) values (?,?,0,'sol')",
+                    vec![rbs::to_value!(evm), rbs::to_value!(Decimal::from_str(amount).unwrap())]).await.unwrap();
+        }
+        save_launch_records(&mut rb, &vec![launch("sol", "10", "tx1", 1), launch("sol", "5", "tx2", 2), launch("a", "7", "tx3", 3)])
+            .await.unwrap();
+        rb.exec("insert into rebate_entries (launch_tx_hash,log_index,inviter,address,level,launch_amount,rate,rebate_amount,create_time) \
+        values ('tx3',0,'sol','a',1,7,0.1,0.7,3)", vec![]).await.unwrap();
+        for version in [1, 2] {
+            rb.exec("insert into merkle_distributions (version,root,total_amount,num_nodes,create_time) values (?,'',0,1,0)",
+                    vec![rbs::to_value!(version)]).await.unwrap();
+            rb.exec("insert into merkle_leaves (version,idx,claimant,amount,proof) values (?,0,'sol',?,'')",
+                    vec![rbs::to_value!(version), rbs::to_value!(version * 100)]).await.unwrap();
+        }
+        rb.exec("insert into claimed_accounts (address,claimed_time,claimed_amount) values ('0xa',5,10)", vec![]).await.unwrap();
+
+        let summary = get_account_summary(&rb, "sol").await.unwrap();
+        assert_eq!(summary.account.unwrap().invite_code, "sol");
+        assert_eq!(summary.bound_accounts.iter().map(|a| a.address.as_str()).collect::<Vec<_>>(), vec!["0xa", "0xb"]);
+        assert_eq!(to_big_decimal(&summary.total_mint), BigDecimal::from(15));
+        assert_eq!(summary.invitee_count, 2);
+        assert_eq!(to_big_decimal(&summary.rebate.accrued), BigDecimal::from_str("0.7").unwrap());
+        assert_eq!(summary.claim_leaf.unwrap().version, 2);
+        assert_eq!(summary.claimed.iter().map(|c| c.address.as_str()).collect::<Vec<_>>(), vec!["0xa"]);
+
+        let empty = get_account_summary(&rb, "nobody").await.unwrap();
+        assert!(empty.account.is_none() && empty.bound_accounts.is_empty() && empty.claim_leaf.is_none());
+        assert_eq!(empty.invitee_count, 0);
+    }
+
+    const LAUNCH_RECORD_SORTS: [&str; 6] = ["time_desc", "time_asc", "slot_desc", "slot_asc", "amount_desc", "amount_asc"];
+
+    #[test]
+    fn test_launch_record_key_args() {
+        let record = LaunchRecord { log_index: 3, ..launch("a", "2.5", "tx1", 100) };
+        for sort in LAUNCH_RECORD_SORTS {
+            let (_, after_condition) = launch_records_sort(sort).unwrap();
+            let args = launch_record_key_args(sort, &launch_record_key(sort, &record)).unwrap();
+            assert_eq!(args.len(), after_condition.matches('?').count(), "{sort}");
+            assert_eq!(args[args.len() - 2..], [rbs::to_value!("tx1"), rbs::to_value!(3i32)], "{sort}");
+        }
+        let args = launch_record_key_args("amount_asc", &launch_record_key("amount_asc", &record)).unwrap();
+        let amount = rbs::to_value!(record.launch_amount.clone());
+        assert_eq!(args[..3], [amount.clone(), amount, rbs::to_value!(100i64)]);
+
+        let key = |key: &[&str]| key.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        assert!(launch_record_key_args("time_desc", &key(&["100", "tx1"])).is_err());
+        assert!(launch_record_key_args("time_desc", &key(&["100", "tx1", "0", "extra"])).is_err());
+        assert!(launch_record_key_args("time_desc", &key(&["soon", "tx1", "0"])).is_err());
+        assert!(launch_record_key_args("time_desc", &key(&["100", "tx1", "first"])).is_err());
+        assert!(launch_record_key_args("amount_desc", &key(&["100", "tx1", "0"])).is_err());
+        assert!(launch_record_key_args("amount_desc", &key(&["lots", "100", "tx1", "0"])).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_launch_record_cursor_pages() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        // ties on amount, time and slot so every tie-break column is used
+        let records = vec![launch("a", "1", "tx1", 100), launch("b", "1", "tx2", 100), launch("a", "2", "tx3", 100),
+                           launch("a", "1", "tx4", 101), launch("b", "2", "tx5", 99), launch("a", "3", "tx6", 101)];
+        save_launch_records(&mut rb, &records).await.unwrap();
+        let filter = LaunchRecordFilter::default();
+        let all = PageRequest { page_no: 1, page_size: 100 };
+        let page = PageRequest { page_no: 1, page_size: 2 };
+        for sort in LAUNCH_RECORD_SORTS {
+            let (_, expected) = get_launch_records(&rb, &filter, sort, &all, None).await.unwrap();
+            let mut walked = vec![];
+            let mut after = None;
+            loop {
+                let (count, mut rows) = get_launch_records(&rb, &filter, sort, &page, after.as_ref()).await.unwrap();
+                assert_eq!(count, 6);
+                let cursor = crate::pagination::next_cursor(&mut rows, page.page_size, sort, |r| launch_record_key(sort, r));
+                walked.extend(rows);
+                let Some(cursor) = cursor else {
+                    break;
+                };
+                after = Some(crate::pagination::decode_cursor(&cursor, sort).unwrap());
+            }
+            let hashes = |rows: &[LaunchRecord]| rows.iter().map(|r| r.launch_tx_hash.clone()).collect::<Vec<_>>();
+            assert_eq!(hashes(&walked), hashes(&expected), "{sort}");
+        }
+        // equal amounts list the newest launch first in both directions
+        let (_, rows) = get_launch_records(&rb, &filter, "amount_asc", &all, None).await.unwrap();
+        assert_eq!(rows.iter().map(|r| r.launch_tx_hash.as_str()).collect::<Vec<_>>(), vec!["tx4", "tx2", "tx1", "tx3", "tx5", "tx6"]);
+    }
+
+    const ACCOUNT_INVITEE_SORTS: [&str; 6] = ["mint_desc", "mint_asc", "bind_desc", "bind_asc", "rebate_desc", "rebate_asc"];
+
+    #[test]
+    fn test_account_invitee_key_args() {
+        let invitee = AccountInviteeInfo {
+            address: "a".to_string(),
+            bind_time: 100,
+            mint_count: 2,
+            mint_amount: Decimal::from_str("15").unwrap(),
+            rebate_amount: Decimal::from_str("1.5").unwrap(),
+            flag_status: None,
+        };
+        for (sort, value) in [("mint_desc", rbs::to_value!(invitee.mint_amount.clone())),
+                              ("bind_asc", rbs::to_value!(100i64)),
+                              ("rebate_desc", rbs::to_value!(invitee.rebate_amount.clone()))] {
+            let args = account_invitee_key_args(sort, &account_invitee_key(sort, &invitee)).unwrap();
+            assert_eq!(args, vec![value, rbs::to_value!("a")], "{sort}");
+        }
+        for sort in ACCOUNT_INVITEE_SORTS {
+            let (_, after_condition) = account_invitees_sort(sort).unwrap();
+            let args = account_invitee_key_args(sort, &account_invitee_key(sort, &invitee)).unwrap();
+            assert_eq!(args.len(), after_condition.matches('?').count(), "{sort}");
+        }
+
+        let key = |key: &[&str]| key.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        assert!(account_invitee_key_args("mint_desc", &key(&["15"])).is_err());
+        assert!(account_invitee_key_args("mint_desc", &key(&["15", "a", "b"])).is_err());
+        assert!(account_invitee_key_args("mint_desc", &key(&["lots", "a"])).is_err());
+        assert!(account_invitee_key_args("bind_desc", &key(&["1.5", "a"])).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_get_account_invitees() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        // a minted and was cleared, b minted and is flagged, c never minted, d belongs to another inviter
+        for (address, inviter, create_time) in [("root", None, 0), ("a", Some("root"), 10), ("b", Some("root"), 20),
+                                                ("c", Some("root"), 30), ("d", Some("a"), 40)] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,?,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter), rbs::to_value!(create_time)])
+                .await.unwrap();
+        }
+        for (address, status) in [("a", "cleared"), ("b", "open")] {
+            rb.exec("insert into abuse_flags (address,reasons,detail,status,inviter,create_time) values (?,'ip_burst','',?,'root',0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(status)]).await.unwrap();
+        }
+        save_launch_records(&mut rb, &vec![launch("a", "5", "tx1", 1), launch("a", "5", "tx2", 2), launch("b", "10", "tx3", 3),
+                                           launch("d", "7", "tx4", 4)]).await.unwrap();
+        rb.exec("insert into rebate_entries (launch_tx_hash,log_index,inviter,address,level,launch_amount,rate,rebate_amount,create_time) \
+        values ('tx1',0,'root','a',1,5,0.1,0.5,1),('tx4',0,'root','d',2,7,0.1,0.7,4)", vec![]).await.unwrap();
+
+        let all = PageRequest { page_no: 1, page_size: 100 };
+        let invitees = |filter: AccountInviteeFilter, sort: &'static str| {
+            let rb = rb.clone();
+            let all = all.clone();
+            async move {
+                let (count, rows) = get_account_invitees(&rb, "root", &filter, sort, &all, None).await.unwrap();
+                assert_eq!(count as usize, rows.len());
+                rows.into_iter().map(|r| r.address).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(invitees(AccountInviteeFilter::default(), "mint_desc").await, vec!["b", "a", "c"]);
+        assert_eq!(invitees(AccountInviteeFilter { minted: Some(true), ..Default::default() }, "bind_asc").await, vec!["a", "b"]);
+        assert_eq!(invitees(AccountInviteeFilter { minted: Some(false), ..Default::default() }, "bind_asc").await, vec!["c"]);
+        assert_eq!(invitees(AccountInviteeFilter { flagged: Some(true), ..Default::default() }, "bind_asc").await, vec!["b"]);
+        assert_eq!(invitees(AccountInviteeFilter { flagged: Some(false), ..Default::default() }, "bind_asc").await, vec!["a", "c"]);
+        let window = AccountInviteeFilter { start_time: Some(20), end_time: Some(30), ..Default::default() };
+        assert_eq!(invitees(window, "bind_asc").await, vec!["b"]);
+        // only level 1 entries of the inviter count
+        assert_eq!(invitees(AccountInviteeFilter::default(), "rebate_desc").await, vec!["a", "c", "b"]);
+
+        let (_, rows) = get_account_invitees(&rb, "root", &AccountInviteeFilter::default(), "mint_desc", &all, None).await.unwrap();
+        assert_eq!(rows.iter().map(|r| (r.mint_count, r.flag_status.as_deref())).collect::<Vec<_>>(),
+                   vec![(1, Some("open")), (2, Some("cleared")), (0, None)]);
+
+        let page = PageRequest { page_no: 1, page_size: 1 };
+        for sort in ACCOUNT_INVITEE_SORTS {
+            let expected = invitees(AccountInviteeFilter::default(), sort).await;
+            let mut walked = vec![];
+            let mut after = None;
+            loop {
+                let (_, mut rows) = get_account_invitees(&rb, "root", &AccountInviteeFilter::default(), sort, &page, after.as_ref())
+                    .await.unwrap();
+                let cursor = crate::pagination::next_cursor(&mut rows, page.page_size, sort, |r| account_invitee_key(sort, r));
+                walked.extend(rows.into_iter().map(|r| r.address));
+                let Some(cursor) = cursor else {
+                    break;
+                };
+                after = Some(crate::pagination::decode_cursor(&cursor, sort).unwrap());
+            }
+            assert_eq!(walked, expected, "{sort}");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_failed_snapshot_rolls_back() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        // a single connection, so the next save reuses the one the failed save ran on
+        rb.get_pool().unwrap().set_max_open_conns(1).await;
+        let snapshot = EligibilitySnapshot {
+            version: 0,
+            source: "test".to_string(),
+            row_count: 1,
+            total_amount: Decimal::from_str("1").unwrap(),
+            create_time: 1,
+        };
+        let entry = EligibilitySnapshotEntry {
+            version: 0,
+            address: "a".to_string(),
+            amount: Decimal::from_str("1").unwrap(),
+            reason: String::new(),
+        };
+        let duplicated = vec![entry.clone(), entry.clone()];
+        assert!(save_eligibility_snapshot(&mut rb, &snapshot, &duplicated, &[], &[]).await.is_err());
+        assert_eq!(get_latest_snapshot_version(&rb).await.unwrap(), None);
+        let version = save_eligibility_snapshot(&mut rb, &snapshot, &[entry], &[], &[]).await.unwrap();
+        assert_eq!(get_latest_snapshot_version(&rb).await.unwrap(), Some(version));
+    }
+}
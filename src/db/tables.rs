@@ -12,6 +12,8 @@ pub struct QueryAccount {
     pub claimable_amount: Decimal,
     pub claim_sol_address: Option<String>,
     pub query_time: i64,
+    pub rules_version: Option<String>,
+    pub breakdown: Option<String>,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -21,11 +23,114 @@ pub struct Account {
     pub inviter: Option<String>,
     pub create_time: i64,
     pub point: i64,
+    // campaign or personal code the account signed up with
+    pub invite_code_used: Option<String>,
+}
+
+// Account as the points engine sees it, bonus_point comes from the campaign code it used.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PointAccount {
+    pub address: String,
+    pub inviter: Option<String>,
+    pub invite_code_used: Option<String>,
+    pub bonus_point: i64,
+    pub create_time: i64,
+}
+
+// One change of a point balance, events are only ever appended.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PointEvent {
+    pub event_id: Option<i64>,
+    pub address: String,
+    pub delta: i64,
+    pub reason: String,
+    pub source: String,
+    pub create_time: i64,
+}
+
+// Sum of the point events of one account for one reason and source.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PointEventTotal {
+    pub address: String,
+    pub reason: String,
+    pub source: String,
+    pub point: i64,
+    // time of the first event
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Season {
+    pub season_id: i64,
+    pub name: String,
+    pub start_time: i64,
+    // exclusive
+    pub end_time: i64,
+    pub snapshot_time: Option<i64>,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PointRank {
+    pub address: String,
+    pub point: i64,
+    pub rank: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LeaderboardRank {
+    pub board: String,
+    pub address: String,
+    pub value: Decimal,
+    pub rank: i64,
+    pub refresh_time: i64,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct AccountInviteeInfo {
     pub address: String,
+    pub bind_time: i64,
+    pub mint_count: i64,
+    pub mint_amount: Decimal,
+    // level 1 rebate the ledger holds for the inviter from the invitee's own launches
+    pub rebate_amount: Decimal,
+    pub flag_status: Option<String>,
+}
+
+// Filters of the invitee list, flagged means an abuse flag that is not cleared,
+// the bind time range includes the start and excludes the end.
+#[derive(Clone, Debug, Default)]
+pub struct AccountInviteeFilter {
+    pub minted: Option<bool>,
+    pub flagged: Option<bool>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferralLevelMint {
+    pub level: i32,
+    pub invitees: i64,
+    pub mint_amount: Decimal,
+}
+
+// A launch record seen from an inviter `level` levels above the sender.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferralLaunch {
+    pub inviter: String,
+    pub address: String,
+    pub level: i32,
+    pub launch_tx_hash: String,
+    pub log_index: i32,
+    pub launch_amount: Decimal,
+    pub launch_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferralNode {
+    pub address: String,
+    pub inviter: String,
+    pub level: i32,
     pub mint_amount: Decimal,
 }
 
@@ -39,6 +144,18 @@ pub struct LaunchRecord {
     pub launch_time: i64,
 }
 
+// Filters of the mint history, time and slot ranges include the start and exclude the end.
+#[derive(Clone, Debug, Default)]
+pub struct LaunchRecordFilter {
+    pub address: Option<String>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub start_slot: Option<i64>,
+    pub end_slot: Option<i64>,
+    // launch units, compared with launch_amount
+    pub min_amount: Option<Decimal>,
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct UserPoint {
     pub address: String,
@@ -58,11 +175,233 @@ pub struct ClaimedAccount {
     pub claimed_amount: Decimal,
 }
 
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RefundRecord {
+    pub launch_tx_hash: String,
+    pub log_index: i32,
+    pub address: String,
+    pub refund_amount: Decimal,
+    pub refund_lamports: i64,
+    pub batch_id: Option<i64>,
+    pub tx_signature: Option<String>,
+    pub status: String,
+    pub update_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LaunchRecordCheck {
+    pub launch_tx_hash: String,
+    pub log_index: i32,
+    pub address: String,
+    pub cumulative_amount: Decimal,
+    pub violation: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Allocation {
+    pub address: String,
+    pub contributed_amount: Decimal,
+    pub token_amount: Decimal,
+    pub pricing_rule: String,
+    pub update_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MerkleDistribution {
+    pub version: i64,
+    pub root: String,
+    pub total_amount: Decimal,
+    pub num_nodes: i64,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MerkleLeaf {
+    pub version: i64,
+    pub idx: i64,
+    pub claimant: String,
+    pub amount: Decimal,
+    pub proof: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct EligibilitySnapshot {
+    pub version: i64,
+    pub source: String,
+    pub row_count: i64,
+    pub total_amount: Decimal,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct EligibilitySnapshotEntry {
+    pub version: i64,
+    pub address: String,
+    pub amount: Decimal,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct OrbiterGasCache {
+    pub address: String,
+    pub gas: String,
+    pub fetch_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct BindChallenge {
+    pub nonce: String,
+    pub address: String,
+    pub sol_address: String,
+    pub message: String,
+    pub expire_time: i64,
+    pub consumed: bool,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LoginChallenge {
+    pub nonce: String,
+    pub address: String,
+    pub message: String,
+    pub expire_time: i64,
+    pub consumed: bool,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub token_hash: String,
+    pub address: String,
+    pub expire_time: i64,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RebateEntry {
+    pub launch_tx_hash: String,
+    pub log_index: i32,
+    pub inviter: String,
+    pub address: String,
+    pub level: i32,
+    pub launch_amount: Decimal,
+    pub rate: Decimal,
+    pub rebate_amount: Decimal,
+    pub batch_id: Option<i64>,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RebateBatch {
+    pub batch_id: i64,
+    pub status: String,
+    pub total_amount: Decimal,
+    pub tx_signature: Option<String>,
+    pub create_time: i64,
+    pub update_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RebateInviterTotal {
+    pub inviter: String,
+    pub amount: Decimal,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RebateSummary {
+    pub accrued: Decimal,
+    pub paid: Decimal,
+    pub in_payout: Decimal,
+}
+
+// Everything the account profile shows, read from one snapshot of the db.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AccountSummary {
+    pub account: Option<Account>,
+    // evm query accounts bound to the solana address
+    pub bound_accounts: Vec<QueryAccount>,
+    pub total_mint: Decimal,
+    pub invitee_count: u64,
+    pub rebate: RebateSummary,
+    // leaf of the address in the latest merkle distribution
+    pub claim_leaf: Option<MerkleLeaf>,
+    pub claimed: Vec<ClaimedAccount>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct InviteCode {
+    pub code: String,
+    pub owner: Option<String>,
+    pub max_uses: Option<i32>,
+    pub used_count: i32,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub campaign: Option<String>,
+    pub bonus_point: i64,
+    pub revoked: bool,
+    pub create_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AbuseFlag {
+    pub address: String,
+    pub reasons: String,
+    pub detail: String,
+    pub status: String,
+    pub held_point: i64,
+    pub inviter: Option<String>,
+    pub inviter_held_point: i64,
+    pub create_time: i64,
+    pub review_time: Option<i64>,
+}
+
+// An account a detection rule picked up and why.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AbuseCandidate {
+    pub address: String,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct FundingSource {
+    pub address: String,
+    pub funder: Option<String>,
+    pub tx_hash: Option<String>,
+    pub fund_time: Option<i64>,
+    pub resolve_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct BindRequest {
+    pub address: String,
+    pub ip: String,
+    pub create_time: i64,
+}
+
 rbatis::crud!(QueryAccount {}, "query_accounts");
 rbatis::crud!(ClaimedAccount {}, "claimed_accounts");
 rbatis::crud!(LastSyncBlock {}, "last_sync_block");
 rbatis::crud!(Account {}, "accounts");
 rbatis::crud!(LaunchRecord {}, "launch_records");
+rbatis::crud!(RefundRecord {}, "refund_records");
+rbatis::crud!(LaunchRecordCheck {}, "launch_record_checks");
+rbatis::crud!(Allocation {}, "allocations");
+rbatis::crud!(MerkleDistribution {}, "merkle_distributions");
+rbatis::crud!(MerkleLeaf {}, "merkle_leaves");
+rbatis::crud!(EligibilitySnapshot {}, "eligibility_snapshots");
+rbatis::crud!(EligibilitySnapshotEntry {}, "eligibility_snapshot_entries");
+rbatis::crud!(OrbiterGasCache {}, "orbiter_gas_cache");
+rbatis::crud!(BindChallenge {}, "bind_challenges");
+rbatis::crud!(LoginChallenge {}, "login_challenges");
+rbatis::crud!(Session {}, "sessions");
+rbatis::crud!(RebateEntry {}, "rebate_entries");
+rbatis::crud!(RebateBatch {}, "rebate_batches");
+rbatis::crud!(InviteCode {}, "invite_codes");
+rbatis::crud!(AbuseFlag {}, "abuse_flags");
+rbatis::crud!(FundingSource {}, "funding_sources");
+rbatis::crud!(BindRequest {}, "bind_requests");
+rbatis::crud!(PointEvent {}, "point_events");
+rbatis::crud!(Season {}, "seasons");
 
 impl Default for QueryAccount {
     fn default() -> Self {
@@ -72,6 +411,8 @@ impl Default for QueryAccount {
             claimable_amount: Decimal::from_str("0").unwrap(),
             claim_sol_address: None,
             query_time: 0,
+            rules_version: None,
+            breakdown: None,
         }
     }
 }
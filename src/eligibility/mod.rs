@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::address::AccountAddress;
+use crate::db;
+use crate::db::tables::QueryAccount;
+use crate::orbiter::OrbiterClient;
+
+pub mod snapshot;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotRule {
+    pub enabled: bool,
+    // claimable amount = snapshot amount * multiplier
+    pub multiplier: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GasTier {
+    pub min_gas: String,
+    pub amount: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EligibilityRules {
+    pub version: String,
+    pub snapshot: Option<SnapshotRule>,
+    // eth gas spend must be above min_gas to earn anything from the gas rules
+    pub min_gas: Option<String>,
+    #[serde(default)]
+    pub gas_tiers: Vec<GasTier>,
+    // tokens per unit of gas spent, keyed by the gas currency of the orbiter api
+    #[serde(default)]
+    pub gas_ratios: BTreeMap<String, String>,
+    pub max_amount: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EligibilityInputs {
+    pub snapshot_amount: Option<BigDecimal>,
+    pub snapshot_reason: Option<String>,
+    pub gas: BTreeMap<String, BigDecimal>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EligibleBreakdown {
+    pub rule: String,
+    pub input: String,
+    pub amount: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct EligibilityResult {
+    pub claimable_amount: BigDecimal,
+    pub rules_version: String,
+    pub breakdown: Vec<EligibleBreakdown>,
+}
+
+impl EligibilityResult {
+    pub fn to_query_account(&self, address: &str, query_time: i64) -> QueryAccount {
+        QueryAccount {
+            address: address.to_string(),
+            claimable_amount: Decimal::from_str(&self.claimable_amount.to_string()).unwrap(),
+            claim_sol_address: None,
+            query_time,
+            rules_version: Some(self.rules_version.clone()),
+            breakdown: serde_json::to_string(&self.breakdown).ok(),
+        }
+    }
+}
+
+// Gas spend is only looked up for evm addresses and only when the rules use it.
+pub async fn collect_inputs(rb: &RBatis, rules: &EligibilityRules, orbiter: &OrbiterClient,
+                            address: &str) -> anyhow::Result<EligibilityInputs> {
+    let snapshot = db::get_latest_snapshot_entry(rb, address).await?;
+    let is_evm_address = matches!(AccountAddress::from_str(address), Ok(AccountAddress::Evm(_)));
+    let gas = if rules.uses_gas() && is_evm_address {
+        orbiter.get_gas(rb, address).await?
+    } else {
+        BTreeMap::new()
+    };
+    Ok(EligibilityInputs {
+        snapshot_amount: snapshot.as_ref()
+            .map(|s| BigDecimal::from_str(&s.amount.to_string()).unwrap_or_default()),
+        snapshot_reason: snapshot.map(|s| s.reason),
+        gas,
+    })
+}
+
+fn parse_decimal(value: &str) -> anyhow::Result<BigDecimal> {
+    BigDecimal::from_str(value.trim()).map_err(|e| anyhow::anyhow!("invalid decimal {value}: {e}"))
+}
+
+impl Default for EligibilityRules {
+    fn default() -> Self {
+        Self {
+            version: "none".to_string(),
+            snapshot: None,
+            min_gas: None,
+            gas_tiers: vec![],
+            gas_ratios: BTreeMap::new(),
+            max_amount: None,
+        }
+    }
+}
+
+impl EligibilityRules {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        if path.is_empty() {
+            log::warn!("eligibility rules path is not set, nobody is eligible");
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        let rules: EligibilityRules = serde_json::from_str(&content)?;
+        rules.validate()?;
+        log::info!("eligibility rules {} loaded from {path}", rules.version);
+        Ok(rules)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if let Some(snapshot) = &self.snapshot {
+            parse_decimal(&snapshot.multiplier)?;
+        }
+        if let Some(min_gas) = &self.min_gas {
+            parse_decimal(min_gas)?;
+        }
+        for tier in &self.gas_tiers {
+            parse_decimal(&tier.min_gas)?;
+            parse_decimal(&tier.amount)?;
+        }
+        for ratio in self.gas_ratios.values() {
+            parse_decimal(ratio)?;
+        }
+        if let Some(max_amount) = &self.max_amount {
+            parse_decimal(max_amount)?;
+        }
+        Ok(())
+    }
+
+    pub fn uses_gas(&self) -> bool {
+        !self.gas_tiers.is_empty() || !self.gas_ratios.is_empty()
+    }
+
+    fn gas_tier_amount(&self, eth_gas: &BigDecimal) -> Option<(BigDecimal, BigDecimal)> {
+        self.gas_tiers.iter()
+            .filter_map(|t| Some((parse_decimal(&t.min_gas).ok()?, parse_decimal(&t.amount).ok()?)))
+            .filter(|(min_gas, _)| min_gas <= eth_gas)
+            .max_by(|a, b| a.0.cmp(&b.0))
+    }
+
+    // Same rules and inputs always give the same amount, every part of it is listed in the breakdown.
+    pub fn evaluate(&self, inputs: &EligibilityInputs) -> EligibilityResult {
+        let mut total = BigDecimal::zero();
+        let mut breakdown = vec![];
+
+        if let (Some(rule), Some(snapshot_amount)) = (&self.snapshot, &inputs.snapshot_amount) {
+            if rule.enabled {
+                let multiplier = parse_decimal(&rule.multiplier).unwrap_or_default();
+                let amount = snapshot_amount.clone() * multiplier;
+                breakdown.push(EligibleBreakdown {
+                    rule: format!("snapshot:{}", inputs.snapshot_reason.clone().unwrap_or_default()),
+                    input: snapshot_amount.to_string(),
+                    amount: amount.to_string(),
+                });
+                total += amount;
+            }
+        }
+
+        let eth_gas = inputs.gas.get("ETH").cloned().unwrap_or_default();
+        let below_min_gas = self.min_gas.as_ref().and_then(|m| parse_decimal(m).ok())
+            .is_some_and(|min_gas| eth_gas <= min_gas);
+        if self.uses_gas() && below_min_gas {
+            breakdown.push(EligibleBreakdown {
+                rule: "min_gas".to_string(),
+                input: eth_gas.to_string(),
+                amount: "0".to_string(),
+            });
+        } else if self.uses_gas() {
+            if let Some((tier_min_gas, amount)) = self.gas_tier_amount(&eth_gas) {
+                breakdown.push(EligibleBreakdown {
+                    rule: format!("gas_tier:{tier_min_gas}"),
+                    input: eth_gas.to_string(),
+                    amount: amount.to_string(),
+                });
+                total += amount;
+            }
+            for (currency, ratio) in &self.gas_ratios {
+                let Some(gas) = inputs.gas.get(currency) else {
+                    continue;
+                };
+                let amount = (gas.clone() * parse_decimal(ratio).unwrap_or_default()).with_scale(0);
+                breakdown.push(EligibleBreakdown {
+                    rule: format!("gas_ratio:{currency}:{ratio}"),
+                    input: gas.to_string(),
+                    amount: amount.to_string(),
+                });
+                total += amount;
+            }
+        }
+
+        if let Some(max_amount) = self.max_amount.as_ref().and_then(|m| parse_decimal(m).ok()) {
+            if total > max_amount {
+                breakdown.push(EligibleBreakdown {
+                    rule: "max_amount".to_string(),
+                    input: total.to_string(),
+                    amount: (max_amount.clone() - total.clone()).to_string(),
+                });
+                total = max_amount;
+            }
+        }
+
+        EligibilityResult {
+            claimable_amount: total,
+            rules_version: self.version.clone(),
+            breakdown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    fn rules() -> EligibilityRules {
+        EligibilityRules {
+            version: "v1".to_string(),
+            snapshot: Some(SnapshotRule { enabled: true, multiplier: "2".to_string() }),
+            min_gas: Some("0.001".to_string()),
+            gas_tiers: vec![
+                GasTier { min_gas: "0.01".to_string(), amount: "100".to_string() },
+                GasTier { min_gas: "0.1".to_string(), amount: "400".to_string() },
+            ],
+            gas_ratios: BTreeMap::from([("ETH".to_string(), "1000".to_string())]),
+            max_amount: Some("1000".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let rules = rules();
+        assert!(rules.validate().is_ok());
+        let inputs = EligibilityInputs {
+            snapshot_amount: Some(decimal("50")),
+            snapshot_reason: Some("early".to_string()),
+            gas: BTreeMap::from([("ETH".to_string(), decimal("0.05"))]),
+        };
+        let result = rules.evaluate(&inputs);
+        // 50 * 2 from the snapshot, the 0.01 tier and 0.05 * 1000 from the ratio
+        assert_eq!(result.claimable_amount, decimal("250"));
+        assert_eq!(result.rules_version, "v1");
+        let rule_names = result.breakdown.iter().map(|b| b.rule.as_str()).collect::<Vec<_>>();
+        assert_eq!(rule_names, vec!["snapshot:early", "gas_tier:0.01", "gas_ratio:ETH:1000"]);
+        // evaluation is deterministic
+        assert_eq!(rules.evaluate(&inputs).claimable_amount, result.claimable_amount);
+
+        let below_min = EligibilityInputs {
+            gas: BTreeMap::from([("ETH".to_string(), decimal("0.0001"))]),
+            ..Default::default()
+        };
+        let result = rules.evaluate(&below_min);
+        assert_eq!(result.claimable_amount, BigDecimal::zero());
+        assert_eq!(result.breakdown[0].rule, "min_gas");
+
+        let capped = EligibilityInputs {
+            snapshot_amount: Some(decimal("600")),
+            gas: BTreeMap::from([("ETH".to_string(), decimal("0.5"))]),
+            ..Default::default()
+        };
+        let result = rules.evaluate(&capped);
+        assert_eq!(result.claimable_amount, decimal("1000"));
+        assert_eq!(result.breakdown.last().unwrap().rule, "max_amount");
+
+        assert_eq!(EligibilityRules::default().evaluate(&capped).claimable_amount, BigDecimal::zero());
+    }
+
+    #[test]
+    fn test_min_gas_is_exclusive() {
+        let rules = EligibilityRules {
+            min_gas: Some("0.01".to_string()),
+            gas_ratios: BTreeMap::from([("ETH".to_string(), "1000".to_string())]),
+            ..Default::default()
+        };
+        let gas = |eth: &str| EligibilityInputs {
+            gas: BTreeMap::from([("ETH".to_string(), decimal(eth))]),
+            ..Default::default()
+        };
+        // spend exactly at min_gas earns nothing, as the original orbiter check did
+        assert_eq!(rules.evaluate(&gas("0.01")).claimable_amount, BigDecimal::zero());
+        assert_eq!(rules.evaluate(&gas("0.011")).claimable_amount, decimal("11"));
+        let no_min = EligibilityRules { min_gas: None, ..rules };
+        assert_eq!(no_min.evaluate(&gas("0.01")).claimable_amount, decimal("10"));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::address::AccountAddress;
+use crate::db;
+use crate::db::tables::{EligibilitySnapshot, EligibilitySnapshotEntry, QueryAccount};
+use crate::eligibility::{EligibilityInputs, EligibilityRules};
+use crate::time::now_secs;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnapshotFormat {
+    Csv,
+    Json,
+}
+
+impl SnapshotFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(SnapshotFormat::Csv),
+            "json" => Some(SnapshotFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        path.rsplit('.').next().and_then(Self::from_name)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct JsonSnapshotRow {
+    address: String,
+    amount: serde_json::Value,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SnapshotRow {
+    pub address: String,
+    pub amount: BigDecimal,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub version: i64,
+    pub previous_version: Option<i64>,
+    pub rows: usize,
+    pub duplicates_merged: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub total_amount: String,
+    pub previous_total_amount: String,
+}
+
+// Evm addresses are stored the way get_eligible looks them up: lowercase without 0x.
+pub fn normalize_address(address: &str) -> Option<String> {
+    AccountAddress::from_str(address).ok().map(|a| a.to_db_string())
+}
+
+fn parse_row(line: usize, address: &str, amount: &str, reason: &str) -> anyhow::Result<SnapshotRow> {
+    let normalized = normalize_address(address)
+        .ok_or(anyhow::anyhow!("line {line}: invalid address {address}"))?;
+    let amount = BigDecimal::from_str(amount.trim())
+        .map_err(|e| anyhow::anyhow!("line {line}: invalid amount {amount}: {e}"))?;
+    if amount < BigDecimal::zero() {
+        anyhow::bail!("line {line}: negative amount {amount}");
+    }
+    Ok(SnapshotRow {
+        address: normalized,
+        amount,
+        reason: reason.trim().to_string(),
+    })
+}
+
+// address,amount[,reason] with an optional header, quoted fields may hold commas.
+fn parse_csv(content: &str) -> anyhow::Result<Vec<SnapshotRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let mut rows = vec![];
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = record.position().map(|p| p.line() as usize).unwrap_or(i + 1);
+        if record.iter().all(|f| f.is_empty()) {
+            continue;
+        }
+        if i == 0 && record[0].eq_ignore_ascii_case("address") {
+            continue;
+        }
+        if record.len() < 2 {
+            anyhow::bail!("line {line}: expected address,amount[,reason]");
+        }
+        rows.push(parse_row(line, &record[0], &record[1], record.get(2).unwrap_or_default())?);
+    }
+    Ok(rows)
+}
+
+fn parse_json(content: &str) -> anyhow::Result<Vec<SnapshotRow>> {
+    let json_rows: Vec<JsonSnapshotRow> = serde_json::from_str(content)?;
+    let mut rows = vec![];
+    for (i, row) in json_rows.iter().enumerate() {
+        let amount = match &row.amount {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Number(n) => n.to_string(),
+            v => anyhow::bail!("row {}: invalid amount {v}", i + 1),
+        };
+        rows.push(parse_row(i + 1, &row.address, &amount, &row.reason.clone().unwrap_or_default())?);
+    }
+    Ok(rows)
+}
+
+pub fn parse_snapshot(content: &str, format: &SnapshotFormat) -> anyhow::Result<Vec<SnapshotRow>> {
+    match format {
+        SnapshotFormat::Csv => parse_csv(content),
+        SnapshotFormat::Json => parse_json(content),
+    }
+}
+
+// Rows of the same address are summed and their reasons joined.
+pub fn merge_rows(rows: Vec<SnapshotRow>) -> BTreeMap<String, (BigDecimal, BTreeSet<String>)> {
+    let mut merged: BTreeMap<String, (BigDecimal, BTreeSet<String>)> = BTreeMap::new();
+    for row in rows {
+        let entry = merged.entry(row.address).or_insert((BigDecimal::zero(), BTreeSet::new()));
+        entry.0 += row.amount;
+        if !row.reason.is_empty() {
+            entry.1.insert(row.reason);
+        }
+    }
+    merged
+}
+
+pub async fn import_snapshot(rb: &mut RBatis, rules: &EligibilityRules, source: &str,
+                             content: &str, format: &SnapshotFormat) -> anyhow::Result<SnapshotDiff> {
+    let rows = parse_snapshot(content, format)?;
+    if rows.is_empty() {
+        anyhow::bail!("snapshot is empty");
+    }
+    let row_count = rows.len();
+    let merged = merge_rows(rows);
+    let total_amount = merged.values().fold(BigDecimal::zero(), |acc, (a, _)| acc + a.clone());
+
+    let previous_version = db::get_latest_snapshot_version(rb).await?;
+    let previous: HashMap<String, BigDecimal> = match previous_version {
+        Some(version) => db::get_snapshot_entries(rb, version).await?
+            .into_iter()
+            .map(|e| (e.address, BigDecimal::from_str(&e.amount.to_string()).unwrap_or_default()))
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    let entries = merged.iter().map(|(address, (amount, reasons))| EligibilitySnapshotEntry {
+        version: 0,
+        address: address.clone(),
+        amount: Decimal::from_str(&amount.to_string()).unwrap(),
+        reason: reasons.iter().cloned().collect::<Vec<_>>().join("|"),
+    }).collect::<Vec<_>>();
+    let now = now_secs();
+
+    let query_accounts = entries.iter().map(|e| {
+        let inputs = EligibilityInputs {
+            snapshot_amount: merged.get(&e.address).map(|(a, _)| a.clone()),
+            snapshot_reason: Some(e.reason.clone()),
+            gas: BTreeMap::new(),
+        };
+        let mut account = rules.evaluate(&inputs).to_query_account(&e.address, now);
+        if rules.uses_gas() {
+            // gas spend is not part of the snapshot, get_eligible completes the amount
+            account.rules_version = None;
+        }
+        account
+    }).collect::<Vec<QueryAccount>>();
+    let removed = previous.keys()
+        .filter(|a| !merged.contains_key(*a))
+        .cloned()
+        .collect::<Vec<_>>();
+    let snapshot = EligibilitySnapshot {
+        version: 0,
+        source: source.to_string(),
+        row_count: row_count as i64,
+        total_amount: Decimal::from_str(&total_amount.to_string()).unwrap(),
+        create_time: now,
+    };
+    let version = db::save_eligibility_snapshot(rb, &snapshot, &entries, &query_accounts, &removed).await?;
+
+    let mut diff = SnapshotDiff {
+        version,
+        previous_version,
+        rows: row_count,
+        duplicates_merged: row_count - merged.len(),
+        added: 0,
+        removed: removed.len(),
+        changed: 0,
+        unchanged: 0,
+        total_amount: total_amount.to_string(),
+        previous_total_amount: previous.values().fold(BigDecimal::zero(), |acc, a| acc + a.clone()).to_string(),
+    };
+    for (address, (amount, _)) in &merged {
+        match previous.get(address) {
+            None => diff.added += 1,
+            Some(prev) if prev != amount => diff.changed += 1,
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+    log::info!("snapshot {version} imported from {source}: {:?}", diff);
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod test {
+    use solana_sdk::pubkey::Pubkey;
+    use crate::db::fixture::test_db;
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let (a, b) = (Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string());
+        let content = format!("address,amount,reason\n{a},10,early\n\n\"{b}\",\"2.5\",\"bridge, then swap\"\n{a},5\n");
+        let rows = parse_snapshot(&content, &SnapshotFormat::Csv).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].reason, "bridge, then swap");
+        assert_eq!(rows[1].amount, BigDecimal::from_str("2.5").unwrap());
+        let merged = merge_rows(rows);
+        assert_eq!(merged[&a].0, BigDecimal::from(15));
+        assert_eq!(merged[&a].1.iter().collect::<Vec<_>>(), vec!["early"]);
+
+        let err = parse_snapshot(&format!("{a},10\n{b}\n"), &SnapshotFormat::Csv).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: expected address,amount[,reason]");
+        assert!(parse_snapshot(&format!("{a},-1\n"), &SnapshotFormat::Csv).is_err());
+        assert!(parse_snapshot("nope,1\n", &SnapshotFormat::Csv).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_import_snapshot() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let (a, b, bound) = (Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string());
+        rb.exec("insert into query_accounts (address,claimable_amount,query_time,claim_sol_address) values (?,7,1,?)",
+                vec![rbs::to_value!(bound.clone()), rbs::to_value!(a.clone())]).await.unwrap();
+        let rules = EligibilityRules {
+            snapshot: Some(crate::eligibility::SnapshotRule { enabled: true, multiplier: "1".to_string() }),
+            ..Default::default()
+        };
+        let content = format!("{a},10\n{b},20\n{bound},30\n");
+        let diff = import_snapshot(&mut rb, &rules, "test", &content, &SnapshotFormat::Csv).await.unwrap();
+        assert_eq!((diff.added, diff.removed, diff.total_amount.as_str()), (3, 0, "60"));
+        let amount = |address: String| {
+            let rb = rb.clone();
+            async move {
+                let account: QueryAccount = rb.query_decode("select * from query_accounts where address = ?",
+                                                            vec![rbs::to_value!(address)]).await.unwrap();
+                account.claimable_amount.to_string()
+            }
+        };
+        assert_eq!(amount(a.clone()).await, "10");
+        // the bound account keeps the amount it was bound with
+        assert_eq!(amount(bound.clone()).await, "7");
+
+        // a bad row fails the import before anything is written
+        assert!(import_snapshot(&mut rb, &rules, "test", &format!("{a},1\n{b},x\n"), &SnapshotFormat::Csv).await.is_err());
+        assert_eq!(db::get_latest_snapshot_version(&rb).await.unwrap(), Some(diff.version));
+    }
+}
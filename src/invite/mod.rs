@@ -0,0 +1,164 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::{OsRng, StdRng};
+use solana_sdk::keccak;
+use solana_sdk::pubkey::Pubkey;
+use crate::db::tables::InviteCode;
+
+pub const INVITE_CODE_LEN: usize = 6;
+pub const INVITE_CODE_MAX_LEN: usize = 12;
+pub const VANITY_CODE_MIN_LEN: usize = 4;
+pub const CAMPAIGN_CODE_LEN: usize = 8;
+// taken candidates tried at one length before codes grow by a character
+const ATTEMPTS_PER_LENGTH: u32 = 8;
+
+const CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+// Rejected anywhere inside a vanity code, also after undoing digit for letter swaps.
+const PROFANITY: &[&str] = &["FUCK", "SHIT", "CUNT", "BITCH", "DICK", "COCK", "PUSSY", "NIGG",
+    "FAG", "SLUT", "WHORE", "RAPE", "PORN", "NAZI", "HITLER", "RETARD"];
+// Codes starting like the project or its staff, only admins can hand them out.
+const RESERVED_WORDS: &[&str] = &["ADMIN", "OCTOPUS", "OCTUPUS", "OFFICIAL", "SUPPORT", "STAFF",
+    "MODERATOR", "TEAM", "ROOT", "SYSTEM", "HELPDESK", "AIRDROP"];
+
+fn random_code(seed: [u8; 32], len: usize) -> String {
+    let mut rng = StdRng::from_seed(seed);
+    (0..len)
+        .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+        .collect()
+}
+
+// Attempt 0 is the code accounts have always been given, later attempts re-seed with the attempt number
+// and grow the code by one character every ATTEMPTS_PER_LENGTH attempts.
+pub fn candidate_invite_code(owner: &Pubkey, attempt: u32) -> String {
+    let seed = if attempt == 0 {
+        owner.to_bytes()
+    } else {
+        keccak::hashv(&[owner.as_ref(), &attempt.to_le_bytes()]).0
+    };
+    random_code(seed, INVITE_CODE_LEN + (attempt / ATTEMPTS_PER_LENGTH) as usize)
+}
+
+// Every code the account of owner may get, in the order the bind tries to insert them.
+pub fn invite_code_candidates(owner: &Pubkey) -> Vec<String> {
+    let max_attempts = (INVITE_CODE_MAX_LEN - INVITE_CODE_LEN + 1) as u32 * ATTEMPTS_PER_LENGTH;
+    (0..max_attempts).map(|attempt| candidate_invite_code(owner, attempt)).collect()
+}
+
+// Random code for a campaign created without a chosen one.
+pub fn generate_campaign_code() -> String {
+    (0..CAMPAIGN_CODE_LEN)
+        .map(|_| CHARSET[OsRng.gen_range(0..CHARSET.len())] as char)
+        .collect()
+}
+
+// Why a campaign code cannot be used at now, the message is shown to the user as is.
+pub fn check_campaign_code(code: &InviteCode, now: i64) -> anyhow::Result<()> {
+    if code.revoked {
+        anyhow::bail!("Invite code is revoked");
+    }
+    if code.start_time.is_some_and(|start| now < start) {
+        anyhow::bail!("Invite code is not active yet");
+    }
+    if code.end_time.is_some_and(|end| now >= end) {
+        anyhow::bail!("Invite code is expired");
+    }
+    if code.max_uses.is_some_and(|max| code.used_count >= max) {
+        anyhow::bail!("Invite code is exhausted");
+    }
+    Ok(())
+}
+
+// Codes are case insensitive, they are stored uppercase.
+pub fn normalize_invite_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+pub fn is_valid_invite_code(code: &str) -> bool {
+    (VANITY_CODE_MIN_LEN..=INVITE_CODE_MAX_LEN).contains(&code.len())
+        && code.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+}
+
+fn undo_leetspeak(code: &str) -> String {
+    code.chars().map(|c| match c {
+        '0' => 'O',
+        '1' => 'I',
+        '3' => 'E',
+        '4' => 'A',
+        '5' => 'S',
+        '7' => 'T',
+        c => c,
+    }).collect()
+}
+
+// Returns the normalized code when it can be claimed as a vanity code.
+pub fn check_vanity_code(code: &str, allow_reserved: bool) -> anyhow::Result<String> {
+    let code = normalize_invite_code(code);
+    if !is_valid_invite_code(&code) {
+        anyhow::bail!("Invite code must be {VANITY_CODE_MIN_LEN} to {INVITE_CODE_MAX_LEN} letters or digits");
+    }
+    let plain = undo_leetspeak(&code);
+    if PROFANITY.iter().any(|w| code.contains(w) || plain.contains(w)) {
+        anyhow::bail!("Invite code is not allowed");
+    }
+    if !allow_reserved && RESERVED_WORDS.iter().any(|w| code.starts_with(w) || plain.starts_with(w)) {
+        anyhow::bail!("Invite code is reserved");
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_candidate_invite_codes() {
+        let owner = Pubkey::new_unique();
+        assert_eq!(candidate_invite_code(&owner, 0), random_code(owner.to_bytes(), INVITE_CODE_LEN));
+        assert_ne!(candidate_invite_code(&owner, 1), candidate_invite_code(&owner, 0));
+        assert_eq!(candidate_invite_code(&owner, ATTEMPTS_PER_LENGTH - 1).len(), INVITE_CODE_LEN);
+        assert_eq!(candidate_invite_code(&owner, ATTEMPTS_PER_LENGTH).len(), INVITE_CODE_LEN + 1);
+        assert!(is_valid_invite_code(&candidate_invite_code(&owner, 3)));
+        let candidates = invite_code_candidates(&owner);
+        assert_eq!(candidates[0], candidate_invite_code(&owner, 0));
+        assert_eq!(candidates.last().unwrap().len(), INVITE_CODE_MAX_LEN);
+    }
+
+    #[test]
+    fn test_check_vanity_code() {
+        assert_eq!(check_vanity_code(" moon42 ", false).unwrap(), "MOON42");
+        assert!(check_vanity_code("abc", false).is_err());
+        assert!(check_vanity_code("moon-42", false).is_err());
+        assert!(check_vanity_code("5H1TC01N", false).is_err());
+        assert!(check_vanity_code("octopusvip", false).is_err());
+        assert_eq!(check_vanity_code("octopusvip", true).unwrap(), "OCTOPUSVIP");
+        assert!(check_vanity_code("fuckit", true).is_err());
+        // reserved words only count at the start of a code
+        assert!(check_vanity_code("team42", false).is_err());
+        assert!(check_vanity_code("7EAM42", false).is_err());
+        assert_eq!(check_vanity_code("steam42", false).unwrap(), "STEAM42");
+    }
+
+    #[test]
+    fn test_check_campaign_code() {
+        let mut code = InviteCode {
+            code: generate_campaign_code(),
+            owner: None,
+            max_uses: Some(2),
+            used_count: 1,
+            start_time: Some(100),
+            end_time: Some(200),
+            campaign: Some("launch".to_string()),
+            bonus_point: 500,
+            revoked: false,
+            create_time: 0,
+        };
+        assert!(is_valid_invite_code(&code.code));
+        assert!(check_campaign_code(&code, 150).is_ok());
+        assert_eq!(check_campaign_code(&code, 50).unwrap_err().to_string(), "Invite code is not active yet");
+        assert_eq!(check_campaign_code(&code, 200).unwrap_err().to_string(), "Invite code is expired");
+        code.used_count = 2;
+        assert_eq!(check_campaign_code(&code, 150).unwrap_err().to_string(), "Invite code is exhausted");
+        code.revoked = true;
+        assert_eq!(check_campaign_code(&code, 150).unwrap_err().to_string(), "Invite code is revoked");
+    }
+}
@@ -0,0 +1,22 @@
+use rbatis::RBatis;
+use crate::db;
+use crate::time::now_secs;
+
+// ranked by accounts.point
+pub const BOARD_POINTS: &str = "points";
+// ranked by the total launch_amount an account minted
+pub const BOARD_MINT: &str = "mint";
+// ranked by the total launch_amount of direct invitees
+pub const BOARD_REFERRAL: &str = "referral";
+
+pub const BOARDS: [&str; 3] = [BOARD_POINTS, BOARD_MINT, BOARD_REFERRAL];
+
+// Rebuilds every board, requests only read the stored ranks.
+pub async fn refresh_leaderboards(rb: &mut RBatis) -> anyhow::Result<()> {
+    let now = now_secs();
+    for board in BOARDS {
+        let count = db::refresh_leaderboard(rb, board, now).await?;
+        log::debug!("leaderboard {board} refreshed with {count} accounts");
+    }
+    Ok(())
+}
@@ -3,10 +3,33 @@ pub mod config;
 pub mod route;
 pub mod db;
 pub mod watcher;
+pub mod transfer;
+pub mod refund;
+pub mod cli;
+pub mod contribution;
+pub mod allocation;
+pub mod merkle;
+pub mod eligibility;
+pub mod orbiter;
+pub mod auth;
+pub mod address;
+pub mod referral;
+pub mod rebate;
+pub mod invite;
+pub mod abuse;
+pub mod points;
+pub mod leaderboard;
+pub mod pagination;
+pub mod time;
 
 use std::cell::RefCell;
+use std::sync::Arc;
 use dotenvy::dotenv;
 use crate::config::Config;
+use crate::eligibility::EligibilityRules;
+use crate::orbiter::OrbiterClient;
+use crate::points::PointRules;
+use crate::rebate::RebatePolicy;
 use crate::server::AppState;
 use futures::executor::block_on;
 use futures::channel::mpsc;
@@ -33,9 +56,27 @@ async fn main() -> std::io::Result<()> {
     let config = Config::from_env();
     let rb = init_db(config.database_url.clone(), config.db_pool_size as usize);
 
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        if let Err(e) = cli::run_command(&args, config, rb).await {
+            log::error!("command failed: {e}");
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let app_state = AppState {
         config:config.clone(),
         db: rb.clone(),
+        eligibility_rules: Arc::new(EligibilityRules::load(&config.eligibility_rules_path)
+            .expect("load eligibility rules failed")),
+        orbiter: Arc::new(OrbiterClient::from_config(&config)
+            .expect("create orbiter client failed")),
+        rebate_policy: Arc::new(RebatePolicy::load(&config)
+            .expect("load rebate policy failed")),
+        point_rules: Arc::new(PointRules::from_config(&config)
+            .expect("load point rules failed")),
     };
     server::run_server(app_state).await;
 
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use solana_sdk::keccak::{hashv, Hash};
+use solana_sdk::pubkey::Pubkey;
+use crate::allocation::to_base_units;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::{MerkleDistribution, MerkleLeaf};
+use crate::time::now_secs;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClaimLeaf {
+    pub index: u64,
+    pub claimant: Pubkey,
+    pub amount: u64,
+}
+
+impl ClaimLeaf {
+    // Same leaf encoding as the merkle-distributor program: keccak(index le, claimant, amount le)
+    pub fn hash(&self) -> Hash {
+        hashv(&[&self.index.to_le_bytes(), &self.claimant.to_bytes(), &self.amount.to_le_bytes()])
+    }
+}
+
+fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    if a.0 <= b.0 {
+        hashv(&[&a.0, &b.0])
+    } else {
+        hashv(&[&b.0, &a.0])
+    }
+}
+
+pub struct MerkleTree {
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: &[ClaimLeaf]) -> Self {
+        let mut layers = vec![leaves.iter().map(|l| l.hash()).collect::<Vec<_>>()];
+        while layers.last().unwrap().len() > 1 {
+            let next = layers.last().unwrap()
+                .chunks(2)
+                .map(|pair| if pair.len() == 2 { hash_pair(&pair[0], &pair[1]) } else { pair[0] })
+                .collect::<Vec<_>>();
+            layers.push(next);
+        }
+        Self { layers }
+    }
+
+    pub fn root(&self) -> Hash {
+        self.layers.last().and_then(|l| l.first().cloned()).unwrap_or_default()
+    }
+
+    pub fn proof(&self, index: usize) -> Vec<Hash> {
+        let mut proof = vec![];
+        let mut idx = index;
+        for layer in &self.layers[..self.layers.len() - 1] {
+            let sibling = idx ^ 1;
+            if sibling < layer.len() {
+                proof.push(layer[sibling]);
+            }
+            idx /= 2;
+        }
+        proof
+    }
+}
+
+pub fn verify_proof(proof: &[Hash], root: &Hash, leaf: &Hash) -> bool {
+    let computed = proof.iter().fold(*leaf, |acc, p| hash_pair(&acc, p));
+    computed == *root
+}
+
+pub fn encode_proof(proof: &[Hash]) -> String {
+    proof.iter().map(|h| hex::encode(h.0)).collect::<Vec<_>>().join(",")
+}
+
+pub fn decode_proof(proof: &str) -> anyhow::Result<Vec<Hash>> {
+    proof.split(',')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let bytes: [u8; 32] = hex::decode(p)?.try_into()
+                .map_err(|_| anyhow::anyhow!("invalid proof element {p}"))?;
+            Ok(Hash::new_from_array(bytes))
+        })
+        .collect()
+}
+
+// Claimants are the bound solana addresses, sorted so the same accounts always give the same tree.
+async fn get_claim_leaves(rb: &RBatis, config: &Config) -> anyhow::Result<Vec<ClaimLeaf>> {
+    let accounts = db::get_bound_query_accounts(rb).await?;
+    let mut amounts: BTreeMap<String, u64> = BTreeMap::new();
+    for account in accounts {
+        let Some(sol_address) = account.claim_sol_address else {
+            continue;
+        };
+        let amount = BigDecimal::from_str(&account.claimable_amount.to_string()).unwrap_or_default();
+        let amount = to_base_units(&amount, config.token_decimal).to_u64()
+            .ok_or(anyhow::anyhow!("claimable amount of {} overflows u64", account.address))?;
+        *amounts.entry(sol_address).or_insert(0) += amount;
+    }
+    let mut leaves = vec![];
+    for (index, (claimant, amount)) in amounts.into_iter().filter(|(_, a)| *a > 0).enumerate() {
+        leaves.push(ClaimLeaf {
+            index: index as u64,
+            claimant: Pubkey::from_str(&claimant)?,
+            amount,
+        });
+    }
+    Ok(leaves)
+}
+
+pub async fn generate_distribution(rb: &mut RBatis, config: &Config) -> anyhow::Result<MerkleDistribution> {
+    let leaves = get_claim_leaves(rb, config).await?;
+    if leaves.is_empty() {
+        anyhow::bail!("no claimable accounts");
+    }
+    let tree = MerkleTree::new(&leaves);
+    let total_amount: u64 = leaves.iter().map(|l| l.amount).sum();
+    let distribution = MerkleDistribution {
+        version: 0,
+        root: hex::encode(tree.root().0),
+        total_amount: Decimal::from_str(&total_amount.to_string()).unwrap(),
+        num_nodes: leaves.len() as i64,
+        create_time: now_secs(),
+    };
+    let merkle_leaves = leaves.iter().map(|l| MerkleLeaf {
+        version: 0,
+        idx: l.index as i64,
+        claimant: l.claimant.to_string(),
+        amount: Decimal::from_str(&l.amount.to_string()).unwrap(),
+        proof: encode_proof(&tree.proof(l.index as usize)),
+    }).collect::<Vec<_>>();
+    let version = db::save_merkle_distribution(rb, &distribution, &merkle_leaves).await?;
+    Ok(MerkleDistribution { version, ..distribution })
+}
+
+// Rebuilds the tree from the persisted leaves and checks the root and every stored proof.
+pub async fn verify_distribution(rb: &RBatis, version: Option<i64>) -> anyhow::Result<MerkleDistribution> {
+    let distribution = match version {
+        Some(version) => db::get_merkle_distribution(rb, version).await?,
+        None => db::get_latest_merkle_distribution(rb).await?,
+    }.ok_or(anyhow::anyhow!("merkle distribution not found"))?;
+    let stored = db::get_merkle_leaves(rb, distribution.version).await?;
+    let mut leaves = vec![];
+    for leaf in &stored {
+        leaves.push(ClaimLeaf {
+            index: leaf.idx as u64,
+            claimant: Pubkey::from_str(&leaf.claimant)?,
+            amount: leaf.amount.to_string().parse::<u64>()?,
+        });
+    }
+    let tree = MerkleTree::new(&leaves);
+    let root = tree.root();
+    if hex::encode(root.0) != distribution.root {
+        anyhow::bail!("root mismatch, stored {} computed {}", distribution.root, hex::encode(root.0));
+    }
+    for (leaf, stored) in leaves.iter().zip(stored.iter()) {
+        if !verify_proof(&decode_proof(&stored.proof)?, &root, &leaf.hash()) {
+            anyhow::bail!("invalid proof for leaf {} of {}", leaf.index, leaf.claimant);
+        }
+    }
+    Ok(distribution)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_proofs_verify_against_root() {
+        for count in [1usize, 2, 3, 7, 16] {
+            let leaves = (0..count).map(|i| ClaimLeaf {
+                index: i as u64,
+                claimant: Pubkey::new_unique(),
+                amount: 1000 * (i as u64 + 1),
+            }).collect::<Vec<_>>();
+            let tree = MerkleTree::new(&leaves);
+            for leaf in &leaves {
+                let proof = decode_proof(&encode_proof(&tree.proof(leaf.index as usize))).unwrap();
+                assert!(verify_proof(&proof, &tree.root(), &leaf.hash()));
+                let forged = ClaimLeaf { amount: leaf.amount + 1, ..leaf.clone() };
+                assert!(!verify_proof(&proof, &tree.root(), &forged.hash()));
+            }
+        }
+    }
+}
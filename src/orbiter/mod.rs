@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use bigdecimal::BigDecimal;
+use rbatis::RBatis;
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::OrbiterGasCache;
+use crate::time::now_secs;
+
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct EligibleResult {
+    pub count: u32,
+    pub gas: HashMap<String,String>,
+}
+#[derive(Clone,Debug,Serialize,Deserialize)]
+pub struct OrbiterEligibleResp {
+    pub code: u32,
+    pub msg: String,
+    pub result: EligibleResult,
+}
+
+// The api could not be reached or answered with an error, as opposed to a failure on our side.
+#[derive(Debug)]
+pub struct OrbiterUnavailable(pub String);
+
+impl std::fmt::Display for OrbiterUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "orbiter api unavailable: {}", self.0)
+    }
+}
+
+impl std::error::Error for OrbiterUnavailable {}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            consecutive_failures: 0,
+            open_until: None,
+        }
+    }
+
+    // After the cooldown one request goes through, its result closes or reopens the breaker.
+    fn allow(&mut self) -> bool {
+        match self.open_until {
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                self.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn on_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.open_until = None;
+    }
+
+    fn on_failure(&mut self) {
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= self.failure_threshold {
+            self.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+pub struct OrbiterClient {
+    base_url: String,
+    http: reqwest::Client,
+    cache_ttl: i64,
+    breaker: Mutex<CircuitBreaker>,
+}
+
+impl OrbiterClient {
+    pub fn new(base_url: &str, timeout: Duration, cache_ttl: i64,
+               failure_threshold: u32, cooldown: Duration) -> anyhow::Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http,
+            cache_ttl,
+            breaker: Mutex::new(CircuitBreaker::new(failure_threshold, cooldown)),
+        })
+    }
+
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        Self::new(&config.orbiter_base_url,
+                  Duration::from_millis(config.orbiter_timeout_ms),
+                  config.orbiter_cache_ttl,
+                  config.orbiter_breaker_threshold,
+                  Duration::from_secs(config.orbiter_breaker_cooldown))
+    }
+
+    async fn request_gas(&self, address: &str) -> anyhow::Result<HashMap<String, String>> {
+        let url = format!("{}/gas?address={}", self.base_url, address);
+        let resp = self.http.get(url).send().await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("orbiter api return status {}", resp.status());
+        }
+        let ret: OrbiterEligibleResp = serde_json::from_str(&resp.text().await?)?;
+        if ret.code != 0 {
+            anyhow::bail!("orbiter api return code {} {}", ret.code, ret.msg);
+        }
+        Ok(ret.result.gas)
+    }
+
+    // Gas spent by an evm address per currency, straight from the api.
+    pub async fn fetch_gas(&self, address: &str) -> anyhow::Result<BTreeMap<String, BigDecimal>> {
+        if !self.breaker.lock().unwrap().allow() {
+            return Err(OrbiterUnavailable("circuit breaker is open".to_string()).into());
+        }
+        let ret = self.request_gas(address).await;
+        let mut breaker = self.breaker.lock().unwrap();
+        match ret {
+            Ok(gas) => {
+                breaker.on_success();
+                let mut parsed = BTreeMap::new();
+                for (currency, amount) in gas {
+                    parsed.insert(currency, BigDecimal::from_str(&amount)?);
+                }
+                Ok(parsed)
+            }
+            Err(e) => {
+                breaker.on_failure();
+                Err(OrbiterUnavailable(e.to_string()).into())
+            }
+        }
+    }
+
+    // Same as fetch_gas, answered from the db cache while it is younger than the ttl.
+    pub async fn get_gas(&self, rb: &RBatis, address: &str) -> anyhow::Result<BTreeMap<String, BigDecimal>> {
+        let now = now_secs();
+        if let Some(cached) = db::get_orbiter_gas_cache(rb, address).await? {
+            if now - cached.fetch_time < self.cache_ttl {
+                let gas: BTreeMap<String, String> = serde_json::from_str(&cached.gas)?;
+                let mut parsed = BTreeMap::new();
+                for (currency, amount) in gas {
+                    parsed.insert(currency, BigDecimal::from_str(&amount)?);
+                }
+                return Ok(parsed);
+            }
+        }
+        let gas = self.fetch_gas(address).await?;
+        let cache = OrbiterGasCache {
+            address: address.to_string(),
+            gas: serde_json::to_string(&gas.iter().map(|(k, v)| (k.clone(), v.to_string())).collect::<BTreeMap<_, _>>())?,
+            fetch_time: now,
+        };
+        let mut rb = rb.clone();
+        if let Err(e) = db::save_orbiter_gas_cache(&mut rb, &cache).await {
+            log::warn!("save_orbiter_gas_cache failed,{e}");
+        }
+        Ok(gas)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use super::*;
+
+    // Answers every connection with the given status and body.
+    async fn run_stub_server(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut buf = [0u8; 4096];
+                let _ = socket.read(&mut buf).await;
+                let resp = format!("HTTP/1.1 {status}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                                   body.len());
+                let _ = socket.write_all(resp.as_bytes()).await;
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_fetch_gas_from_stub() {
+        let url = run_stub_server("200 OK",
+                                  r#"{"code":0,"msg":"success","result":{"count":3,"gas":{"ETH":"0.0125"}}}"#).await;
+        let client = OrbiterClient::new(&url, Duration::from_secs(2), 60, 3, Duration::from_secs(60)).unwrap();
+        let gas = client.fetch_gas("0000000000000000000000000000000000000001").await.unwrap();
+        assert_eq!(gas.get("ETH"), Some(&BigDecimal::from_str("0.0125").unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_after_failures() {
+        let url = run_stub_server("503 Service Unavailable", "{}").await;
+        let client = OrbiterClient::new(&url, Duration::from_secs(2), 60, 2, Duration::from_secs(60)).unwrap();
+        for _ in 0..2 {
+            let err = client.fetch_gas("0000000000000000000000000000000000000001").await.unwrap_err();
+            assert!(err.to_string().contains("status"));
+            assert!(err.downcast_ref::<OrbiterUnavailable>().is_some());
+        }
+        let err = client.fetch_gas("0000000000000000000000000000000000000001").await.unwrap_err();
+        assert!(err.to_string().contains("circuit breaker"));
+        assert!(err.downcast_ref::<OrbiterUnavailable>().is_some());
+    }
+}
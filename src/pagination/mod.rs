@@ -0,0 +1,116 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use qstring::QString;
+
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+// Offset page of a list endpoint, pg_no starts at 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageRequest {
+    pub page_no: i64,
+    pub page_size: i64,
+}
+
+// What a keyset cursor points after, only valid for the sort it was issued for.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Cursor {
+    sort: String,
+    key: Vec<String>,
+}
+
+impl PageRequest {
+    // Reads pg_no (page_no on admin endpoints) and page_size, both must be positive integers.
+    pub fn from_query(qs: &QString, default_size: i64) -> anyhow::Result<Self> {
+        let page_no = match qs.get("pg_no").or(qs.get("page_no")) {
+            Some(page_no) => page_no.parse::<i64>().ok().filter(|p| *p >= 1)
+                .ok_or(anyhow::anyhow!("Page number must be a positive integer"))?,
+            None => 1,
+        };
+        let page_size = match qs.get("page_size") {
+            Some(page_size) => page_size.parse::<i64>().ok().filter(|s| (1..=MAX_PAGE_SIZE).contains(s))
+                .ok_or(anyhow::anyhow!("Page size must be between 1 and {MAX_PAGE_SIZE}"))?,
+            None => default_size,
+        };
+        if (page_no - 1).checked_mul(page_size).is_none() {
+            anyhow::bail!("Page number is too large");
+        }
+        Ok(Self { page_no, page_size })
+    }
+
+    // from_query makes sure this does not overflow.
+    pub fn offset(&self) -> i64 {
+        (self.page_no - 1) * self.page_size
+    }
+
+    // Pages needed for total rows, the last partial page counts.
+    pub fn page_count(&self, total: u64) -> usize {
+        total.div_ceil(self.page_size as u64) as usize
+    }
+}
+
+pub fn encode_cursor(sort: &str, key: Vec<String>) -> String {
+    let cursor = Cursor { sort: sort.to_string(), key };
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(&cursor).unwrap_or_default())
+}
+
+// Key of the row the cursor points after.
+pub fn decode_cursor(cursor: &str, sort: &str) -> anyhow::Result<Vec<String>> {
+    let cursor = URL_SAFE_NO_PAD.decode(cursor.trim()).ok()
+        .and_then(|bytes| serde_json::from_slice::<Cursor>(&bytes).ok())
+        .ok_or(anyhow::anyhow!("Invalid cursor"))?;
+    if cursor.sort != sort {
+        anyhow::bail!("Cursor belongs to another sort order");
+    }
+    Ok(cursor.key)
+}
+
+pub fn cursor_from_query(qs: &QString, sort: &str) -> anyhow::Result<Option<Vec<String>>> {
+    qs.get("cursor").filter(|c| !c.is_empty()).map(|c| decode_cursor(c, sort)).transpose()
+}
+
+// Rows are fetched one past the page size, the extra row only tells there is a next page.
+pub fn next_cursor<T>(rows: &mut Vec<T>, page_size: i64, sort: &str, key: impl Fn(&T) -> Vec<String>) -> Option<String> {
+    if rows.len() as i64 <= page_size {
+        return None;
+    }
+    rows.truncate(page_size as usize);
+    rows.last().map(|row| encode_cursor(sort, key(row)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_page_request() {
+        let page = PageRequest::from_query(&QString::from(""), 10).unwrap();
+        assert_eq!(page, PageRequest { page_no: 1, page_size: 10 });
+        assert_eq!(page.page_count(0), 0);
+        assert_eq!(page.page_count(7), 1);
+        assert_eq!(page.page_count(21), 3);
+        let page = PageRequest::from_query(&QString::from("pg_no=3&page_size=25"), 10).unwrap();
+        assert_eq!(page.offset(), 50);
+        assert!(PageRequest::from_query(&QString::from("pg_no=0"), 10).is_err());
+        assert!(PageRequest::from_query(&QString::from("pg_no=-2"), 10).is_err());
+        assert!(PageRequest::from_query(&QString::from("pg_no=abc"), 10).is_err());
+        assert!(PageRequest::from_query(&QString::from("page_size=1000"), 10).is_err());
+        let page = PageRequest::from_query(&QString::from(format!("pg_no={}&page_size=1", i64::MAX).as_str()), 10).unwrap();
+        assert_eq!(page.offset(), i64::MAX - 1);
+        assert!(PageRequest::from_query(&QString::from(format!("pg_no={}&page_size=2", i64::MAX).as_str()), 10).is_err());
+        assert!(PageRequest::from_query(&QString::from(format!("pg_no={}", i64::MAX / 10).as_str()), 20).is_err());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let cursor = encode_cursor("time_desc", vec!["100".to_string(), "sig".to_string()]);
+        assert_eq!(decode_cursor(&cursor, "time_desc").unwrap(), vec!["100", "sig"]);
+        assert!(decode_cursor(&cursor, "amount_desc").is_err());
+        assert!(decode_cursor("not a cursor", "time_desc").is_err());
+
+        let mut rows = vec![1, 2, 3];
+        assert_eq!(next_cursor(&mut rows, 3, "n", |r| vec![r.to_string()]), None);
+        let cursor = next_cursor(&mut rows, 2, "n", |r| vec![r.to_string()]).unwrap();
+        assert_eq!(rows, vec![1, 2]);
+        assert_eq!(decode_cursor(&cursor, "n").unwrap(), vec!["2"]);
+    }
+}
@@ -0,0 +1,422 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use rbatis::RBatis;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::{LaunchRecord, PointAccount, PointEvent, PointEventTotal};
+use crate::referral::to_big_decimal;
+use crate::watcher::watcher::LAMPORTS_PER_LAUNCH_UNIT;
+use crate::time::now_secs;
+
+pub mod season;
+
+pub const REASON_MINT: &str = "mint";
+pub const REASON_INVITEE_MINT: &str = "invitee_mint";
+pub const REASON_INVITE: &str = "invite";
+pub const REASON_INVITED: &str = "invited";
+pub const REASON_CAMPAIGN_BONUS: &str = "campaign_bonus";
+// takes back an award while an abuse flag is open, the source is reason:source of the award
+pub const REASON_WITHHELD: &str = "withheld";
+
+#[derive(Clone, Debug)]
+pub struct PointRules {
+    pub per_sol: BigDecimal,
+    // paid to the inviter for every direct invitee
+    pub per_invite: i64,
+    // paid to an account that signed up with an inviter
+    pub per_invited: i64,
+    // paid to the inviter for every SOL a direct invitee mints
+    pub per_invitee_sol: BigDecimal,
+    // (end slot, multiplier) sorted by end slot, mints before the end slot are multiplied
+    pub early_bird: Vec<(i64, BigDecimal)>,
+}
+
+// Points one account earns from one source, the engine output before it is summed up per account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointAward {
+    pub address: String,
+    pub point: i64,
+    pub reason: String,
+    // launch signature or the address of the other account
+    pub source: String,
+    // direct invitee that earned the inviter this award
+    pub invitee: Option<String>,
+    // launch time or bind time the award was earned at
+    pub time: i64,
+}
+
+// What the engine kept from a flagged account and from its inviter because of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeldPoint {
+    pub held_point: i64,
+    pub inviter_held_point: i64,
+}
+
+fn parse_decimal(name: &str, value: &str) -> anyhow::Result<BigDecimal> {
+    BigDecimal::from_str(value.trim()).map_err(|e| anyhow::anyhow!("invalid {name} {value}: {e}"))
+}
+
+impl PointRules {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let mut early_bird = vec![];
+        for tier in config.points_early_bird.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            let (slot, multiplier) = tier.split_once(':')
+                .ok_or(anyhow::anyhow!("early bird tier must be slot:multiplier, got {tier}"))?;
+            let slot = slot.trim().parse::<i64>()?;
+            early_bird.push((slot, parse_decimal("early bird multiplier", multiplier)?));
+        }
+        early_bird.sort_by_key(|(slot, _)| *slot);
+        Ok(Self {
+            per_sol: parse_decimal("points per sol", &config.points_per_sol)?,
+            per_invite: config.points_per_invite,
+            per_invited: config.points_per_invited,
+            per_invitee_sol: parse_decimal("points per invitee sol", &config.points_per_invitee_sol)?,
+            early_bird,
+        })
+    }
+
+    // First tier the slot is still inside of.
+    pub fn multiplier(&self, slot: i64) -> BigDecimal {
+        self.early_bird.iter()
+            .find(|(end_slot, _)| slot < *end_slot)
+            .map(|(_, multiplier)| multiplier.clone())
+            .unwrap_or(BigDecimal::from(1))
+    }
+}
+
+fn launch_sol(record: &LaunchRecord) -> BigDecimal {
+    to_big_decimal(&record.launch_amount) * BigDecimal::from(LAMPORTS_PER_LAUNCH_UNIT) / BigDecimal::from(LAMPORTS_PER_SOL)
+}
+
+// Whole points, fractions are dropped per award so totals do not depend on summing order.
+fn whole_points(points: BigDecimal) -> i64 {
+    points.with_scale(0).to_i64().unwrap_or_default()
+}
+
+// Every award the rules give out, in a stable order.
+pub fn compute_awards(rules: &PointRules, accounts: &[PointAccount], launches: &[LaunchRecord]) -> Vec<PointAward> {
+    let inviters: HashMap<&str, &str> = accounts.iter()
+        .filter_map(|a| a.inviter.as_deref().map(|i| (a.address.as_str(), i)))
+        .collect();
+    let mut awards = vec![];
+    for account in accounts {
+        if let Some(inviter) = &account.inviter {
+            awards.push(PointAward {
+                address: inviter.clone(),
+                point: rules.per_invite,
+                reason: REASON_INVITE.to_string(),
+                source: account.address.clone(),
+                invitee: Some(account.address.clone()),
+                time: account.create_time,
+            });
+            awards.push(PointAward {
+                address: account.address.clone(),
+                point: rules.per_invited,
+                reason: REASON_INVITED.to_string(),
+                source: inviter.clone(),
+                invitee: None,
+                time: account.create_time,
+            });
+        }
+        if let (Some(code), true) = (&account.invite_code_used, account.bonus_point > 0) {
+            awards.push(PointAward {
+                address: account.address.clone(),
+                point: account.bonus_point,
+                reason: REASON_CAMPAIGN_BONUS.to_string(),
+                source: code.clone(),
+                invitee: None,
+                time: account.create_time,
+            });
+        }
+    }
+    for record in launches {
+        let sol = launch_sol(record) * rules.multiplier(record.launch_block);
+        let source = format!("{}:{}", record.launch_tx_hash, record.log_index);
+        awards.push(PointAward {
+            address: record.address.clone(),
+            point: whole_points(sol.clone() * rules.per_sol.clone()),
+            reason: REASON_MINT.to_string(),
+            source: source.clone(),
+            invitee: None,
+            time: record.launch_time,
+        });
+        if let Some(inviter) = inviters.get(record.address.as_str()) {
+            awards.push(PointAward {
+                address: inviter.to_string(),
+                point: whole_points(sol * rules.per_invitee_sol.clone()),
+                reason: REASON_INVITEE_MINT.to_string(),
+                source,
+                invitee: Some(record.address.clone()),
+                time: record.launch_time,
+            });
+        }
+    }
+    awards.retain(|a| a.point != 0);
+    awards
+}
+
+// Takes back the awards of withheld accounts and the awards their inviters earned through them with a
+// withheld award next to each, so the ledger keeps the award and shows why it does not count.
+// Returns the awards with the withheld ones and what was held per withheld account.
+pub fn withhold_awards(awards: Vec<PointAward>, withheld: &HashSet<String>) -> (Vec<PointAward>, BTreeMap<String, HeldPoint>) {
+    let mut held: BTreeMap<String, HeldPoint> = BTreeMap::new();
+    let mut kept = vec![];
+    for award in awards {
+        if withheld.contains(&award.address) {
+            held.entry(award.address.clone()).or_default().held_point += award.point;
+        } else if let Some(invitee) = award.invitee.as_ref().filter(|i| withheld.contains(*i)) {
+            held.entry(invitee.clone()).or_default().inviter_held_point += award.point;
+        } else {
+            kept.push(award);
+            continue;
+        }
+        kept.push(PointAward {
+            address: award.address.clone(),
+            point: -award.point,
+            reason: REASON_WITHHELD.to_string(),
+            source: format!("{}:{}", award.reason, award.source),
+            invitee: award.invitee.clone(),
+            time: award.time,
+        });
+        kept.push(award);
+    }
+    (kept, held)
+}
+
+// Events that bring the ledger from its current totals to the awards. Every event is dated when the award
+// was earned, so seasons count it in the right window. An award that grew, shrank or went away, e.g. because
+// its account got flagged or cleared, is corrected by a new event instead of rewriting the old one, dated
+// like the award or, when it went away, like its first event. A correction of a frozen season changes
+// the ledger but not the snapshot.
+pub fn ledger_events(awards: &[PointAward], totals: &[PointEventTotal]) -> Vec<PointEvent> {
+    let mut deltas: BTreeMap<(&str, &str, &str), (i64, i64)> = BTreeMap::new();
+    for award in awards {
+        let entry = deltas.entry((award.address.as_str(), award.reason.as_str(), award.source.as_str()))
+            .or_insert((0, award.time));
+        entry.0 += award.point;
+    }
+    for total in totals {
+        let entry = deltas.entry((total.address.as_str(), total.reason.as_str(), total.source.as_str()))
+            .or_insert((0, total.create_time));
+        entry.0 -= total.point;
+    }
+    deltas.into_iter()
+        .filter(|(_, (delta, _))| *delta != 0)
+        .map(|((address, reason, source), (delta, time))| PointEvent {
+            event_id: None,
+            address: address.to_string(),
+            delta,
+            reason: reason.to_string(),
+            source: source.to_string(),
+            create_time: time,
+        })
+        .collect()
+}
+
+// Events a new account and its inviter earn at bind. They are saved with the account, so it does not show 0
+// points until the next engine run, which then finds them in the ledger and appends nothing for them.
+pub fn bind_events(rules: &PointRules, account: &PointAccount) -> Vec<PointEvent> {
+    ledger_events(&compute_awards(rules, std::slice::from_ref(account), &[]), &[])
+}
+
+// Recomputes every award from launch_records and accounts, records the difference in the point_events
+// ledger and refreshes the accounts.point cache from it. Running it twice appends nothing the second time.
+// Seasons that ended before the sync started are frozen at the end of it.
+// Every run reads all accounts, launch records and ledger totals, so its cost grows with the whole history,
+// not with what changed since the last run. Bind points are written by the bind itself, what waits for a run
+// is mint points and corrections.
+pub async fn sync_points(rb: &mut RBatis, rules: &PointRules) -> anyhow::Result<usize> {
+    let synced_at = now_secs();
+    let launches = db::get_all_launch_records(rb).await?;
+    let withheld = db::get_withheld_addresses(rb).await?.into_iter().collect::<HashSet<_>>();
+    let mut held = BTreeMap::new();
+    let appended = db::sync_point_events(rb, |accounts, totals| {
+        let awards;
+        (awards, held) = withhold_awards(compute_awards(rules, accounts, &launches), &withheld);
+        ledger_events(&awards, totals)
+    }).await?;
+    db::update_abuse_held_points(rb, &held).await?;
+    season::freeze_ended_seasons(rb, synced_at).await?;
+    Ok(appended)
+}
+
+#[cfg(test)]
+mod test {
+    use rbatis::rbdc::decimal::Decimal;
+    use crate::db::fixture::test_db;
+    use super::*;
+
+    fn account(address: &str, inviter: Option<&str>) -> PointAccount {
+        PointAccount {
+            address: address.to_string(),
+            inviter: inviter.map(|i| i.to_string()),
+            invite_code_used: None,
+            bonus_point: 0,
+            create_time: 0,
+        }
+    }
+
+    fn launch(address: &str, amount: &str, slot: i64) -> LaunchRecord {
+        LaunchRecord {
+            address: address.to_string(),
+            launch_amount: Decimal::from_str(amount).unwrap(),
+            launch_block: slot,
+            launch_tx_hash: format!("tx{slot}"),
+            log_index: 0,
+            launch_time: slot,
+        }
+    }
+
+    #[test]
+    fn test_points_rules() {
+        let rules = PointRules {
+            per_sol: BigDecimal::from(100),
+            per_invite: 1000,
+            per_invited: 500,
+            per_invitee_sol: BigDecimal::from(10),
+            early_bird: vec![(100, BigDecimal::from(2))],
+        };
+        let accounts = vec![account("a", None), account("b", Some("a")), account("c", Some("a"))];
+        // 10 launch units are 1 SOL
+        let launches = vec![launch("b", "10", 50), launch("b", "10", 150), launch("c", "5", 200)];
+        let awards = compute_awards(&rules, &accounts, &launches);
+        let points = |awards: &[PointAward]| accounts.iter()
+            .map(|a| awards.iter().filter(|w| w.address == a.address).map(|w| w.point).sum::<i64>())
+            .collect::<Vec<_>>();
+        assert_eq!(points(&awards), vec![2000 + 20 + 10 + 5, 500 + 200 + 100, 500 + 50]);
+        assert_eq!(compute_awards(&rules, &accounts, &launches), awards);
+
+        let withheld = HashSet::from(["b".to_string()]);
+        let (kept, held) = withhold_awards(awards, &withheld);
+        assert_eq!(points(&kept), vec![1000 + 5, 0, 550]);
+        assert_eq!(held["b"], HeldPoint { held_point: 800, inviter_held_point: 1030 });
+        let taken_back = kept.iter().find(|w| w.reason == REASON_WITHHELD && w.address == "a").unwrap();
+        assert_eq!((taken_back.point, taken_back.source.as_str()), (-1000, "invite:b"));
+    }
+
+    #[test]
+    fn test_ledger_events() {
+        let award = |address: &str, point: i64, reason: &str, source: &str| PointAward {
+            address: address.to_string(),
+            point,
+            reason: reason.to_string(),
+            source: source.to_string(),
+            invitee: None,
+            time: 5,
+        };
+        let total = |address: &str, point: i64, reason: &str, source: &str| PointEventTotal {
+            address: address.to_string(),
+            reason: reason.to_string(),
+            source: source.to_string(),
+            point,
+            create_time: 3,
+        };
+        let awards = vec![award("a", 1000, REASON_INVITE, "b"), award("a", 30, REASON_INVITEE_MINT, "tx1:0")];
+        let events = ledger_events(&awards, &[]);
+        assert_eq!(events.iter().map(|e| (e.delta, e.create_time)).collect::<Vec<_>>(), vec![(1000, 5), (30, 5)]);
+
+        let totals = vec![total("a", 1000, REASON_INVITE, "b"), total("a", 20, REASON_INVITEE_MINT, "tx1:0"),
+                          total("c", 500, REASON_INVITED, "a")];
+        let events = ledger_events(&awards, &totals);
+        assert_eq!(events.iter().map(|e| (e.address.as_str(), e.delta)).collect::<Vec<_>>(), vec![("a", 10), ("c", -500)]);
+        // corrections stay in the window of the award, or of the first event when the award went away
+        assert_eq!(events.iter().map(|e| (e.create_time, e.reason.as_str())).collect::<Vec<_>>(),
+                   vec![(5, REASON_INVITEE_MINT), (3, REASON_INVITED)]);
+        assert!(ledger_events(&awards, &[total("a", 1000, REASON_INVITE, "b"), total("a", 30, REASON_INVITEE_MINT, "tx1:0")]).is_empty());
+    }
+
+    #[test]
+    fn test_bind_events() {
+        let rules = PointRules {
+            per_sol: BigDecimal::from(100),
+            per_invite: 1000,
+            per_invited: 500,
+            per_invitee_sol: BigDecimal::from(10),
+            early_bird: vec![],
+        };
+        let mut invitee = account("b", Some("a"));
+        invitee.invite_code_used = Some("LAUNCH".to_string());
+        invitee.bonus_point = 50;
+        invitee.create_time = 7;
+        let events = bind_events(&rules, &invitee);
+        assert_eq!(events.iter().map(|e| (e.address.as_str(), e.delta, e.reason.as_str(), e.create_time)).collect::<Vec<_>>(),
+                   vec![("a", 1000, REASON_INVITE, 7), ("b", 50, REASON_CAMPAIGN_BONUS, 7), ("b", 500, REASON_INVITED, 7)]);
+        // the engine sees the same awards and has nothing left to append
+        let totals = events.iter().map(|e| PointEventTotal {
+            address: e.address.clone(),
+            reason: e.reason.clone(),
+            source: e.source.clone(),
+            point: e.delta,
+            create_time: e.create_time,
+        }).collect::<Vec<_>>();
+        assert!(ledger_events(&compute_awards(&rules, &[invitee], &[]), &totals).is_empty());
+        assert!(bind_events(&rules, &account("c", None)).is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_concurrent_syncs_append_once() {
+        let db = test_db().await;
+        let rb = db.rb.clone();
+        for (address, inviter) in [("a", None), ("b", Some("a")), ("c", Some("a"))] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter)]).await.unwrap();
+        }
+        rb.exec("insert into abuse_flags (address,reasons,detail,status,inviter,create_time) values ('c','ip_burst','','open','a',0)",
+                vec![]).await.unwrap();
+        let rules = PointRules {
+            per_sol: BigDecimal::from(100),
+            per_invite: 1000,
+            per_invited: 500,
+            per_invitee_sol: BigDecimal::from(10),
+            early_bird: vec![],
+        };
+        let sync = || {
+            let mut rb = rb.clone();
+            let rules = rules.clone();
+            async move { sync_points(&mut rb, &rules).await.unwrap() }
+        };
+        let (first, second) = tokio::join!(sync(), sync());
+        // invite and invited of b, the same two for c and a withheld event next to each
+        assert_eq!(first + second, 6);
+        assert_eq!(sync().await, 0);
+        let points = db::get_point_balances(&rb).await.unwrap().into_iter().map(|b| b.point).collect::<Vec<_>>();
+        assert_eq!(points, vec![1000, 500, 0]);
+        let withheld: Vec<PointEvent> = rb.query_decode("select * from point_events where reason = ? order by source",
+                                                        vec![rbs::to_value!(REASON_WITHHELD)]).await.unwrap();
+        assert_eq!(withheld.iter().map(|e| (e.address.as_str(), e.source.as_str())).collect::<Vec<_>>(),
+                   vec![("a", "invite:c"), ("c", "invited:a")]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_corrections_stay_in_their_season() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        for (address, inviter) in [("a", None), ("b", Some("a"))] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,10,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter)]).await.unwrap();
+        }
+        let rules = PointRules {
+            per_sol: BigDecimal::from(100),
+            per_invite: 1000,
+            per_invited: 500,
+            per_invitee_sol: BigDecimal::from(10),
+            early_bird: vec![],
+        };
+        let closed = db::create_season(&mut rb, "s1", 0, 1000, 0).await.unwrap().unwrap();
+        let current = db::create_season(&mut rb, "s2", 1000, i64::MAX / 2, 0).await.unwrap().unwrap();
+        assert_eq!(sync_points(&mut rb, &rules).await.unwrap(), 2);
+        rb.exec("insert into abuse_flags (address,reasons,detail,status,inviter,create_time) values ('b','ip_burst','','open','a',0)",
+                vec![]).await.unwrap();
+        assert_eq!(sync_points(&mut rb, &rules).await.unwrap(), 2);
+
+        let closed = db::get_season(&rb, closed.season_id).await.unwrap().unwrap();
+        assert_eq!(db::get_point_rank(&rb, &closed, "a").await.unwrap().unwrap().point, 1000);
+        assert!(db::get_point_rank(&rb, &current, "a").await.unwrap().is_none());
+        let balances = db::get_point_balances(&rb).await.unwrap().into_iter().map(|b| b.point).collect::<Vec<_>>();
+        assert_eq!(balances, vec![0, 0]);
+    }
+}
@@ -0,0 +1,78 @@
+use rbatis::RBatis;
+use crate::db;
+use crate::db::tables::{PointRank, Season};
+
+pub const SEASON_UPCOMING: &str = "upcoming";
+pub const SEASON_ACTIVE: &str = "active";
+// over, waiting for the points sync that freezes its snapshot
+pub const SEASON_ENDED: &str = "ended";
+pub const SEASON_FROZEN: &str = "frozen";
+
+pub fn season_status(season: &Season, now: i64) -> &'static str {
+    if season.snapshot_time.is_some() {
+        SEASON_FROZEN
+    } else if now < season.start_time {
+        SEASON_UPCOMING
+    } else if now < season.end_time {
+        SEASON_ACTIVE
+    } else {
+        SEASON_ENDED
+    }
+}
+
+// Freezes every season that ended before synced_at, the start of a points sync that went through,
+// so the snapshot holds every launch the sync could see.
+pub async fn freeze_ended_seasons(rb: &mut RBatis, synced_at: i64) -> anyhow::Result<usize> {
+    let mut frozen = 0;
+    for season in db::get_unfrozen_seasons(rb, synced_at).await? {
+        if let Some(count) = db::freeze_season_snapshot(rb, &season, synced_at).await? {
+            log::info!("season {} {} frozen with {count} ranked accounts", season.season_id, season.name);
+            frozen += 1;
+        }
+    }
+    Ok(frozen)
+}
+
+pub fn snapshot_csv(ranks: &[PointRank]) -> String {
+    let mut csv = "rank,address,point\n".to_string();
+    for rank in ranks {
+        csv += &format!("{},{},{}\n", rank.rank, rank.address, rank.point);
+    }
+    csv
+}
+
+// The frozen season end snapshot as csv, ready for rewards distribution.
+pub async fn export_season_snapshot(rb: &RBatis, season_id: i64) -> anyhow::Result<(usize, String)> {
+    let season = db::get_season(rb, season_id).await?
+        .ok_or(anyhow::anyhow!("Season {season_id} not found"))?;
+    if season.snapshot_time.is_none() {
+        anyhow::bail!("Season {season_id} is not frozen yet");
+    }
+    let ranks = db::get_season_snapshot(rb, season_id).await?;
+    Ok((ranks.len(), snapshot_csv(&ranks)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_season_status() {
+        let mut season = Season {
+            season_id: 1,
+            name: "season 1".to_string(),
+            start_time: 100,
+            end_time: 200,
+            snapshot_time: None,
+            create_time: 0,
+        };
+        assert_eq!(season_status(&season, 50), SEASON_UPCOMING);
+        assert_eq!(season_status(&season, 100), SEASON_ACTIVE);
+        assert_eq!(season_status(&season, 200), SEASON_ENDED);
+        season.snapshot_time = Some(260);
+        assert_eq!(season_status(&season, 300), SEASON_FROZEN);
+
+        let ranks = vec![PointRank { address: "a".to_string(), point: 30, rank: 1 }];
+        assert_eq!(snapshot_csv(&ranks), "rank,address,point\n1,a,30\n");
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use crate::db;
+use crate::db::tables::{RebateBatch, RebateEntry};
+use crate::rebate::{ComputedRebate, RebatePolicy};
+use crate::rebate::payout::RebatePayout;
+use crate::referral::to_big_decimal;
+use crate::transfer::pack_instruction_groups;
+use crate::time::now_secs;
+
+pub const REBATE_BATCH_PENDING: &str = "pending";
+pub const REBATE_BATCH_SENT: &str = "sent";
+pub const REBATE_BATCH_CONFIRMED: &str = "confirmed";
+
+pub fn to_ledger_entries(computed: &[ComputedRebate], create_time: i64) -> Vec<RebateEntry> {
+    computed.iter()
+        .filter(|c| !c.rebate.is_zero())
+        .map(|c| RebateEntry {
+            launch_tx_hash: c.launch_tx_hash.clone(),
+            log_index: c.log_index,
+            inviter: c.inviter.clone(),
+            address: c.address.clone(),
+            level: c.level,
+            launch_amount: Decimal::from_str(&c.launch_amount.to_string()).unwrap(),
+            rate: Decimal::from_str(&c.rate.to_string()).unwrap(),
+            rebate_amount: Decimal::from_str(&c.rebate.to_string()).unwrap(),
+            batch_id: None,
+            create_time,
+        })
+        .collect()
+}
+
+// Accrues an entry for every launch record that earns an inviter a rebate and has no entry yet.
+// Only unrecorded launches are computed, against what each inviter already accrued, so a launch that arrives
+// late can not push an inviter past the cap. Rebates of flagged inviters or invitees wait until the flag is cleared,
+// they are left out before the cap is applied so they do not use up the room of clean launches.
+pub async fn sync_rebate_ledger(rb: &mut RBatis, policy: &RebatePolicy) -> anyhow::Result<u64> {
+    let withheld = db::get_withheld_addresses(rb).await?.into_iter().collect::<HashSet<_>>();
+    let launches = db::get_unrecorded_referral_launches(rb, policy.depth()).await?.into_iter()
+        .filter(|l| !withheld.contains(&l.inviter) && !withheld.contains(&l.address))
+        .collect::<Vec<_>>();
+    let earned = db::get_recorded_rebate_totals(rb).await?.into_iter()
+        .map(|t| (t.inviter, to_big_decimal(&t.amount)))
+        .collect::<HashMap<_, _>>();
+    let computed = policy.compute_from(&launches, earned);
+    let entries = to_ledger_entries(&computed, now_secs());
+    db::save_rebate_entries(rb, &entries).await
+}
+
+// Groups everything accrued outside a batch into new pending payout batches, each batch fills one transaction.
+// Inviters owed less than the smallest transfer keep accruing until the next run, what rounding leaves out of a
+// transfer is carried into the next batch.
+pub async fn create_rebate_batches(rb: &mut RBatis, payout: &RebatePayout) -> anyhow::Result<Vec<RebateBatch>> {
+    let mut totals = vec![];
+    for total in db::get_unbatched_rebate_totals(rb).await? {
+        if payout.amount(&to_big_decimal(&total.amount))? >= payout.min_amount() {
+            totals.push(total);
+        }
+    }
+    let mut groups = vec![];
+    for total in &totals {
+        groups.push(payout.inviter_instructions(total)?);
+    }
+    let mut batches = vec![];
+    for pack in pack_instruction_groups(&payout.treasury, &groups)? {
+        let inviters = pack.iter().map(|i| totals[*i].inviter.clone()).collect::<Vec<_>>();
+        let carry = |owed: &BigDecimal| Ok(owed.clone() - payout.paid_rebate(owed)?);
+        batches.push(db::create_rebate_batch(rb, &inviters, carry, now_secs()).await?);
+    }
+    Ok(batches)
+}
+
+pub async fn record_rebate_signature(rb: &mut RBatis, batch_id: i64, signature: &str) -> anyhow::Result<()> {
+    let updated = db::update_rebate_batch_signature(rb, batch_id, signature, now_secs()).await?;
+    if updated == 0 {
+        anyhow::bail!("rebate batch {batch_id} is not pending");
+    }
+    Ok(())
+}
+
+// Marks a sent batch paid once its signature has been checked on chain.
+pub async fn confirm_rebate_batch(rb: &mut RBatis, batch_id: i64) -> anyhow::Result<()> {
+    let batch = db::get_rebate_batch(rb, batch_id).await?
+        .ok_or(anyhow::anyhow!("rebate batch {batch_id} not found"))?;
+    if batch.status != REBATE_BATCH_SENT {
+        anyhow::bail!("rebate batch {batch_id} is {}, only sent batches can be confirmed", batch.status);
+    }
+    db::update_rebate_batch_status(rb, batch_id, REBATE_BATCH_CONFIRMED, now_secs()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use solana_sdk::pubkey::Pubkey;
+    use crate::db::fixture::test_db;
+    use crate::db::tables::LaunchRecord;
+    use crate::rebate::payout::PayoutAsset;
+    use crate::referral::ReferralRates;
+    use super::*;
+
+    fn computed(inviter: &str, log_index: i32, rebate: &str) -> ComputedRebate {
+        ComputedRebate {
+            inviter: inviter.to_string(),
+            address: "invitee".to_string(),
+            level: 1,
+            launch_tx_hash: "tx".to_string(),
+            log_index,
+            launch_time: 100,
+            launch_amount: BigDecimal::from(100),
+            rate: BigDecimal::from_str("0.1").unwrap(),
+            rebate: BigDecimal::from_str(rebate).unwrap(),
+        }
+    }
+
+    fn launch(address: &str, tx_hash: &str, launch_time: i64) -> LaunchRecord {
+        LaunchRecord {
+            address: address.to_string(),
+            launch_amount: Decimal::from_str("100").unwrap(),
+            launch_block: launch_time,
+            launch_tx_hash: tx_hash.to_string(),
+            log_index: 0,
+            launch_time,
+        }
+    }
+
+    #[test]
+    fn test_to_ledger_entries() {
+        let entries = to_ledger_entries(&[computed("a", 0, "10"), computed("a", 1, "0")], 7);
+        // capped launches earn nothing and leave no entry
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].rebate_amount.to_string(), "10");
+        assert_eq!(entries[0].rate.to_string(), "0.1");
+        assert_eq!(entries[0].create_time, 7);
+        assert!(entries[0].batch_id.is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_sync_and_batch_rebates() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let inviter = Pubkey::new_unique().to_string();
+        for (address, parent) in [(inviter.as_str(), None), ("invitee", Some(inviter.as_str()))] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(parent)]).await.unwrap();
+        }
+        let policy = RebatePolicy {
+            base_rates: ReferralRates::parse("0.1").unwrap(),
+            boosts: vec![],
+            inviter_cap: Some(BigDecimal::from(15)),
+            overrides: HashMap::new(),
+        };
+        db::save_launch_records(&mut rb, &vec![launch("invitee", "tx2", 200)]).await.unwrap();
+        assert_eq!(sync_rebate_ledger(&mut rb, &policy).await.unwrap(), 1);
+        // an earlier launch seen late only gets what is left under the cap
+        db::save_launch_records(&mut rb, &vec![launch("invitee", "tx1", 100)]).await.unwrap();
+        assert_eq!(sync_rebate_ledger(&mut rb, &policy).await.unwrap(), 1);
+        assert_eq!(sync_rebate_ledger(&mut rb, &policy).await.unwrap(), 0);
+        let totals = db::get_unbatched_rebate_totals(&rb).await.unwrap();
+        assert_eq!(to_big_decimal(&totals[0].amount), BigDecimal::from(15));
+
+        let payout = RebatePayout { treasury: Pubkey::new_unique(), asset: PayoutAsset::Sol };
+        let batches = create_rebate_batches(&mut rb, &payout).await.unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].status, REBATE_BATCH_PENDING);
+        assert_eq!(to_big_decimal(&batches[0].total_amount), BigDecimal::from(15));
+        assert!(create_rebate_batches(&mut rb, &payout).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_flagged_invitee_leaves_cap_room() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let inviter = Pubkey::new_unique().to_string();
+        for (address, parent) in [(inviter.as_str(), None), ("flagged", Some(inviter.as_str())), ("clean", Some(inviter.as_str()))] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(parent)]).await.unwrap();
+        }
+        rb.exec("insert into abuse_flags (address,reasons,detail,status,inviter,create_time) values ('flagged','ip_burst','','open',?,0)",
+                vec![rbs::to_value!(inviter.clone())]).await.unwrap();
+        let policy = RebatePolicy {
+            base_rates: ReferralRates::parse("0.1").unwrap(),
+            boosts: vec![],
+            inviter_cap: Some(BigDecimal::from(10)),
+            overrides: HashMap::new(),
+        };
+        db::save_launch_records(&mut rb, &vec![launch("flagged", "tx1", 100), launch("clean", "tx2", 200)]).await.unwrap();
+        assert_eq!(sync_rebate_ledger(&mut rb, &policy).await.unwrap(), 1);
+        // the earlier launch of the flagged invitee does not take the room under the cap
+        let totals = db::get_unbatched_rebate_totals(&rb).await.unwrap();
+        assert_eq!(to_big_decimal(&totals[0].amount), BigDecimal::from(10));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_batch_holds_small_sol_and_carries_dust() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let (owed, small) = (Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string());
+        let entries = to_ledger_entries(&[computed(&owed, 0, "0.123456789"), computed(&small, 1, "0.001")], 7);
+        db::save_rebate_entries(&mut rb, &entries).await.unwrap();
+        let payout = RebatePayout { treasury: Pubkey::new_unique(), asset: PayoutAsset::Sol };
+        let batches = create_rebate_batches(&mut rb, &payout).await.unwrap();
+        assert_eq!(batches.len(), 1);
+        // the batch holds exactly what the transfer moves
+        assert_eq!(to_big_decimal(&batches[0].total_amount), BigDecimal::from_str("0.12345678").unwrap());
+        let unbatched = db::get_unbatched_rebate_totals(&rb).await.unwrap().into_iter()
+            .map(|t| (t.inviter, to_big_decimal(&t.amount)))
+            .collect::<HashMap<_, _>>();
+        // the dust is carried, the small sol rebate stays below the rent exempt minimum and keeps accruing
+        assert_eq!(unbatched[&owed], BigDecimal::from_str("0.000000009").unwrap());
+        assert_eq!(unbatched[&small], BigDecimal::from_str("0.001").unwrap());
+        let summary = db::get_rebate_summary(&rb, &owed).await.unwrap();
+        assert_eq!(to_big_decimal(&summary.accrued), BigDecimal::from_str("0.123456789").unwrap());
+    }
+}
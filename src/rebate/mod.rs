@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+use crate::config::Config;
+use crate::db::tables::ReferralLaunch;
+use crate::referral::{to_big_decimal, ReferralRates};
+
+pub mod ledger;
+pub mod payout;
+
+// Rebates earned on launches inside [start_time, end_time) are multiplied.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RebateBoost {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub multiplier: String,
+}
+
+// Partner terms for one inviter, unset fields fall back to the base policy.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InviterOverride {
+    pub level_rates: Option<String>,
+    pub cap: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct RebatePolicyFile {
+    #[serde(default)]
+    boosts: Vec<RebateBoost>,
+    // max total rebate one inviter can earn
+    inviter_cap: Option<String>,
+    // keyed by inviter solana address
+    #[serde(default)]
+    overrides: BTreeMap<String, InviterOverride>,
+}
+
+#[derive(Clone, Debug)]
+struct InviterTerms {
+    rates: Option<ReferralRates>,
+    cap: Option<BigDecimal>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RebatePolicy {
+    base_rates: ReferralRates,
+    boosts: Vec<(i64, i64, BigDecimal)>,
+    inviter_cap: Option<BigDecimal>,
+    overrides: HashMap<String, InviterTerms>,
+}
+
+// Rebate an inviter earns on one launch record of someone in their subtree.
+#[derive(Clone, Debug)]
+pub struct ComputedRebate {
+    pub inviter: String,
+    pub address: String,
+    pub level: i32,
+    pub launch_tx_hash: String,
+    pub log_index: i32,
+    pub launch_time: i64,
+    pub launch_amount: BigDecimal,
+    pub rate: BigDecimal,
+    pub rebate: BigDecimal,
+}
+
+fn parse_decimal(value: &str) -> anyhow::Result<BigDecimal> {
+    BigDecimal::from_str(value.trim()).map_err(|e| anyhow::anyhow!("invalid decimal {value}: {e}"))
+}
+
+impl RebatePolicy {
+    pub fn load(config: &Config) -> anyhow::Result<Self> {
+        let base_rates = ReferralRates::from_config(config)?;
+        let file = if config.rebate_policy_path.is_empty() {
+            RebatePolicyFile::default()
+        } else {
+            serde_json::from_str(&std::fs::read_to_string(&config.rebate_policy_path)?)?
+        };
+        let mut boosts = vec![];
+        for boost in &file.boosts {
+            if boost.end_time <= boost.start_time {
+                anyhow::bail!("rebate boost ends before it starts: {:?}", boost);
+            }
+            boosts.push((boost.start_time, boost.end_time, parse_decimal(&boost.multiplier)?));
+        }
+        let mut overrides = HashMap::new();
+        for (inviter, terms) in &file.overrides {
+            overrides.insert(inviter.clone(), InviterTerms {
+                rates: terms.level_rates.as_deref().map(ReferralRates::parse).transpose()?,
+                cap: terms.cap.as_deref().map(parse_decimal).transpose()?,
+            });
+        }
+        let policy = Self {
+            base_rates,
+            boosts,
+            inviter_cap: file.inviter_cap.as_deref().map(parse_decimal).transpose()?,
+            overrides,
+        };
+        log::info!("rebate policy loaded: {} levels, {} boosts, {} overrides",
+                   policy.depth(), policy.boosts.len(), policy.overrides.len());
+        Ok(policy)
+    }
+
+    // Deepest level any inviter is paid on, the referral queries walk this far.
+    pub fn depth(&self) -> usize {
+        self.overrides.values()
+            .filter_map(|t| t.rates.as_ref().map(|r| r.depth()))
+            .fold(self.base_rates.depth(), usize::max)
+    }
+
+    // Rate of the inviter for a level before boosts.
+    pub fn base_rate(&self, inviter: &str, level: usize) -> BigDecimal {
+        self.overrides.get(inviter)
+            .and_then(|t| t.rates.as_ref())
+            .unwrap_or(&self.base_rates)
+            .rate(level)
+    }
+
+    // Rate of the inviter for a level on a launch at launch_time, boosts included.
+    pub fn effective_rate(&self, inviter: &str, level: usize, launch_time: i64) -> BigDecimal {
+        self.base_rate(inviter, level) * self.boost_multiplier(launch_time)
+    }
+
+    fn boost_multiplier(&self, launch_time: i64) -> BigDecimal {
+        self.boosts.iter()
+            .filter(|(start, end, _)| *start <= launch_time && launch_time < *end)
+            .map(|(_, _, multiplier)| multiplier.clone())
+            .max()
+            .unwrap_or(BigDecimal::from(1))
+    }
+
+    fn cap(&self, inviter: &str) -> Option<BigDecimal> {
+        self.overrides.get(inviter)
+            .and_then(|t| t.cap.clone())
+            .or(self.inviter_cap.clone())
+    }
+
+    // Launches are paid in chain order, once an inviter hits the cap later launches earn nothing.
+    pub fn compute(&self, launches: &[ReferralLaunch]) -> Vec<ComputedRebate> {
+        self.compute_from(launches, HashMap::new())
+    }
+
+    // Like compute, with what each inviter has already earned counting against their cap.
+    pub fn compute_from(&self, launches: &[ReferralLaunch], mut earned: HashMap<String, BigDecimal>) -> Vec<ComputedRebate> {
+        let mut sorted = launches.to_vec();
+        sorted.sort_by(|a, b| (a.launch_time, &a.launch_tx_hash, a.log_index, a.level)
+            .cmp(&(b.launch_time, &b.launch_tx_hash, b.log_index, b.level)));
+        let mut entries = vec![];
+        for launch in sorted {
+            let launch_amount = to_big_decimal(&launch.launch_amount);
+            let rate = self.effective_rate(&launch.inviter, launch.level as usize, launch.launch_time);
+            let mut rebate = launch_amount.clone() * rate.clone();
+            let total = earned.entry(launch.inviter.clone()).or_insert(BigDecimal::zero());
+            if let Some(cap) = self.cap(&launch.inviter) {
+                let room = if *total >= cap { BigDecimal::zero() } else { cap - total.clone() };
+                if rebate > room {
+                    rebate = room;
+                }
+            }
+            *total += rebate.clone();
+            entries.push(ComputedRebate {
+                inviter: launch.inviter,
+                address: launch.address,
+                level: launch.level,
+                launch_tx_hash: launch.launch_tx_hash,
+                log_index: launch.log_index,
+                launch_time: launch.launch_time,
+                launch_amount,
+                rate,
+                rebate,
+            });
+        }
+        entries
+    }
+}
+
+pub fn total_rebate(entries: &[ComputedRebate]) -> BigDecimal {
+    entries.iter().fold(BigDecimal::zero(), |acc, e| acc + e.rebate.clone())
+}
+
+// (mint amount, rebate) per referral level
+pub fn rebate_by_level(entries: &[ComputedRebate]) -> BTreeMap<i32, (BigDecimal, BigDecimal)> {
+    let mut levels: BTreeMap<i32, (BigDecimal, BigDecimal)> = BTreeMap::new();
+    for entry in entries {
+        let level = levels.entry(entry.level).or_insert((BigDecimal::zero(), BigDecimal::zero()));
+        level.0 += entry.launch_amount.clone();
+        level.1 += entry.rebate.clone();
+    }
+    levels
+}
+
+#[cfg(test)]
+mod test {
+    use rbatis::rbdc::decimal::Decimal;
+    use super::*;
+
+    fn launch(inviter: &str, level: i32, amount: &str, launch_time: i64) -> ReferralLaunch {
+        ReferralLaunch {
+            inviter: inviter.to_string(),
+            address: format!("invitee{launch_time}"),
+            level,
+            launch_tx_hash: format!("tx{launch_time}"),
+            log_index: 0,
+            launch_amount: Decimal::from_str(amount).unwrap(),
+            launch_time,
+        }
+    }
+
+    #[test]
+    fn test_overrides_boosts_and_caps() {
+        let mut overrides = HashMap::new();
+        overrides.insert("kol".to_string(), InviterTerms {
+            rates: Some(ReferralRates::parse("0.2").unwrap()),
+            cap: None,
+        });
+        let policy = RebatePolicy {
+            base_rates: ReferralRates::parse("0.1,0.03").unwrap(),
+            boosts: vec![(100, 200, BigDecimal::from(2))],
+            inviter_cap: Some(BigDecimal::from(25)),
+            overrides,
+        };
+        let entries = policy.compute(&[
+            launch("base", 1, "100", 10),
+            launch("base", 2, "100", 20),
+            launch("base", 1, "100", 150),
+            launch("base", 1, "100", 300),
+            launch("kol", 1, "100", 30),
+            launch("kol", 2, "100", 40),
+        ]);
+        let rebates = |inviter: &str| entries.iter()
+            .filter(|e| e.inviter == inviter)
+            .map(|e| e.rebate.clone())
+            .collect::<Vec<_>>();
+        // the boosted launch is cut to what is left under the cap, later launches earn nothing
+        assert_eq!(rebates("base"), [10, 3, 12, 0].map(BigDecimal::from).to_vec());
+        // the partner rate only covers direct invitees
+        assert_eq!(rebates("kol"), [20, 0].map(BigDecimal::from).to_vec());
+        assert_eq!(policy.base_rate("kol", 2), BigDecimal::zero());
+        assert_eq!(policy.effective_rate("base", 1, 150), BigDecimal::from_str("0.2").unwrap());
+        assert_eq!(policy.effective_rate("base", 1, 200), BigDecimal::from_str("0.1").unwrap());
+        assert_eq!(policy.depth(), 2);
+        // an inviter that already earned 20 has 5 left under the cap
+        let earned = HashMap::from([("base".to_string(), BigDecimal::from(20))]);
+        let entries = policy.compute_from(&[launch("base", 1, "100", 10)], earned);
+        assert_eq!(entries[0].rebate, BigDecimal::from(5));
+    }
+}
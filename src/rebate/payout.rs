@@ -0,0 +1,218 @@
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use rbatis::RBatis;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::system_instruction;
+use crate::allocation::to_base_units;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::RebateInviterTotal;
+use crate::rebate::ledger::{REBATE_BATCH_CONFIRMED, REBATE_BATCH_PENDING, REBATE_BATCH_SENT};
+use crate::referral::to_big_decimal;
+use crate::transfer::{build_unsigned_transaction, create_associated_token_account_idempotent, encode_transaction,
+                      get_signature_states, token_transfer_checked, SignatureState};
+use crate::watcher::watcher::LAMPORTS_PER_LAUNCH_UNIT;
+use crate::time::now_secs;
+
+// precision of the paid part of a rebate whose conversion to tokens does not divide evenly
+const PAID_REBATE_SCALE: i64 = 18;
+
+#[derive(Clone, Debug)]
+pub enum PayoutAsset {
+    Sol,
+    // rebates are converted at tokens_per_unit tokens for every launch unit
+    Spl { mint: Pubkey, decimals: u8, tokens_per_unit: BigDecimal },
+}
+
+// Where rebates are paid from and in what.
+#[derive(Clone, Debug)]
+pub struct RebatePayout {
+    pub treasury: Pubkey,
+    pub asset: PayoutAsset,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RebatePayoutTransfer {
+    pub inviter: String,
+    pub rebate_amount: String,
+    // lamports or token base units
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RebatePayoutTransaction {
+    pub batch_id: i64,
+    pub transfers: Vec<RebatePayoutTransfer>,
+    // base64 encoded unsigned transaction
+    pub transaction: String,
+}
+
+impl RebatePayout {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let treasury = Pubkey::from_str(&config.rebate_treasury_address)
+            .map_err(|e| anyhow::anyhow!("invalid rebate treasury address: {e}"))?;
+        let asset = match config.rebate_payout_asset.as_str() {
+            "sol" => PayoutAsset::Sol,
+            "spl" => {
+                let mint = Pubkey::from_str(&config.token_address)
+                    .map_err(|e| anyhow::anyhow!("invalid token address: {e}"))?;
+                let tokens_per_unit = BigDecimal::from_str(&config.rebate_token_rate)
+                    .map_err(|e| anyhow::anyhow!("invalid rebate token rate: {e}"))?;
+                PayoutAsset::Spl { mint, decimals: config.token_decimal as u8, tokens_per_unit }
+            }
+            other => anyhow::bail!("rebate payout asset must be sol or spl, got {other}"),
+        };
+        Ok(Self { treasury, asset })
+    }
+
+    // Rebates are kept in launch units, rounding down to what the chain can move.
+    pub fn amount(&self, rebate: &BigDecimal) -> anyhow::Result<u64> {
+        let amount = match &self.asset {
+            PayoutAsset::Sol => (rebate.clone() * BigDecimal::from(LAMPORTS_PER_LAUNCH_UNIT)).with_scale(0),
+            PayoutAsset::Spl { decimals, tokens_per_unit, .. } =>
+                to_base_units(&(rebate.clone() * tokens_per_unit.clone()), *decimals as u32),
+        };
+        amount.to_u64().ok_or_else(|| anyhow::anyhow!("rebate payout of {rebate} overflows u64"))
+    }
+
+    // Smallest transfer worth sending. A system transfer that leaves an unfunded inviter below the rent exempt
+    // minimum fails on chain, so sol payouts wait until the inviter is owed at least that much.
+    pub fn min_amount(&self) -> u64 {
+        match &self.asset {
+            PayoutAsset::Sol => Rent::default().minimum_balance(0),
+            PayoutAsset::Spl { .. } => 1,
+        }
+    }
+
+    // The part of a rebate the transfer actually moves, in launch units, the rest stays owed.
+    pub fn paid_rebate(&self, rebate: &BigDecimal) -> anyhow::Result<BigDecimal> {
+        let amount = BigDecimal::from(self.amount(rebate)?);
+        let paid = match &self.asset {
+            PayoutAsset::Sol => amount / BigDecimal::from(LAMPORTS_PER_LAUNCH_UNIT),
+            PayoutAsset::Spl { decimals, tokens_per_unit, .. } => {
+                let unit = BigDecimal::from_str(&format!("1e{decimals}")).unwrap();
+                // round up so converting back gives the same base units
+                let paid = amount / (unit * tokens_per_unit.clone());
+                let truncated = paid.with_scale(PAID_REBATE_SCALE);
+                if truncated < paid {
+                    truncated + BigDecimal::new(1.into(), PAID_REBATE_SCALE)
+                } else {
+                    truncated
+                }
+            }
+        };
+        Ok(paid.min(rebate.clone()))
+    }
+
+    // Instructions paying one inviter, spl payouts create the token account the inviter may not have yet.
+    pub fn transfer_instructions(&self, inviter: &Pubkey, amount: u64) -> Vec<Instruction> {
+        match &self.asset {
+            PayoutAsset::Sol => vec![system_instruction::transfer(&self.treasury, inviter, amount)],
+            PayoutAsset::Spl { mint, decimals, .. } => vec![
+                create_associated_token_account_idempotent(&self.treasury, inviter, mint),
+                token_transfer_checked(&self.treasury, inviter, mint, amount, *decimals),
+            ],
+        }
+    }
+
+    pub fn inviter_instructions(&self, total: &RebateInviterTotal) -> anyhow::Result<Vec<Instruction>> {
+        let inviter = Pubkey::from_str(&total.inviter)?;
+        Ok(self.transfer_instructions(&inviter, self.amount(&to_big_decimal(&total.amount))?))
+    }
+}
+
+async fn build_rebate_transaction(rb: &RBatis, payout: &RebatePayout, batch_id: i64) -> anyhow::Result<RebatePayoutTransaction> {
+    let mut instructions = vec![];
+    let mut transfers = vec![];
+    for total in db::get_rebate_batch_totals(rb, batch_id).await? {
+        instructions.extend(payout.inviter_instructions(&total)?);
+        transfers.push(RebatePayoutTransfer {
+            inviter: total.inviter.clone(),
+            rebate_amount: total.amount.to_string(),
+            amount: payout.amount(&to_big_decimal(&total.amount))?,
+        });
+    }
+    let tx = build_unsigned_transaction(&payout.treasury, &instructions);
+    Ok(RebatePayoutTransaction {
+        batch_id,
+        transfers,
+        transaction: encode_transaction(&tx)?,
+    })
+}
+
+// Unsigned transactions of every batch still waiting for broadcast, one transaction per batch.
+pub async fn build_rebate_transactions(rb: &RBatis, payout: &RebatePayout) -> anyhow::Result<Vec<RebatePayoutTransaction>> {
+    let mut txs = vec![];
+    for batch in db::get_rebate_batches_by_status(rb, REBATE_BATCH_PENDING).await? {
+        txs.push(build_rebate_transaction(rb, payout, batch.batch_id).await?);
+    }
+    Ok(txs)
+}
+
+// Settles sent batches once the watcher sees their signature on chain, a failed transfer can be paid again.
+pub async fn confirm_rebate_payouts(rb: &mut RBatis, client: &RpcClient) -> anyhow::Result<()> {
+    let sent = db::get_rebate_batches_by_status(rb, REBATE_BATCH_SENT).await?;
+    let signatures = sent.iter()
+        .filter_map(|b| b.tx_signature.clone())
+        .collect::<Vec<_>>();
+    for (signature, state) in get_signature_states(client, &signatures).await? {
+        let Some(batch) = sent.iter().find(|b| b.tx_signature.as_deref() == Some(signature.as_str())) else {
+            continue;
+        };
+        match state {
+            SignatureState::Confirmed => {
+                log::info!("rebate payout {signature} of batch {} confirmed", batch.batch_id);
+                db::update_rebate_batch_status(rb, batch.batch_id, REBATE_BATCH_CONFIRMED, now_secs()).await?;
+            }
+            SignatureState::Failed => {
+                log::warn!("rebate payout {signature} failed on chain, batch {} goes back to pending", batch.batch_id);
+                db::update_rebate_batch_status(rb, batch.batch_id, REBATE_BATCH_PENDING, now_secs()).await?;
+            }
+            SignatureState::Unknown => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_payout_amount() {
+        let rebate = BigDecimal::from_str("0.123456789").unwrap();
+        let sol = RebatePayout { treasury: Pubkey::new_unique(), asset: PayoutAsset::Sol };
+        assert_eq!(sol.amount(&rebate).unwrap(), 12345678);
+        assert_eq!(sol.paid_rebate(&rebate).unwrap(), BigDecimal::from_str("0.12345678").unwrap());
+        assert_eq!(sol.min_amount(), 890880);
+        let spl = RebatePayout {
+            treasury: Pubkey::new_unique(),
+            asset: PayoutAsset::Spl {
+                mint: Pubkey::new_unique(),
+                decimals: 6,
+                tokens_per_unit: BigDecimal::from(1000),
+            },
+        };
+        assert_eq!(spl.amount(&rebate).unwrap(), 123456789);
+        assert_eq!(spl.paid_rebate(&rebate).unwrap(), rebate);
+        assert_eq!(spl.min_amount(), 1);
+        let thirds = RebatePayout {
+            treasury: Pubkey::new_unique(),
+            asset: PayoutAsset::Spl {
+                mint: Pubkey::new_unique(),
+                decimals: 0,
+                tokens_per_unit: BigDecimal::from(3),
+            },
+        };
+        // 1 token is a third of a launch unit, paying that part back converts to the same token
+        let paid = thirds.paid_rebate(&BigDecimal::from_str("0.5").unwrap()).unwrap();
+        assert!(paid < BigDecimal::from_str("0.5").unwrap());
+        assert_eq!(thirds.amount(&paid).unwrap(), 1);
+        assert_eq!(spl.transfer_instructions(&Pubkey::new_unique(), 1).len(), 2);
+        // a rebate too large for a transfer is an error rather than a zero payout
+        assert!(sol.amount(&BigDecimal::from_str("1e12").unwrap()).is_err());
+    }
+}
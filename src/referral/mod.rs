@@ -0,0 +1,61 @@
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, Zero};
+use rbatis::rbdc::decimal::Decimal;
+use crate::config::Config;
+
+// Rebate percentage per referral level, the first entry is paid on direct invitees.
+#[derive(Clone, Debug)]
+pub struct ReferralRates {
+    pub rates: Vec<BigDecimal>,
+}
+
+impl ReferralRates {
+    // "0.1,0.03" pays 10% on direct invitees and 3% on their invitees
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        let mut rates = vec![];
+        for rate in value.split(',').map(|r| r.trim()).filter(|r| !r.is_empty()) {
+            let rate = BigDecimal::from_str(rate)
+                .map_err(|e| anyhow::anyhow!("invalid referral rate {rate}: {e}"))?;
+            if rate < BigDecimal::zero() || rate > BigDecimal::from(1) {
+                anyhow::bail!("referral rate {rate} must be between 0 and 1");
+            }
+            rates.push(rate);
+        }
+        Ok(Self { rates })
+    }
+
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        Self::parse(&config.referral_level_rates)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.rates.len()
+    }
+
+    // Levels start at 1, levels deeper than the config earn nothing.
+    pub fn rate(&self, level: usize) -> BigDecimal {
+        level.checked_sub(1)
+            .and_then(|i| self.rates.get(i))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+pub fn to_big_decimal(amount: &Decimal) -> BigDecimal {
+    BigDecimal::from_str(&amount.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rate_per_level() {
+        let rates = ReferralRates::parse("0.1, 0.03").unwrap();
+        assert_eq!(rates.depth(), 2);
+        assert_eq!(rates.rate(1), BigDecimal::from_str("0.1").unwrap());
+        assert_eq!(rates.rate(2), BigDecimal::from_str("0.03").unwrap());
+        assert_eq!(rates.rate(3), BigDecimal::zero());
+        assert!(ReferralRates::parse("0.1,1.5").is_err());
+    }
+}
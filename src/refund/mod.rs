@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use rbatis::RBatis;
+use rbatis::rbdc::decimal::Decimal;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::{LaunchRecord, RefundRecord};
+use crate::transfer::{build_unsigned_transaction, encode_transaction, get_signature_states, pack_instruction_groups, SignatureState};
+use crate::watcher::watcher::LAMPORTS_PER_LAUNCH_UNIT;
+use crate::time::now_secs;
+
+pub const REFUND_STATUS_PENDING: &str = "pending";
+pub const REFUND_STATUS_SENT: &str = "sent";
+pub const REFUND_STATUS_CONFIRMED: &str = "confirmed";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RefundTransfer {
+    pub address: String,
+    pub lamports: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RefundTransaction {
+    pub batch_id: i64,
+    pub transfers: Vec<RefundTransfer>,
+    // base64 encoded unsigned transaction
+    pub transaction: String,
+}
+
+fn to_big_decimal(amount: &Decimal) -> BigDecimal {
+    BigDecimal::from_str(&amount.to_string()).unwrap_or_default()
+}
+
+// Walks the launch records in chain order, everything above the launch cap is owed back to the sender.
+pub fn compute_refund_ledger(records: &[LaunchRecord], launch_max_amount: u64, update_time: i64) -> anyhow::Result<Vec<RefundRecord>> {
+    let cap = BigDecimal::from(launch_max_amount);
+    let mut total = BigDecimal::zero();
+    let mut refunds = vec![];
+    for record in records {
+        let amount = to_big_decimal(&record.launch_amount);
+        let refund_amount = if total >= cap {
+            amount.clone()
+        } else if total.clone() + amount.clone() > cap {
+            total.clone() + amount.clone() - cap.clone()
+        } else {
+            BigDecimal::zero()
+        };
+        total += amount;
+        if refund_amount.is_zero() {
+            continue;
+        }
+        let refund_lamports = (refund_amount.clone() * BigDecimal::from(LAMPORTS_PER_LAUNCH_UNIT))
+            .to_i64()
+            .ok_or_else(|| anyhow::anyhow!("refund for {}:{} overflows lamports", record.launch_tx_hash, record.log_index))?;
+        refunds.push(RefundRecord {
+            launch_tx_hash: record.launch_tx_hash.clone(),
+            log_index: record.log_index,
+            address: record.address.clone(),
+            refund_amount: Decimal::from_str(&refund_amount.to_string()).unwrap(),
+            refund_lamports,
+            batch_id: None,
+            tx_signature: None,
+            status: REFUND_STATUS_PENDING.to_string(),
+            update_time,
+        });
+    }
+    Ok(refunds)
+}
+
+pub async fn sync_refund_ledger(rb: &mut RBatis, config: &Config) -> anyhow::Result<usize> {
+    // a zero cap would refund every launch in full
+    if config.launch_max_amount == 0 {
+        anyhow::bail!("launch max amount is not configured");
+    }
+    let records = db::get_all_launch_records(rb).await?;
+    let refunds = compute_refund_ledger(&records, config.launch_max_amount, now_secs())?;
+    db::save_refund_records(rb, &refunds).await?;
+    Ok(refunds.len())
+}
+
+fn group_transfers(records: &[RefundRecord]) -> BTreeMap<String, u64> {
+    let mut transfers = BTreeMap::new();
+    for record in records {
+        *transfers.entry(record.address.clone()).or_insert(0u64) += record.refund_lamports as u64;
+    }
+    transfers
+}
+
+fn build_refund_transaction(payer: &Pubkey, batch_id: i64, transfers: &BTreeMap<String, u64>) -> anyhow::Result<RefundTransaction> {
+    let mut instructions = vec![];
+    for (address, lamports) in transfers {
+        let to = Pubkey::from_str(address)?;
+        instructions.push(system_instruction::transfer(payer, &to, *lamports));
+    }
+    let tx = build_unsigned_transaction(payer, &instructions);
+    Ok(RefundTransaction {
+        batch_id,
+        transfers: transfers.iter().map(|(address, lamports)| RefundTransfer {
+            address: address.clone(),
+            lamports: *lamports,
+        }).collect(),
+        transaction: encode_transaction(&tx)?,
+    })
+}
+
+// Assigns unbatched refunds to new batches and returns every batch still waiting for broadcast.
+pub async fn build_refund_transactions(rb: &mut RBatis, config: &Config) -> anyhow::Result<Vec<RefundTransaction>> {
+    let payer = Pubkey::from_str(&config.refund_wallet_address)
+        .map_err(|e| anyhow::anyhow!("invalid refund wallet address: {e}"))?;
+
+    let unbatched = db::get_unbatched_refund_records(rb).await?;
+    let transfers = group_transfers(&unbatched);
+    let addresses = transfers.keys().cloned().collect::<Vec<_>>();
+    let mut groups = vec![];
+    for (address, lamports) in &transfers {
+        let to = Pubkey::from_str(address)?;
+        groups.push(vec![system_instruction::transfer(&payer, &to, *lamports)]);
+    }
+    for pack in pack_instruction_groups(&payer, &groups)? {
+        let batch_id = db::get_next_refund_batch_id(rb).await?;
+        let batch_addresses = pack.iter().map(|i| addresses[*i].clone()).collect::<Vec<_>>();
+        db::assign_refund_batch(rb, batch_id, &batch_addresses, now_secs()).await?;
+    }
+
+    let pending = db::get_refund_records_by_status(rb, REFUND_STATUS_PENDING).await?;
+    let mut batches: BTreeMap<i64, Vec<RefundRecord>> = BTreeMap::new();
+    for record in pending {
+        if let Some(batch_id) = record.batch_id {
+            batches.entry(batch_id).or_default().push(record);
+        }
+    }
+    let mut txs = vec![];
+    for (batch_id, records) in batches {
+        txs.push(build_refund_transaction(&payer, batch_id, &group_transfers(&records))?);
+    }
+    Ok(txs)
+}
+
+pub async fn record_refund_signature(rb: &mut RBatis, batch_id: i64, signature: &str) -> anyhow::Result<()> {
+    let updated = db::update_refund_batch_signature(rb, batch_id, signature, now_secs()).await?;
+    if updated == 0 {
+        anyhow::bail!("no pending refunds in batch {batch_id}");
+    }
+    Ok(())
+}
+
+pub async fn confirm_refund_transfers(rb: &mut RBatis, client: &RpcClient) -> anyhow::Result<()> {
+    let sent = db::get_refund_records_by_status(rb, REFUND_STATUS_SENT).await?;
+    let mut signatures = sent.iter()
+        .filter_map(|r| r.tx_signature.clone())
+        .collect::<Vec<_>>();
+    // a batch shares one signature, dedup only drops neighbours
+    signatures.sort();
+    signatures.dedup();
+    for (signature, state) in get_signature_states(client, &signatures).await? {
+        match state {
+            SignatureState::Confirmed => {
+                log::info!("refund transaction {signature} confirmed");
+                db::update_refund_status_by_signature(rb, &signature, REFUND_STATUS_CONFIRMED, now_secs()).await?;
+            }
+            SignatureState::Failed => {
+                log::warn!("refund transaction {signature} failed on chain, batch goes back to pending");
+                db::update_refund_status_by_signature(rb, &signature, REFUND_STATUS_PENDING, now_secs()).await?;
+            }
+            SignatureState::Unknown => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn launch(address: &str, amount: &str, log_index: i32) -> LaunchRecord {
+        LaunchRecord {
+            address: address.to_string(),
+            launch_amount: Decimal::from_str(amount).unwrap(),
+            launch_block: 1,
+            launch_tx_hash: format!("tx{log_index}"),
+            log_index,
+            launch_time: 1,
+        }
+    }
+
+    #[test]
+    fn test_compute_refund_ledger() {
+        let records = vec![launch("a", "60", 0), launch("b", "30", 1), launch("a", "20", 2), launch("c", "10", 3)];
+        let refunds = compute_refund_ledger(&records, 100, 7).unwrap();
+        assert_eq!(refunds.len(), 2);
+        // the launch crossing the cap is refunded the part above it, later launches in full
+        assert_eq!((refunds[0].address.as_str(), refunds[0].log_index), ("a", 2));
+        assert_eq!(refunds[0].refund_amount.to_string(), "10");
+        assert_eq!(refunds[0].refund_lamports, 10 * LAMPORTS_PER_LAUNCH_UNIT as i64);
+        assert_eq!((refunds[1].address.as_str(), refunds[1].log_index), ("c", 3));
+        assert_eq!(refunds[1].refund_amount.to_string(), "10");
+        assert!(refunds.iter().all(|r| r.status == REFUND_STATUS_PENDING && r.update_time == 7));
+        assert!(compute_refund_ledger(&records, 120, 7).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compute_refund_ledger_overflow() {
+        let records = vec![launch("a", "1", 0), launch("b", "100000000000000", 1)];
+        // a refund too large for i64 lamports is an error rather than a zero refund
+        assert!(compute_refund_ledger(&records, 1, 7).is_err());
+    }
+}
@@ -1,18 +1,26 @@
-use std::ops::Div;
 use std::str::FromStr;
-use std::time::{SystemTime, UNIX_EPOCH};
 use actix_web::{HttpRequest, HttpResponse, web};
-use bigdecimal::{BigDecimal, ToPrimitive};
+use bigdecimal::BigDecimal;
 use qstring::QString;
-use rand::{Rng, SeedableRng};
-use rand::rngs::StdRng;
 use solana_sdk::pubkey::Pubkey;
+use crate::address::EvmAddress;
+use crate::auth;
+use crate::contribution::ContributionLimits;
 use crate::db;
-use crate::db::tables::{Account, LaunchRecord, QueryAccount};
+use crate::abuse::ABUSE_FLAG_CLEARED;
+use crate::db::tables::{Account, AccountInviteeFilter, AccountSummary, BindRequest, LaunchRecord, LaunchRecordFilter, PointAccount, QueryAccount, RebateSummary};
+use crate::invite;
+use crate::pagination::next_cursor;
+use crate::points;
+use crate::rebate;
+use crate::referral::to_big_decimal;
 use crate::route::BackendResponse;
+use crate::route::auth::SessionAccount;
+use crate::route::points::{season_from_query, season_point};
 use crate::route::err::BackendError;
-use crate::route::utils::get_solana_address_from_parameter;
+use crate::route::utils::{bound_solana_address, client_ip, invalid_address_response, parse_query_param, query_cursor, query_page};
 use crate::server::AppState;
+use crate::time::now_secs;
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct NewAccountReq {
@@ -25,6 +33,12 @@ pub struct BindAccountReq {
     pub address: String,
     pub sol_address: String,
     pub inviter_code: Option<String>,
+    // nonce of the challenge from get_bind_challenge
+    pub nonce: String,
+    // base58 ed25519 signature of the challenge message by sol_address
+    pub sol_signature: String,
+    // hex EIP-191 personal_sign signature of the challenge message by address
+    pub evm_signature: String,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -32,89 +46,173 @@ pub struct BindAccountRsp {
     pub invite_code: Option<String>,
 }
 
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SetInviteCodeReq {
+    pub code: String,
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct AccountRebateRsp {
-    pub rebate: f64,
+    // everything the ledger has accrued for the account
+    pub accrued: String,
+    // paid by confirmed payout batches
+    pub paid: String,
+    // in payout batches that are not confirmed yet
+    pub in_payout: String,
+    // accrued - paid
+    pub outstanding: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct BoundAddressInfo {
+    // evm address
+    pub address: String,
+    pub claimable_amount: String,
+    pub rules_version: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClaimStatusInfo {
+    // not_eligible, claimable or claimed
+    pub status: String,
+    // merkle distribution version and amount of the claim
+    pub version: Option<i64>,
+    pub amount: Option<String>,
+    pub claimed_amount: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AccountSummaryRsp {
+    // solana address
+    pub address: String,
+    pub bound_addresses: Vec<BoundAddressInfo>,
+    // None until the solana address is bound
+    pub invite_code: Option<String>,
+    pub inviter: Option<String>,
+    // points earned in the season passed in, all time points otherwise
+    pub point: i64,
+    pub create_time: Option<i64>,
+    pub total_mint: String,
+    pub invitee_count: u64,
+    pub rebate: AccountRebateRsp,
+    // claimable amounts the bound addresses were given when they were last checked, summed up, the rules
+    // are only evaluated by /get_eligible
+    pub bound_claimable_amount: String,
+    pub claim: ClaimStatusInfo,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AccountAllowanceRsp {
+    pub total_mint: String,
+    pub min_contribution: Option<String>,
+    pub max_contribution: Option<String>,
+    pub remaining_allowance: Option<String>,
+    pub violations: usize,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct MintRecordsRsp {
     pub page_count: usize,
+    // rows matching the filters
+    pub total: u64,
     pub mint_records: Vec<MintRecordsInfo>,
+    // pass as cursor for the page after this one, stable while new mints arrive
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct MintRecordsInfo {
     pub address: String,
+    // launch units, LAMPORTS_PER_LAUNCH_UNIT lamports each
     pub amount: String,
     pub time: i64,
+    pub slot: i64,
+    // launch transaction signature
+    pub signature: String,
+}
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PointHistoryRsp {
+    pub page_count: usize,
+    pub events: Vec<PointEventInfo>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PointEventInfo {
+    pub delta: i64,
+    pub reason: String,
+    // launch signature:log index, address of the invitee or inviter, or campaign code
+    pub source: String,
+    pub time: i64,
 }
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct AccountInvitee {
     pub invitee: String,
+    pub bind_time: i64,
+    pub minted: bool,
+    pub mint_count: i64,
     pub mint_amount: String,
+    // recorded in the rebate ledger, launches since the last ledger sync and withheld rebates are not in it
     pub rebate: String,
+    pub flagged: bool,
+    // open, cleared or confirmed, none when the invitee was never flagged
+    pub flag_status: Option<String>,
 }
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct AccountInviteesRsp {
     pub page_count: usize,
+    pub total: u64,
     pub invitees: Vec<AccountInvitee>,
+    pub next_cursor: Option<String>,
 }
 
-fn generate_invite_code(seed: [u8;32]) -> String {
-    let charset: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-    let mut rng = StdRng::from_seed(seed);
-    let invite_code: String = (0..6)
-        .map(|_| {
-            let idx = rng.gen_range(0..charset.len());
-            charset[idx] as char
-        })
-        .collect();
-
-    invite_code
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferralLevelInfo {
+    pub level: i32,
+    pub rate: String,
+    pub invitees: i64,
+    pub mint_amount: String,
+    // computed from the launches with the rebate policy, ahead of the ledger and before any abuse hold
+    pub rebate: String,
 }
-
-fn is_valid_invite_code(invite_code: &str) -> bool {
-    if invite_code.len() != 6 {
-        return false;
-    }
-    if !invite_code.chars().all(|c| matches!(c, '0'..='9') | matches!(c, 'A'..='Z')) {
-        return false;
-    }
-
-    true
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferralNodeInfo {
+    pub address: String,
+    pub inviter: String,
+    pub level: i32,
+    pub mint_amount: String,
+}
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReferralTreeRsp {
+    pub levels: Vec<ReferralLevelInfo>,
+    // computed like the level rebates
+    pub total_rebate: String,
+    pub page_count: usize,
+    pub nodes: Vec<ReferralNodeInfo>,
 }
 
+// invitees are listed by mint amount, then address, both descending unless sort says otherwise
+const INVITEES_SORT: &str = "mint_desc";
+
 pub async fn bind_sol_address(
     data: web::Data<AppState>,
+    req: HttpRequest,
     msg: web::Json<BindAccountReq>,
 ) -> actix_web::Result<HttpResponse> {
     let mut rb = data.db.clone();
     if !Pubkey::from_str(&msg.sol_address).is_ok()  {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Invalid solana address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+        return Ok(invalid_address_response("Invalid solana address"));
     }
+    let Ok(evm_address) = EvmAddress::from_str(&msg.address) else {
+        return Ok(invalid_address_response("Invalid evm address"));
+    };
 
-    let mut inviter_account = None;
-    if let Some(inviter_code) = msg.inviter_code.clone() {
+    let now = now_secs();
+    let mut inviter_address = None;
+    let mut campaign_code = None;
+    let mut invite_code_used = None;
+    if let Some(inviter_code) = msg.inviter_code.as_deref().map(invite::normalize_invite_code) {
         if inviter_code != "" {
-            if is_valid_invite_code(&inviter_code) {
-                let account_by_code = db::get_account_by_inviter_code(&rb, &inviter_code).await.unwrap_or_default();
-                if account_by_code.is_none() {
-                    let resp = BackendResponse {
-                        code: BackendError::InvalidParameters,
-                        error: Some("Invite code is not exist".to_owned()),
-                        data: None::<()>
-                    };
-                    return Ok(HttpResponse::Ok().json(resp));
-                } else {
-                    inviter_account = account_by_code;
-                }
-            } else {
+            if !invite::is_valid_invite_code(&inviter_code) {
                 let resp = BackendResponse {
                     code: BackendError::InvalidParameters,
                     error: Some("Invite code is invalid".to_owned()),
@@ -122,13 +220,101 @@ pub async fn bind_sol_address(
                 };
                 return Ok(HttpResponse::Ok().json(resp));
             }
+            // campaign codes first, then the personal code of an account
+            match db::get_invite_code(&rb, &inviter_code).await {
+                Ok(Some(code)) => {
+                    if let Err(e) = invite::check_campaign_code(&code, now) {
+                        let resp = BackendResponse {
+                            code: BackendError::InvalidParameters,
+                            error: Some(e.to_string()),
+                            data: None::<()>
+                        };
+                        return Ok(HttpResponse::Ok().json(resp));
+                    }
+                    inviter_address = code.owner.clone();
+                    campaign_code = Some(code);
+                }
+                Ok(None) => {
+                    let account_by_code = db::get_account_by_inviter_code(&rb, &inviter_code).await.unwrap_or_default();
+                    if account_by_code.is_none() {
+                        let resp = BackendResponse {
+                            code: BackendError::InvalidParameters,
+                            error: Some("Invite code is not exist".to_owned()),
+                            data: None::<()>
+                        };
+                        return Ok(HttpResponse::Ok().json(resp));
+                    }
+                    inviter_address = account_by_code.map(|x| x.address);
+                }
+                Err(e) => {
+                    log::error!("get_invite_code failed {:?}",e);
+                    let resp = BackendResponse {
+                        code: BackendError::InternalErr,
+                        error: Some("get invite code failed".to_string()),
+                        data: None::<()>,
+                    };
+                    return Ok(HttpResponse::Ok().json(resp));
+                }
+            }
+            invite_code_used = Some(inviter_code);
         }
     }
-    let mut address = msg.address.to_lowercase();
-    if let Some(ret) = address.strip_prefix("0x") {
-        address = ret.to_string();
+    let address = evm_address.to_db_string();
+
+    let challenge = match db::get_bind_challenge(&rb,&msg.nonce).await {
+        Ok(Some(challenge)) => challenge,
+        Ok(None) => {
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some("Challenge is not exist".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+        Err(e) => {
+            log::error!("get_bind_challenge failed {:?}",e);
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get challenge failed".to_string()),
+                data: None::<()>,
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
     };
-    let ret =  db::get_query_account_by_address(&rb,address.clone())
+    if challenge.consumed || challenge.expire_time <= now {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Challenge is used or expired".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+    if challenge.address != address || challenge.sol_address != msg.sol_address {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Challenge is not for these addresses".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+    if !auth::verify_solana_signature(&msg.sol_address,&challenge.message,&msg.sol_signature) {
+        let resp = BackendResponse {
+            code: BackendError::Unauthorized,
+            error: Some("Invalid solana signature".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+    if !auth::verify_personal_sign(&address,&challenge.message,&msg.evm_signature) {
+        let resp = BackendResponse {
+            code: BackendError::Unauthorized,
+            error: Some("Invalid evm signature".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+
+    let ret =  db::get_query_account_by_address(&rb,&evm_address)
         .await;
     if let Err(e) = ret {
         log::error!("get_query_account_by_address failed {:?}",e);
@@ -163,40 +349,83 @@ pub async fn bind_sol_address(
     }
 
     let sol_account = ret.unwrap();
-    let (new_account,inviter,invite_code) = if sol_account.is_none() {
+    let (new_account,invite_codes,invite_code) = if sol_account.is_none() {
         let pub_key = Pubkey::from_str(&msg.sol_address).unwrap();
-        let invite_code = generate_invite_code(pub_key.to_bytes());
-        let inviter = inviter_account.map(|x| x.address);
-        let point = if inviter.is_some() {
-            1000
-        } else {
-            0
-        };
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         (Some(Account {
             address: msg.sol_address.clone(),
-            invite_code: invite_code.clone(),
-            inviter: inviter.clone(),
-            create_time: now as i64,
-            point,
-        }),inviter,Some(invite_code))
+            // picked from the candidates when the account is saved
+            invite_code: String::new(),
+            inviter: inviter_address,
+            create_time: now,
+            point: 0,
+            invite_code_used,
+        }),invite::invite_code_candidates(&pub_key),None)
     } else {
-        (None,None,Some(sol_account.unwrap().invite_code))
+        // the code only counts for new accounts
+        campaign_code = None;
+        (None,vec![],Some(sol_account.unwrap().invite_code))
     };
+    let is_new_account = new_account.is_some();
+    // saved with the account, the points engine only adds mint points later
+    let new_account = new_account.map(|account| {
+        let point_events = points::bind_events(&data.point_rules, &PointAccount {
+            address: account.address.clone(),
+            inviter: account.inviter.clone(),
+            invite_code_used: account.invite_code_used.clone(),
+            bonus_point: campaign_code.as_ref().map(|c| c.bonus_point).unwrap_or_default(),
+            create_time: account.create_time,
+        });
+        (account,point_events)
+    });
     let update_query_account = query_account.map(|_| QueryAccount {
         address: address.clone(),
         claim_sol_address: Some(msg.sol_address.clone()),
         ..Default::default()
     });
-    if let Err(e) = db::db_bind_sol_address(&mut rb,update_query_account,
-                                            new_account,inviter).await {
-        log::error!("db_bind_sol_address failed,{e}");
-        let resp = BackendResponse {
-            code: BackendError::InternalErr,
-            error: Some("Save to db failed".to_string()),
-            data: None::<()>,
+    // the challenge may expire while the signatures are checked, consuming it checks again
+    let consume_time = now_secs();
+    let invite_code = match db::db_bind_sol_address(&mut rb,&msg.nonce,consume_time,update_query_account,
+                                                    new_account,&invite_codes,
+                                                    campaign_code.map(|c| c.code)).await {
+        Ok(saved_code) => saved_code.or(invite_code),
+        Err(e) => {
+            if e.downcast_ref::<db::BindChallengeUnavailable>().is_some() {
+                let resp = BackendResponse {
+                    code: BackendError::InvalidParameters,
+                    error: Some("Challenge is used or expired".to_owned()),
+                    data: None::<()>
+                };
+                return Ok(HttpResponse::Ok().json(resp));
+            }
+            if e.downcast_ref::<db::InviteCodeUnavailable>().is_some() {
+                let resp = BackendResponse {
+                    code: BackendError::InvalidParameters,
+                    error: Some("Invite code is exhausted or expired".to_string()),
+                    data: None::<()>,
+                };
+                return Ok(HttpResponse::Ok().json(resp));
+            }
+            log::error!("db_bind_sol_address failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("Save to db failed".to_string()),
+                data: None::<()>,
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+    };
+
+    // kept for ip burst detection, a failed save does not fail the bind
+    let ip = client_ip(&req,&data.config.trusted_proxies);
+    if let (Some(ip), true) = (ip, is_new_account) {
+        let request = BindRequest {
+            address: msg.sol_address.clone(),
+            ip,
+            create_time: now,
         };
-        return Ok(HttpResponse::Ok().json(resp));
+        if let Err(e) = db::save_bind_request(&mut rb,&request).await {
+            log::warn!("save_bind_request failed,{e}");
+        }
     }
 
     let bind_account_rsp = BindAccountRsp {
@@ -211,30 +440,28 @@ pub async fn bind_sol_address(
 
 }
 
-pub async fn get_account(data: web::Data<AppState>, req: HttpRequest)
+// point is the points earned in the season passed in, all time points otherwise.
+pub async fn get_account(data: web::Data<AppState>, session: SessionAccount, req: HttpRequest)
                                           -> actix_web::Result<HttpResponse> {
     let query_str = req.query_string();
     let qs = QString::from(query_str);
-    let Some(address) = qs.get("address") else {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Not input address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
     };
-
-    let Some(address) = get_solana_address_from_parameter(address,&data.db).await else {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Need bind solana address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+    let season = match season_from_query(&data, &qs).await {
+        Ok(season) => season,
+        Err(resp) => return Ok(resp),
     };
 
     match db::get_account_by_address(&data.db,&address).await {
-        Ok(Some(account)) => {
+        Ok(Some(mut account)) => {
+            if let Some(season) = &season {
+                account.point = match season_point(&data, season, &address).await {
+                    Ok(point) => point,
+                    Err(resp) => return Ok(resp),
+                };
+            }
             let resp = BackendResponse {
                 code: BackendError::Ok,
                 error: None,
@@ -262,41 +489,146 @@ pub async fn get_account(data: web::Data<AppState>, req: HttpRequest)
     }
 }
 
-pub async fn get_account_rebate(data: web::Data<AppState>, req: HttpRequest)
-                         -> actix_web::Result<HttpResponse> {
-    let query_str = req.query_string();
-    let qs = QString::from(query_str);
-    let Some(address) = qs.get("address") else {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Not input address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+// Replaces the invite code of the signed in account with a vanity code, the old code stops working.
+pub async fn set_invite_code(data: web::Data<AppState>, session: SessionAccount, msg: web::Json<SetInviteCodeReq>)
+                             -> actix_web::Result<HttpResponse> {
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
     };
+    Ok(claim_invite_code(&data, &address, &msg.code, false).await)
+}
 
-    let Some(address) = get_solana_address_from_parameter(address,&data.db).await else {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Need bind solana address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+// Shared by users and admins, only admins may hand out reserved words.
+pub async fn claim_invite_code(data: &AppState, address: &str, code: &str, allow_reserved: bool) -> HttpResponse {
+    let code = match invite::check_vanity_code(code, allow_reserved) {
+        Ok(code) => code,
+        Err(e) => {
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some(e.to_string()),
+                data: None::<()>
+            };
+            return HttpResponse::Ok().json(resp);
+        }
     };
+    match db::get_account_by_address(&data.db,address).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some("address not exist".to_owned()),
+                data: None::<()>
+            };
+            return HttpResponse::Ok().json(resp);
+        }
+        Err(e) => {
+            log::warn!("get_account_by_address failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get account failed".to_owned()),
+                data: None::<()>
+            };
+            return HttpResponse::Ok().json(resp);
+        }
+    }
 
+    let mut rb = data.db.clone();
+    // a concurrent claim of the same code trips the unique index, it is reported as taken too
+    match db::update_invite_code(&mut rb,address,&code).await {
+        Ok(1) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(BindAccountRsp { invite_code: Some(code) })
+            };
+            HttpResponse::Ok().json(resp)
+        }
+        ret => {
+            if let Err(e) = ret {
+                log::warn!("update_invite_code failed,{e}");
+            }
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some("Invite code is taken".to_owned()),
+                data: None::<()>
+            };
+            HttpResponse::Ok().json(resp)
+        }
+    }
+}
 
-    match db::get_account_invitees_total_mint(&data.db,&address).await {
-        Ok(total_mint) => {
-            let total_mint = total_mint.0.to_f64().unwrap_or_default();
+fn rebate_rsp(summary: &RebateSummary) -> AccountRebateRsp {
+    let accrued = BigDecimal::from_str(&summary.accrued.to_string()).unwrap_or_default();
+    let paid = BigDecimal::from_str(&summary.paid.to_string()).unwrap_or_default();
+    let in_payout = BigDecimal::from_str(&summary.in_payout.to_string()).unwrap_or_default();
+    AccountRebateRsp {
+        accrued: accrued.to_string(),
+        paid: paid.to_string(),
+        in_payout: in_payout.to_string(),
+        outstanding: (accrued - paid).to_string(),
+    }
+}
+
+pub async fn get_account_rebate(data: web::Data<AppState>, session: SessionAccount)
+                         -> actix_web::Result<HttpResponse> {
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
+    };
+
+
+    match db::get_rebate_summary(&data.db,&address).await {
+        Ok(summary) => {
+            let rebate = rebate_rsp(&summary);
             let resp = BackendResponse {
                 code: BackendError::Ok,
                 error: None,
-                data: Some(total_mint * 0.1)
+                data: Some(rebate)
             };
             Ok(HttpResponse::Ok().json(resp))
         },
         Err(e) => {
-            log::warn!("get_account_invitees_total_mint failed,{e}");
+            log::warn!("get_rebate_summary failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get info failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+pub async fn get_account_allowance(data: web::Data<AppState>, session: SessionAccount)
+                         -> actix_web::Result<HttpResponse> {
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
+    };
+
+    let ret = db::get_account_total_mint(&data.db,&address).await;
+    let violations = db::get_account_violations(&data.db,&address).await;
+    match (ret,violations) {
+        (Ok(total_mint),Ok(violations)) => {
+            let limits = ContributionLimits::from_config(&data.config);
+            let total_mint = BigDecimal::from_str(&total_mint.to_string()).unwrap_or_default();
+            let allowance = AccountAllowanceRsp {
+                total_mint: total_mint.to_string(),
+                min_contribution: limits.min.as_ref().map(|v| v.to_string()),
+                max_contribution: limits.max.as_ref().map(|v| v.to_string()),
+                remaining_allowance: limits.remaining_allowance(&total_mint).map(|v| v.to_string()),
+                violations: violations.len(),
+            };
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(allowance)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        (Err(e),_) | (_,Err(e)) => {
+            log::warn!("get_account_allowance failed,{e}");
             let resp = BackendResponse {
                 code: BackendError::InternalErr,
                 error: Some("get info failed".to_owned()),
@@ -307,20 +639,147 @@ pub async fn get_account_rebate(data: web::Data<AppState>, req: HttpRequest)
     }
 }
 
+fn account_summary_rsp(address: &str, summary: &AccountSummary, point: i64) -> AccountSummaryRsp {
+    let bound_claimable_amount = summary.bound_accounts.iter()
+        .map(|a| to_big_decimal(&a.claimable_amount))
+        .fold(BigDecimal::from(0), |total, amount| total + amount);
+    let claimed_amount = summary.claimed.iter()
+        .map(|c| to_big_decimal(&c.claimed_amount))
+        .fold(BigDecimal::from(0), |total, amount| total + amount);
+    let status = if !summary.claimed.is_empty() {
+        "claimed"
+    } else if summary.claim_leaf.is_some() {
+        "claimable"
+    } else {
+        "not_eligible"
+    };
+    let account = summary.account.as_ref();
+    AccountSummaryRsp {
+        address: address.to_string(),
+        bound_addresses: summary.bound_accounts.iter().map(|a| BoundAddressInfo {
+            address: a.address.clone(),
+            claimable_amount: a.claimable_amount.to_string(),
+            rules_version: a.rules_version.clone(),
+        }).collect(),
+        invite_code: account.map(|a| a.invite_code.clone()),
+        inviter: account.and_then(|a| a.inviter.clone()),
+        point,
+        create_time: account.map(|a| a.create_time),
+        total_mint: to_big_decimal(&summary.total_mint).to_string(),
+        invitee_count: summary.invitee_count,
+        rebate: rebate_rsp(&summary.rebate),
+        bound_claimable_amount: bound_claimable_amount.to_string(),
+        claim: ClaimStatusInfo {
+            status: status.to_string(),
+            version: summary.claim_leaf.as_ref().map(|l| l.version),
+            amount: summary.claim_leaf.as_ref().map(|l| l.amount.to_string()),
+            claimed_amount: claimed_amount.to_string(),
+        },
+    }
+}
+
+pub async fn get_account_summary(data: web::Data<AppState>, session: SessionAccount, req: HttpRequest)
+                                 -> actix_web::Result<HttpResponse> {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
+    };
+    let season = match season_from_query(&data, &qs).await {
+        Ok(season) => season,
+        Err(resp) => return Ok(resp),
+    };
+
+    match db::get_account_summary(&data.db,&address).await {
+        Ok(summary) => {
+            let point = match (&season, &summary.account) {
+                (Some(season), Some(_)) => match season_point(&data, season, &address).await {
+                    Ok(point) => point,
+                    Err(resp) => return Ok(resp),
+                },
+                (_, account) => account.as_ref().map(|a| a.point).unwrap_or_default(),
+            };
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(account_summary_rsp(&address, &summary, point))
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Err(e) => {
+            log::warn!("get_account_summary failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get account summary failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+// Filters of /get_mint_records. min_amount is in launch units like the returned amount, one unit is
+// LAMPORTS_PER_LAUNCH_UNIT lamports (0.1 SOL), not in SOL.
+fn launch_record_filter(qs: &QString, address: Option<String>) -> Result<LaunchRecordFilter,HttpResponse> {
+    Ok(LaunchRecordFilter {
+        address,
+        start_time: parse_query_param(qs, "start_time")?,
+        end_time: parse_query_param(qs, "end_time")?,
+        start_slot: parse_query_param(qs, "start_slot")?,
+        end_slot: parse_query_param(qs, "end_slot")?,
+        min_amount: parse_query_param(qs, "min_amount")?,
+    })
+}
+
 pub async fn get_mint_records(data: web::Data<AppState>, req: HttpRequest)
                                 -> actix_web::Result<HttpResponse> {
     let query_str = req.query_string();
     let qs = QString::from(query_str);
-    let pg_no = qs.get("pg_no").unwrap_or("1").parse::<i32>().unwrap();
-    match db::get_launch_records(&data.db,pg_no).await {
-        Ok((page_count,records)) => {
+    let page = match query_page(&qs, 10) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    let sort = qs.get("sort").unwrap_or("time_desc");
+    if db::launch_records_sort(sort).is_none() {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Sort must be one of time_desc, time_asc, slot_desc, slot_asc, amount_desc, amount_asc".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+    let after = match query_cursor(&qs, sort) {
+        Ok(after) => after,
+        Err(resp) => return Ok(resp),
+    };
+    let address = match qs.get("address") {
+        Some(address) => match bound_solana_address(address,&data.db).await {
+            Ok(address) => Some(address),
+            Err(resp) => return Ok(resp),
+        },
+        None => None,
+    };
+    let filter = match launch_record_filter(&qs, address) {
+        Ok(filter) => filter,
+        Err(resp) => return Ok(resp),
+    };
+
+    match db::get_launch_records(&data.db,&filter,sort,&page,after.as_ref()).await {
+        Ok((total,mut records)) => {
+            let next_cursor = next_cursor(&mut records, page.page_size, sort, |r| db::launch_record_key(sort, r));
             let mint_records = records.iter().map(|r| MintRecordsInfo {
                 address: r.address.clone(),
                 amount: r.launch_amount.to_string(),
                 time: r.launch_time,
+                slot: r.launch_block,
+                signature: r.launch_tx_hash.clone(),
             }).collect::<Vec<_>>();
             let data = MintRecordsRsp {
-              page_count, mint_records
+                page_count: page.page_count(total),
+                total,
+                mint_records,
+                next_cursor,
             };
             let resp = BackendResponse {
                 code: BackendError::Ok,
@@ -341,42 +800,111 @@ pub async fn get_mint_records(data: web::Data<AppState>, req: HttpRequest)
     }
 }
 
-pub async fn get_account_invitees(data: web::Data<AppState>, req: HttpRequest)
-                              -> actix_web::Result<HttpResponse> {
+pub async fn get_point_history(data: web::Data<AppState>, session: SessionAccount, req: HttpRequest)
+                               -> actix_web::Result<HttpResponse> {
     let query_str = req.query_string();
     let qs = QString::from(query_str);
-    let pg_no = qs.get("pg_no").unwrap_or("1").parse::<i32>().unwrap();
-    let Some(address) = qs.get("address") else {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Not input address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+    let page = match query_page(&qs, 20) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    let season = match season_from_query(&data, &qs).await {
+        Ok(season) => season,
+        Err(resp) => return Ok(resp),
+    };
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
     };
 
-    let Some(address) = get_solana_address_from_parameter(address,&data.db).await else {
+    match db::get_point_history(&data.db,&address,season.as_ref(),&page).await {
+        Ok((page_count,records)) => {
+            let events = records.into_iter().map(|e| PointEventInfo {
+                delta: e.delta,
+                reason: e.reason,
+                source: e.source,
+                time: e.create_time,
+            }).collect::<Vec<_>>();
+            let data = PointHistoryRsp {
+                page_count, events
+            };
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(data)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Err(e) => {
+            log::warn!("get_point_history failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get point history failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+fn account_invitee_filter(qs: &QString) -> Result<AccountInviteeFilter,HttpResponse> {
+    Ok(AccountInviteeFilter {
+        minted: parse_query_param(qs, "minted")?,
+        flagged: parse_query_param(qs, "flagged")?,
+        start_time: parse_query_param(qs, "start_time")?,
+        end_time: parse_query_param(qs, "end_time")?,
+    })
+}
+
+pub async fn get_account_invitees(data: web::Data<AppState>, session: SessionAccount, req: HttpRequest)
+                              -> actix_web::Result<HttpResponse> {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let page = match query_page(&qs, 10) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    let sort = qs.get("sort").unwrap_or(INVITEES_SORT);
+    if db::account_invitees_sort(sort).is_none() {
         let resp = BackendResponse {
             code: BackendError::InvalidParameters,
-            error: Some("Need bind solana address".to_owned()),
+            error: Some("Sort must be one of mint_desc, mint_asc, bind_desc, bind_asc, rebate_desc, rebate_asc".to_owned()),
             data: None::<()>
         };
         return Ok(HttpResponse::Ok().json(resp));
+    }
+    let after = match query_cursor(&qs, sort) {
+        Ok(after) => after,
+        Err(resp) => return Ok(resp),
+    };
+    let filter = match account_invitee_filter(&qs) {
+        Ok(filter) => filter,
+        Err(resp) => return Ok(resp),
+    };
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
     };
 
-    match db::get_account_invitees(&data.db,&address,pg_no).await {
-        Ok((page_count,records)) => {
-            let invitees = records.iter().map(|r| {
-                let rebate = BigDecimal::from_str(&r.mint_amount.to_string()).unwrap().div(BigDecimal::from(10u32));
-                AccountInvitee {
-                    invitee: r.address.clone(),
-                    mint_amount: r.mint_amount.to_string(),
-                    rebate: rebate.to_string(),
-                }
+    match db::get_account_invitees(&data.db,&address,&filter,sort,&page,after.as_ref()).await {
+        Ok((total,mut records)) => {
+            let next_cursor = next_cursor(&mut records, page.page_size, sort,
+                                          |r| db::account_invitee_key(sort, r));
+            let invitees = records.into_iter().map(|r| AccountInvitee {
+                invitee: r.address,
+                bind_time: r.bind_time,
+                minted: r.mint_count > 0,
+                mint_count: r.mint_count,
+                mint_amount: r.mint_amount.to_string(),
+                rebate: r.rebate_amount.to_string(),
+                flagged: r.flag_status.as_ref().is_some_and(|s| s != ABUSE_FLAG_CLEARED),
+                flag_status: r.flag_status,
             }).collect::<Vec<_>>();
             let data = AccountInviteesRsp {
-                page_count,
+                page_count: page.page_count(total),
+                total,
                 invitees,
+                next_cursor,
             };
             let resp = BackendResponse {
                 code: BackendError::Ok,
@@ -397,26 +925,84 @@ pub async fn get_account_invitees(data: web::Data<AppState>, req: HttpRequest)
     }
 }
 
-pub async fn get_account_invitees_count(data: web::Data<AppState>, req: HttpRequest)
-                                  -> actix_web::Result<HttpResponse> {
+pub async fn get_referral_tree(data: web::Data<AppState>, session: SessionAccount, req: HttpRequest)
+                               -> actix_web::Result<HttpResponse> {
     let query_str = req.query_string();
     let qs = QString::from(query_str);
-    let Some(address) = qs.get("address") else {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Not input address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+    let page = match query_page(&qs, 10) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
     };
 
-    let Some(address) = get_solana_address_from_parameter(address,&data.db).await else {
-        let resp = BackendResponse {
-            code: BackendError::InvalidParameters,
-            error: Some("Need bind solana address".to_owned()),
-            data: None::<()>
-        };
-        return Ok(HttpResponse::Ok().json(resp));
+    let policy = &data.rebate_policy;
+    // the rate a launch made now would earn
+    let now = now_secs();
+    let levels = match db::get_referral_levels(&data.db,&address,policy.depth()).await {
+        Ok(levels) => levels,
+        Err(e) => {
+            log::warn!("get_referral_levels failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get referral tree failed".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+    };
+    let launches = db::get_referral_launches(&data.db,&address,policy.depth()).await;
+    let nodes = db::get_referral_nodes(&data.db,&address,policy.depth(),&levels,&page).await;
+    match (launches,nodes) {
+        (Ok(launches),Ok((page_count,nodes))) => {
+            let entries = policy.compute(&launches);
+            let rebates = rebate::rebate_by_level(&entries);
+            let tree = ReferralTreeRsp {
+                levels: levels.iter().map(|l| {
+                    let (mint_amount,rebate) = rebates.get(&l.level).cloned().unwrap_or_default();
+                    ReferralLevelInfo {
+                        level: l.level,
+                        rate: policy.effective_rate(&address,l.level as usize,now).to_string(),
+                        invitees: l.invitees,
+                        mint_amount: mint_amount.to_string(),
+                        rebate: rebate.to_string(),
+                    }
+                }).collect(),
+                total_rebate: rebate::total_rebate(&entries).to_string(),
+                page_count,
+                nodes: nodes.iter().map(|n| ReferralNodeInfo {
+                    address: n.address.clone(),
+                    inviter: n.inviter.clone(),
+                    level: n.level,
+                    mint_amount: n.mint_amount.to_string(),
+                }).collect(),
+            };
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(tree)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        (Err(e),_) | (_,Err(e)) => {
+            log::warn!("get_referral_tree failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get referral tree failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+pub async fn get_account_invitees_count(data: web::Data<AppState>, session: SessionAccount)
+                                  -> actix_web::Result<HttpResponse> {
+    let address = match bound_solana_address(&session.address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
     };
 
     match db::get_account_invitees_count(&data.db,&address).await {
@@ -508,4 +1094,71 @@ pub async fn get_total_claimed_amount(data: web::Data<AppState>, _req: HttpReque
             Ok(HttpResponse::Ok().json(resp))
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use rbatis::rbdc::decimal::Decimal;
+    use crate::db::tables::{ClaimedAccount, MerkleLeaf};
+    use super::*;
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn test_account_summary_rsp() {
+        let bound = |address: &str, amount: &str| QueryAccount {
+            address: address.to_string(),
+            claimable_amount: decimal(amount),
+            claim_sol_address: Some("sol".to_string()),
+            query_time: 0,
+            rules_version: Some("v1".to_string()),
+            breakdown: None,
+        };
+        let mut summary = AccountSummary {
+            account: Some(Account {
+                address: "sol".to_string(),
+                invite_code: "CODE".to_string(),
+                inviter: Some("inviter".to_string()),
+                create_time: 7,
+                point: 1000,
+                invite_code_used: None,
+            }),
+            bound_accounts: vec![bound("0xa", "10.5"), bound("0xb", "20")],
+            total_mint: decimal("15"),
+            invitee_count: 2,
+            rebate: RebateSummary { accrued: decimal("3"), paid: decimal("1"), in_payout: decimal("0") },
+            claim_leaf: None,
+            claimed: vec![],
+        };
+        let rsp = account_summary_rsp("sol", &summary, 40);
+        assert_eq!((rsp.invite_code.as_deref(), rsp.inviter.as_deref(), rsp.point, rsp.create_time),
+                   (Some("CODE"), Some("inviter"), 40, Some(7)));
+        assert_eq!(rsp.bound_addresses.iter().map(|a| a.address.as_str()).collect::<Vec<_>>(), vec!["0xa", "0xb"]);
+        assert_eq!(rsp.bound_claimable_amount, "30.5");
+        assert_eq!((rsp.total_mint.as_str(), rsp.rebate.outstanding.as_str()), ("15", "2"));
+        assert_eq!((rsp.claim.status.as_str(), rsp.claim.version), ("not_eligible", None));
+
+        summary.claim_leaf = Some(MerkleLeaf {
+            version: 2,
+            idx: 0,
+            claimant: "sol".to_string(),
+            amount: decimal("30"),
+            proof: String::new(),
+        });
+        let rsp = account_summary_rsp("sol", &summary, 40);
+        assert_eq!((rsp.claim.status.as_str(), rsp.claim.version, rsp.claim.amount.as_deref()), ("claimable", Some(2), Some("30")));
+
+        summary.claimed = vec![
+            ClaimedAccount { address: "0xa".to_string(), claimed_time: 1, claimed_amount: decimal("10") },
+            ClaimedAccount { address: "0xb".to_string(), claimed_time: 2, claimed_amount: decimal("20") },
+        ];
+        let rsp = account_summary_rsp("sol", &summary, 40);
+        assert_eq!((rsp.claim.status.as_str(), rsp.claim.claimed_amount.as_str()), ("claimed", "30"));
+
+        summary.account = None;
+        let rsp = account_summary_rsp("sol", &summary, 0);
+        assert!(rsp.invite_code.is_none() && rsp.create_time.is_none());
+    }
+}
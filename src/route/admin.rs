@@ -0,0 +1,424 @@
+use std::str::FromStr;
+use actix_web::{HttpRequest, HttpResponse, web};
+use qstring::QString;
+use solana_sdk::pubkey::Pubkey;
+use crate::abuse;
+use crate::config::Config;
+use crate::db;
+use crate::db::tables::{AbuseFlag, InviteCode};
+use crate::eligibility::snapshot::{import_snapshot, SnapshotFormat};
+use crate::invite;
+use crate::points::season;
+use crate::route::BackendResponse;
+use crate::route::account::claim_invite_code;
+use crate::route::auth::bearer_token;
+use crate::route::err::BackendError;
+use crate::route::utils::query_page;
+use crate::server::AppState;
+use crate::time::now_secs;
+
+pub const SNAPSHOT_UPLOAD_LIMIT: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AdminInviteCodeReq {
+    // solana address of the account
+    pub address: String,
+    pub code: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CreateInviteCodeReq {
+    // generated when not set
+    pub code: Option<String>,
+    // solana address credited as inviter
+    pub owner: Option<String>,
+    pub max_uses: Option<i32>,
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub campaign: Option<String>,
+    pub bonus_point: Option<i64>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RevokeInviteCodeReq {
+    pub code: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ReviewAbuseFlagReq {
+    pub address: String,
+    // clear or confirm
+    pub action: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AbuseFlagsRsp {
+    pub page_count: usize,
+    pub flags: Vec<AbuseFlag>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CreateSeasonReq {
+    pub name: String,
+    pub start_time: i64,
+    // exclusive
+    pub end_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct InviteCodesRsp {
+    pub page_count: usize,
+    pub invite_codes: Vec<InviteCode>,
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Admin requests carry "Authorization: Bearer <ADMIN_TOKEN>", nothing passes while the token is unset.
+pub fn is_admin(req: &HttpRequest, config: &Config) -> bool {
+    if config.admin_token.is_empty() {
+        return false;
+    }
+    bearer_token(req)
+        .map(|token| constant_time_eq(token.as_bytes(), config.admin_token.as_bytes()))
+        .unwrap_or(false)
+}
+
+pub fn unauthorized() -> HttpResponse {
+    let resp = BackendResponse {
+        code: BackendError::Unauthorized,
+        error: Some("Admin token required".to_owned()),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
+
+pub async fn upload_snapshot(data: web::Data<AppState>, req: HttpRequest, body: web::Bytes)
+                             -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let Some(format) = SnapshotFormat::from_name(qs.get("format").unwrap_or("csv")) else {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Format must be csv or json".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    };
+    let source = qs.get("source").unwrap_or("upload").to_string();
+    let Ok(content) = String::from_utf8(body.to_vec()) else {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Snapshot is not utf8".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    };
+
+    let mut db = data.db.clone();
+    match import_snapshot(&mut db, &data.eligibility_rules, &source, &content, &format).await {
+        Ok(diff) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(diff)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Err(e) => {
+            log::warn!("import_snapshot failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some(format!("Import snapshot failed: {e}")),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+pub async fn set_account_invite_code(data: web::Data<AppState>, req: HttpRequest, msg: web::Json<AdminInviteCodeReq>)
+                                     -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    Ok(claim_invite_code(&data, &msg.address, &msg.code, true).await)
+}
+
+fn invalid_parameters(error: &str) -> HttpResponse {
+    let resp = BackendResponse {
+        code: BackendError::InvalidParameters,
+        error: Some(error.to_owned()),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
+
+pub async fn create_invite_code(data: web::Data<AppState>, req: HttpRequest, msg: web::Json<CreateInviteCodeReq>)
+                                -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    let code = match &msg.code {
+        Some(code) => match invite::check_vanity_code(code, true) {
+            Ok(code) => code,
+            Err(e) => return Ok(invalid_parameters(&e.to_string())),
+        },
+        None => invite::generate_campaign_code(),
+    };
+    if let Some(owner) = &msg.owner {
+        if Pubkey::from_str(owner).is_err() {
+            return Ok(invalid_parameters("Owner must be a solana address"));
+        }
+    }
+    if msg.max_uses.is_some_and(|max| max <= 0) {
+        return Ok(invalid_parameters("Max uses must be positive"));
+    }
+    if let (Some(start), Some(end)) = (msg.start_time, msg.end_time) {
+        if end <= start {
+            return Ok(invalid_parameters("End time must be after start time"));
+        }
+    }
+    if msg.bonus_point.is_some_and(|bonus| bonus < 0) {
+        return Ok(invalid_parameters("Bonus point can not be negative"));
+    }
+
+    // invitees of the code are credited to the owner, who must have bound an account
+    if let Some(owner) = &msg.owner {
+        match db::get_account_by_address(&data.db, owner).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return Ok(invalid_parameters("Owner has no account")),
+            Err(e) => {
+                log::error!("get_account_by_address failed,{e}");
+                let resp = BackendResponse {
+                    code: BackendError::InternalErr,
+                    error: Some("Get account failed".to_owned()),
+                    data: None::<()>
+                };
+                return Ok(HttpResponse::Ok().json(resp));
+            }
+        }
+    }
+
+    let invite_code = InviteCode {
+        code,
+        owner: msg.owner.clone(),
+        max_uses: msg.max_uses,
+        used_count: 0,
+        start_time: msg.start_time,
+        end_time: msg.end_time,
+        campaign: msg.campaign.clone(),
+        bonus_point: msg.bonus_point.unwrap_or_default(),
+        revoked: false,
+        create_time: now_secs(),
+    };
+    let mut rb = data.db.clone();
+    match db::save_invite_code(&mut rb, &invite_code).await {
+        Ok(0) => Ok(invalid_parameters("Invite code is taken")),
+        Ok(_) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(invite_code)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+        Err(e) => {
+            log::error!("save_invite_code failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("Save to db failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+pub async fn list_invite_codes(data: web::Data<AppState>, req: HttpRequest)
+                               -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let page = match query_page(&qs, 20) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    match db::get_invite_codes(&data.db, qs.get("campaign"), &page).await {
+        Ok((page_count, invite_codes)) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(InviteCodesRsp { page_count, invite_codes })
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+        Err(e) => {
+            log::warn!("get_invite_codes failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get invite codes failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+// Revoked codes stay listed, accounts that already used them keep their inviter.
+pub async fn revoke_invite_code(data: web::Data<AppState>, req: HttpRequest, msg: web::Json<RevokeInviteCodeReq>)
+                                -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    let mut rb = data.db.clone();
+    match db::revoke_invite_code(&mut rb, &invite::normalize_invite_code(&msg.code)).await {
+        Ok(0) => Ok(invalid_parameters("Invite code is not exist or already revoked")),
+        Ok(_) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+        Err(e) => {
+            log::error!("revoke_invite_code failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("Save to db failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+pub async fn list_abuse_flags(data: web::Data<AppState>, req: HttpRequest)
+                              -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let page = match query_page(&qs, 20) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    match db::get_abuse_flags(&data.db, qs.get("status"), &page).await {
+        Ok((page_count, flags)) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(AbuseFlagsRsp { page_count, flags })
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+        Err(e) => {
+            log::warn!("get_abuse_flags failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get abuse flags failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+pub async fn review_abuse_flag(data: web::Data<AppState>, req: HttpRequest, msg: web::Json<ReviewAbuseFlagReq>)
+                               -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    if msg.action != "clear" && msg.action != "confirm" {
+        return Ok(invalid_parameters("Action must be clear or confirm"));
+    }
+    let mut rb = data.db.clone();
+    match abuse::review_flag(&mut rb, &msg.address, &msg.action).await {
+        Ok(false) => Ok(invalid_parameters("No open flag for the address")),
+        Ok(true) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+        Err(e) => {
+            log::error!("review_abuse_flag failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("Save to db failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+pub async fn create_season(data: web::Data<AppState>, req: HttpRequest, msg: web::Json<CreateSeasonReq>)
+                           -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    let name = msg.name.trim();
+    if name.is_empty() {
+        return Ok(invalid_parameters("Season name is required"));
+    }
+    if msg.end_time <= msg.start_time {
+        return Ok(invalid_parameters("End time must be after start time"));
+    }
+    let now = now_secs();
+    if msg.end_time <= now {
+        return Ok(invalid_parameters("Season must end in the future"));
+    }
+
+    let mut rb = data.db.clone();
+    match db::create_season(&mut rb, name, msg.start_time, msg.end_time, now).await {
+        Ok(Some(season)) => {
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(season)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+        Ok(None) => Ok(invalid_parameters("Season overlaps another season")),
+        Err(e) => {
+            log::error!("create_season failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("Save to db failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
+
+// The frozen season end snapshot as csv, for rewards distribution.
+pub async fn export_season(data: web::Data<AppState>, req: HttpRequest)
+                           -> actix_web::Result<HttpResponse> {
+    if !is_admin(&req, &data.config) {
+        return Ok(unauthorized());
+    }
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let Some(season_id) = qs.get("season").and_then(|s| s.parse::<i64>().ok()) else {
+        return Ok(invalid_parameters("Invalid season"));
+    };
+    match season::export_season_snapshot(&data.db, season_id).await {
+        Ok((_, csv)) => Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"season_{season_id}.csv\"")))
+            .body(csv)),
+        Err(e) => Ok(invalid_parameters(&e.to_string())),
+    }
+}
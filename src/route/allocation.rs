@@ -0,0 +1,62 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use qstring::QString;
+use crate::db;
+use crate::route::BackendResponse;
+use crate::route::err::BackendError;
+use crate::route::utils::bound_solana_address;
+use crate::server::AppState;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct AllocationRsp {
+    pub address: String,
+    pub contributed_amount: String,
+    pub token_amount: String,
+    pub token_decimal: u32,
+    pub pricing_rule: String,
+}
+
+pub async fn get_allocation(data: web::Data<AppState>, req: HttpRequest)
+                            -> actix_web::Result<HttpResponse> {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let Some(address) = qs.get("address") else {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Not input address".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    };
+
+    let address = match bound_solana_address(address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
+    };
+
+    match db::get_allocation_by_address(&data.db,&address).await {
+        Ok(allocation) => {
+            let allocation = allocation.map(|a| AllocationRsp {
+                address: a.address,
+                contributed_amount: a.contributed_amount.to_string(),
+                token_amount: a.token_amount.to_string(),
+                token_decimal: data.config.token_decimal,
+                pricing_rule: a.pricing_rule,
+            });
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: allocation
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Err(e) => {
+            log::warn!("get_allocation_by_address failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get allocation failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, web};
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use qstring::QString;
+use solana_sdk::pubkey::Pubkey;
+use crate::address::{AccountAddress, EvmAddress};
+use crate::auth;
+use crate::db;
+use crate::db::tables::{BindChallenge, LoginChallenge, Session};
+use crate::route::BackendResponse;
+use crate::route::err::BackendError;
+use crate::route::utils::invalid_address_response;
+use crate::server::AppState;
+use crate::time::now_secs;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct BindChallengeRsp {
+    pub nonce: String,
+    pub message: String,
+    pub expire_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LoginChallengeRsp {
+    pub nonce: String,
+    pub message: String,
+    pub expire_time: i64,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LoginReq {
+    pub nonce: String,
+    // base58 ed25519 signature for solana, hex personal_sign signature for evm
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LoginRsp {
+    pub token: String,
+    pub address: String,
+    pub expire_time: i64,
+}
+
+// Address proven by the session token in "Authorization: Bearer <token>".
+#[derive(Clone, Debug)]
+pub struct SessionAccount {
+    pub address: String,
+}
+
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+}
+
+fn session_error(error: &str) -> actix_web::Error {
+    let resp = BackendResponse {
+        code: BackendError::Unauthorized,
+        error: Some(error.to_owned()),
+        data: None::<()>
+    };
+    InternalError::from_response(error.to_owned(), HttpResponse::Ok().json(resp)).into()
+}
+
+impl FromRequest for SessionAccount {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let data = req.app_data::<web::Data<AppState>>().cloned();
+        let token_hash = bearer_token(req).map(auth::hash_session_token);
+        Box::pin(async move {
+            let (Some(data), Some(token_hash)) = (data, token_hash) else {
+                return Err(session_error("Session token required"));
+            };
+            let now = now_secs();
+            match db::get_session(&data.db, &token_hash, now).await {
+                Ok(Some(session)) => Ok(SessionAccount { address: session.address }),
+                Ok(None) => Err(session_error("Session is invalid or expired")),
+                Err(e) => {
+                    log::warn!("get_session failed,{e}");
+                    Err(session_error("get session failed"))
+                }
+            }
+        })
+    }
+}
+
+// Issues the message both wallets have to sign before bind_sol_address accepts the pair.
+pub async fn get_bind_challenge(data: web::Data<AppState>, req: HttpRequest)
+                                -> actix_web::Result<HttpResponse> {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let (Some(address), Some(sol_address)) = (qs.get("address"), qs.get("sol_address")) else {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Not input address".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    };
+
+    let Ok(address) = EvmAddress::from_str(address).map(|a| a.to_db_string()) else {
+        return Ok(invalid_address_response("Invalid evm address"));
+    };
+    if Pubkey::from_str(sol_address).is_err() {
+        return Ok(invalid_address_response("Invalid solana address"));
+    }
+
+    let now = now_secs();
+    let nonce = auth::generate_nonce();
+    let expire_time = now + data.config.bind_challenge_ttl;
+    let challenge = BindChallenge {
+        nonce: nonce.clone(),
+        address: address.clone(),
+        sol_address: sol_address.to_string(),
+        message: auth::bind_challenge_message(&data.config.auth_domain, &address, sol_address,
+                                              &nonce, expire_time),
+        expire_time,
+        consumed: false,
+        create_time: now,
+    };
+    let mut rb = data.db.clone();
+    if let Err(e) = db::save_bind_challenge(&mut rb, &challenge).await {
+        log::error!("save_bind_challenge failed,{e}");
+        let resp = BackendResponse {
+            code: BackendError::InternalErr,
+            error: Some("Save to db failed".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+
+    let resp = BackendResponse {
+        code: BackendError::Ok,
+        error: None,
+        data: Some(BindChallengeRsp {
+            nonce,
+            message: challenge.message,
+            expire_time,
+        })
+    };
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub async fn get_login_challenge(data: web::Data<AppState>, req: HttpRequest)
+                                 -> actix_web::Result<HttpResponse> {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let Some(address) = qs.get("address") else {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Not input address".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    };
+    let Ok(address) = AccountAddress::from_str(address).map(|a| a.to_db_string()) else {
+        return Ok(invalid_address_response("Invalid address"));
+    };
+
+    let now = now_secs();
+    let nonce = auth::generate_nonce();
+    let expire_time = now + data.config.login_challenge_ttl;
+    let challenge = LoginChallenge {
+        nonce: nonce.clone(),
+        address: address.clone(),
+        message: auth::login_message(&auth::SignInOrigin::from_config(&data.config), &address, &nonce, now, expire_time),
+        expire_time,
+        consumed: false,
+        create_time: now,
+    };
+    let mut rb = data.db.clone();
+    if let Err(e) = db::save_login_challenge(&mut rb, &challenge).await {
+        log::error!("save_login_challenge failed,{e}");
+        let resp = BackendResponse {
+            code: BackendError::InternalErr,
+            error: Some("Save to db failed".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+
+    let resp = BackendResponse {
+        code: BackendError::Ok,
+        error: None,
+        data: Some(LoginChallengeRsp {
+            nonce,
+            message: challenge.message,
+            expire_time,
+        })
+    };
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub async fn login(data: web::Data<AppState>, msg: web::Json<LoginReq>)
+                   -> actix_web::Result<HttpResponse> {
+    let mut rb = data.db.clone();
+    let now = now_secs();
+    let challenge = match db::consume_login_challenge(&mut rb, &msg.nonce, now).await {
+        Ok(Some(challenge)) => challenge,
+        Ok(None) => {
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some("Challenge is not exist, used or expired".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+        Err(e) => {
+            log::error!("consume_login_challenge failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get challenge failed".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+    };
+    if !auth::verify_login_signature(&challenge.address, &challenge.message, &msg.signature) {
+        let resp = BackendResponse {
+            code: BackendError::Unauthorized,
+            error: Some("Invalid signature".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+
+    let token = auth::generate_session_token();
+    let session = Session {
+        token_hash: auth::hash_session_token(&token),
+        address: challenge.address.clone(),
+        expire_time: now + data.config.session_ttl,
+        create_time: now,
+    };
+    if let Err(e) = db::save_session(&mut rb, &session).await {
+        log::error!("save_session failed,{e}");
+        let resp = BackendResponse {
+            code: BackendError::InternalErr,
+            error: Some("Save to db failed".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    }
+
+    let resp = BackendResponse {
+        code: BackendError::Ok,
+        error: None,
+        data: Some(LoginRsp {
+            token,
+            address: session.address,
+            expire_time: session.expire_time,
+        })
+    };
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub async fn logout(data: web::Data<AppState>, req: HttpRequest, _session: SessionAccount)
+                    -> actix_web::Result<HttpResponse> {
+    let mut rb = data.db.clone();
+    if let Some(token) = bearer_token(&req) {
+        if let Err(e) = db::delete_session(&mut rb, &auth::hash_session_token(token)).await {
+            log::error!("delete_session failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("Delete session failed".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+    }
+    let resp = BackendResponse {
+        code: BackendError::Ok,
+        error: None,
+        data: None::<()>
+    };
+    Ok(HttpResponse::Ok().json(resp))
+}
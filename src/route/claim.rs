@@ -0,0 +1,96 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use qstring::QString;
+use crate::db;
+use crate::route::BackendResponse;
+use crate::route::err::BackendError;
+use crate::route::utils::bound_solana_address;
+use crate::server::AppState;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClaimProofRsp {
+    pub version: i64,
+    pub root: String,
+    pub index: i64,
+    pub claimant: String,
+    pub amount: String,
+    pub proof: Vec<String>,
+}
+
+pub async fn get_claim_proof(data: web::Data<AppState>, req: HttpRequest)
+                             -> actix_web::Result<HttpResponse> {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let Some(address) = qs.get("address") else {
+        let resp = BackendResponse {
+            code: BackendError::InvalidParameters,
+            error: Some("Not input address".to_owned()),
+            data: None::<()>
+        };
+        return Ok(HttpResponse::Ok().json(resp));
+    };
+
+    let address = match bound_solana_address(address,&data.db).await {
+        Ok(address) => address,
+        Err(resp) => return Ok(resp),
+    };
+
+    let distribution = match db::get_latest_merkle_distribution(&data.db).await {
+        Ok(Some(distribution)) => distribution,
+        Ok(None) => {
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some("Claim is not ready".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+        Err(e) => {
+            log::warn!("get_latest_merkle_distribution failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get claim proof failed".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+    };
+
+    match db::get_merkle_leaf_by_claimant(&data.db,distribution.version,&address).await {
+        Ok(Some(leaf)) => {
+            let proof = leaf.proof.split(',')
+                .filter(|p| !p.is_empty())
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>();
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(ClaimProofRsp {
+                    version: distribution.version,
+                    root: distribution.root,
+                    index: leaf.idx,
+                    claimant: leaf.claimant,
+                    amount: leaf.amount.to_string(),
+                    proof,
+                })
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Ok(None) => {
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some("Address is not eligible".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+        Err(e) => {
+            log::warn!("get_merkle_leaf_by_claimant failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get claim proof failed".to_owned()),
+                data: None::<()>
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        }
+    }
+}
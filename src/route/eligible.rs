@@ -1,35 +1,24 @@
-use std::collections::HashMap;
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 use actix_web::{HttpRequest, HttpResponse, web};
 use qstring::QString;
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
-use rbatis::rbdc::decimal::Decimal;
 use crate::server::AppState;
 use serde::{Serialize, Deserialize};
 use solana_sdk::pubkey::Pubkey;
+use crate::address::AccountAddress;
 use crate::db;
-use crate::db::tables::QueryAccount;
+use crate::eligibility;
+use crate::eligibility::EligibleBreakdown;
+use crate::orbiter::OrbiterUnavailable;
 use crate::route::BackendResponse;
 use crate::route::err::BackendError;
-
-#[derive(Clone,Debug,Serialize,Deserialize)]
-pub struct EligibleResult {
-    pub count: u32,
-    pub gas: HashMap<String,String>,
-}
-#[derive(Clone,Debug,Serialize,Deserialize)]
-pub struct OrbiterEligibleResp {
-    pub code: u32,
-    pub msg: String,
-    pub result: EligibleResult,
-}
+use crate::route::utils::invalid_address_response;
 
 #[derive(Clone,Debug,Serialize,Deserialize)]
 pub struct EligibleResp {
-    // pub eth_gas_cost: String,
     pub claimable_amount: String,
+    pub rules_version: String,
+    pub breakdown: Vec<EligibleBreakdown>,
 }
 
 #[inline]
@@ -50,116 +39,73 @@ pub async fn get_eligible(data: web::Data<AppState>, req: HttpRequest)
         };
         return Ok(HttpResponse::Ok().json(resp));
     };
-    let mut address = address.to_lowercase();
-    if let Some(ret) = address.strip_prefix("0x") {
-        address = ret.to_string();
+    // solana addresses are case sensitive, only evm addresses are normalized
+    let Ok(account_address) = AccountAddress::from_str(address) else {
+        return Ok(invalid_address_response("Invalid address"));
     };
+    let address = account_address.to_db_string();
 
     let now = SystemTime::now();
     let since_epoch = now.duration_since(UNIX_EPOCH).expect("Time went backwards");
     let timestamp = since_epoch.as_secs();
-    let base_url = "https://openapi.orbiter.finance/mainnet/v1/gas";
-    let url = format!("{}?address={}", base_url, address);
-    // match reqwest::Client::new().get(url).send().await {
-    //     Ok(resp) => {
-    //         if resp.status().is_success() {
-    //             let ret = resp.text().await.unwrap();
-    //             let eligible_ret: OrbiterEligibleResp = serde_json::from_str(&ret).unwrap();
-    //             let gas_eth_cost = eligible_ret.result.gas.get("ETH");
-    //             if gas_eth_cost.is_none() {
-    //                 let resp = BackendResponse {
-    //                     code: BackendError::Ok,
-    //                     error: None,
-    //                     data: Some(EligibleResp {
-    //                         eth_gas_cost: "0".to_string(),
-    //                         claimable_amount: "0".to_string(),
-    //                     })
-    //                 };
-    //                 return Ok(HttpResponse::Ok().json(resp));
-    //             }
-    //             let tokens_number_per_gas = BigDecimal::from_str(&data.config.tokens_number_per_gas).unwrap_or_default();
-    //             let gas_eth_cost = BigDecimal::from_str(gas_eth_cost.unwrap()).unwrap_or_default();
-    //             let eligible_min_gas = BigDecimal::from_str(&data.config.eligible_min_gas).unwrap_or_default();
-    //             let claimable_amount = if gas_eth_cost.gt(&eligible_min_gas) && !tokens_number_per_gas.is_zero() {
-    //                 (gas_eth_cost.clone() / tokens_number_per_gas).to_bigint().unwrap()
-    //             } else {
-    //                 BigInt::from(0)
-    //             };
-    //
-    //             if let Err(e) = db::save_query_account(data.db.clone(), QueryAccount {
-    //                 address: address.to_string(),
-    //                 claimable_amount: Decimal::from_str(&claimable_amount.to_string()).unwrap(),
-    //                 sol_address: sol_address.to_string(),
-    //                 query_time: timestamp as i64,
-    //             }).await {
-    //                 log::warn!("save_query_account failed ,{e}")
-    //             };
-    //             let resp = BackendResponse {
-    //                 code: BackendError::Ok,
-    //                 error: None,
-    //                 data: Some(EligibleResp {
-    //                     eth_gas_cost: gas_eth_cost.to_string(),
-    //                     claimable_amount: claimable_amount.to_string(),
-    //                 })
-    //             };
-    //             Ok(HttpResponse::Ok().json(resp))
-    //         } else {
-    //             let resp = BackendResponse {
-    //                 code: BackendError::InternalErr,
-    //                 error: Some("Orbiter api return failed".to_owned()),
-    //                 data: None::<()>
-    //             };
-    //             Ok(HttpResponse::Ok().json(resp))
-    //         }
-    //
-    //     },
-    //     Err(_e) => {
-    //         let resp = BackendResponse {
-    //             code: BackendError::InternalErr,
-    //             error: Some("Orbiter api connected failed".to_owned()),
-    //             data: None::<()>
-    //         };
-    //         Ok(HttpResponse::Ok().json(resp))
-    //     }
-    // }
-    if let Ok(account) = db::get_queried_account(&data.db,&address).await {
-        let (claimable_amount,need_save) = if let Some(account) = account {
-            (account.claimable_amount,false)
-        } else {
-            let mut rng = StdRng::from_entropy();
-            let min = 10000u32;
-            let max = 40000u32;
-            let rand_amount = rng.gen_range(min..=max);
-            (Decimal::from_str(&rand_amount.to_string()).unwrap(),true)
-        };
-        if need_save {
+    let account = match db::get_queried_account(&data.db,&account_address).await {
+        Ok(account) => account,
+        Err(e) => {
+            log::warn!("get_queried_account failed ,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("Get account failed".to_owned()),
+                data: None::<()>
+            };
+            return Ok(HttpResponse::Ok().json(resp));
+        }
+    };
+    let rules = &data.eligibility_rules;
+    let eligible = match account {
+        Some(account) if account.rules_version.as_deref() == Some(rules.version.as_str()) => {
+            let breakdown = account.breakdown
+                .and_then(|b| serde_json::from_str::<Vec<EligibleBreakdown>>(&b).ok())
+                .unwrap_or_default();
+            EligibleResp {
+                claimable_amount: account.claimable_amount.to_string(),
+                rules_version: rules.version.clone(),
+                breakdown,
+            }
+        }
+        _ => {
+            let inputs = match eligibility::collect_inputs(&data.db,rules,&data.orbiter,&address).await {
+                Ok(inputs) => inputs,
+                Err(e) => {
+                    log::warn!("collect eligibility inputs failed ,{e}");
+                    let error = if e.downcast_ref::<OrbiterUnavailable>().is_some() {
+                        "Orbiter api unavailable"
+                    } else {
+                        "Get eligibility failed"
+                    };
+                    let resp = BackendResponse {
+                        code: BackendError::InternalErr,
+                        error: Some(error.to_owned()),
+                        data: None::<()>
+                    };
+                    return Ok(HttpResponse::Ok().json(resp));
+                }
+            };
+            let result = rules.evaluate(&inputs);
             let mut db = data.db.clone();
-            if let Err(e) = db::save_query_account(&mut db, QueryAccount {
-                address: address.to_string(),
-                claimable_amount: Decimal::from_str(&claimable_amount.to_string()).unwrap(),
-                claim_sol_address: None,
-                query_time: timestamp as i64,
-            }).await {
+            if let Err(e) = db::save_query_account(&mut db, result.to_query_account(&address,timestamp as i64)).await {
                 log::warn!("save_query_account failed ,{e}")
             };
+            EligibleResp {
+                claimable_amount: result.claimable_amount.to_string(),
+                rules_version: result.rules_version,
+                breakdown: result.breakdown,
+            }
         }
-        let resp = BackendResponse {
-            code: BackendError::Ok,
-            error: None,
-            data: Some(EligibleResp {
-                // eth_gas_cost: gas_eth_cost.to_string(),
-                claimable_amount: claimable_amount.to_string(),
-            })
-        };
-        Ok(HttpResponse::Ok().json(resp))
-
-    } else {
-        let resp = BackendResponse {
-            code: BackendError::InternalErr,
-            error: Some("Get account failed".to_owned()),
-            data: None::<()>
-        };
-        Ok(HttpResponse::Ok().json(resp))
-    }
-
+    };
+    let resp = BackendResponse {
+        code: BackendError::Ok,
+        error: None,
+        data: Some(eligible)
+    };
+    Ok(HttpResponse::Ok().json(resp))
 }
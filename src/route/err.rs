@@ -6,6 +6,8 @@ pub enum BackendError {
     Ok = 0,
     DbErr = 100,
     InvalidParameters = 201,
+    InvalidAddress = 202,
+    Unauthorized = 401,
     InternalErr = 500,
 }
 
@@ -21,6 +23,8 @@ impl AsRef<str> for BackendError {
             BackendError::Ok => "Ok",
             BackendError::DbErr => "Db error",
             BackendError::InvalidParameters => "Invalid request parameters",
+            BackendError::InvalidAddress => "Invalid address",
+            BackendError::Unauthorized => "Unauthorized",
             BackendError::InternalErr => "Server internal error",
         }
     }
@@ -0,0 +1,174 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use qstring::QString;
+use rbatis::RBatis;
+use crate::db;
+use crate::db::tables::{LeaderboardRank, PointRank};
+use crate::leaderboard::{BOARD_MINT, BOARD_REFERRAL};
+use crate::route::BackendResponse;
+use crate::route::err::BackendError;
+use crate::route::utils::{address_error_response, get_solana_address_from_parameter, query_page};
+use crate::server::AppState;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub address: String,
+    // points, launch amount minted, or launch amount minted by direct invitees, by board
+    pub value: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LeaderboardRsp {
+    pub page_count: usize,
+    pub ranks: Vec<LeaderboardEntry>,
+    // rank of the address passed in, None when it is not on the board
+    pub own: Option<LeaderboardEntry>,
+    // when the board was last rebuilt, season points are ranked live and frozen at the season snapshot
+    pub refresh_time: Option<i64>,
+}
+
+impl From<&LeaderboardRank> for LeaderboardEntry {
+    fn from(rank: &LeaderboardRank) -> Self {
+        Self {
+            rank: rank.rank,
+            address: rank.address.clone(),
+            value: rank.value.to_string(),
+        }
+    }
+}
+
+impl From<&PointRank> for LeaderboardEntry {
+    fn from(rank: &PointRank) -> Self {
+        Self {
+            rank: rank.rank,
+            address: rank.address.clone(),
+            value: rank.point.to_string(),
+        }
+    }
+}
+
+pub(crate) async fn leaderboard(rb: &RBatis, req: &HttpRequest, board: &str) -> HttpResponse {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let page = match query_page(&qs, 20) {
+        Ok(page) => page,
+        Err(resp) => return resp,
+    };
+    let address = match qs.get("address") {
+        Some(address) => match get_solana_address_from_parameter(address,rb).await {
+            Ok(address) => address,
+            Err(e) => return address_error_response(e),
+        },
+        None => None,
+    };
+
+    let own = match &address {
+        Some(address) => db::get_leaderboard_rank(rb, board, address).await,
+        None => Ok(None),
+    };
+    match (db::get_leaderboard_ranks(rb, board, &page).await, own) {
+        (Ok((page_count, ranks)), Ok(own)) => {
+            let refresh_time = ranks.first().or(own.as_ref()).map(|r| r.refresh_time);
+            let data = LeaderboardRsp {
+                page_count,
+                ranks: ranks.iter().map(LeaderboardEntry::from).collect(),
+                own: own.as_ref().map(LeaderboardEntry::from),
+                refresh_time,
+            };
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(data)
+            };
+            HttpResponse::Ok().json(resp)
+        },
+        (Err(e),_) | (_,Err(e)) => {
+            log::warn!("get_leaderboard_ranks {board} failed,{e}");
+            let resp = BackendResponse {
+                code: BackendError::InternalErr,
+                error: Some("get leaderboard failed".to_owned()),
+                data: None::<()>
+            };
+            HttpResponse::Ok().json(resp)
+        }
+    }
+}
+
+pub async fn get_mint_leaderboard(data: web::Data<AppState>, req: HttpRequest)
+                                  -> actix_web::Result<HttpResponse> {
+    Ok(leaderboard(&data.db, &req, BOARD_MINT).await)
+}
+
+pub async fn get_referral_leaderboard(data: web::Data<AppState>, req: HttpRequest)
+                                      -> actix_web::Result<HttpResponse> {
+    Ok(leaderboard(&data.db, &req, BOARD_REFERRAL).await)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use actix_web::body::to_bytes;
+    use actix_web::test::TestRequest;
+    use rbatis::rbdc::decimal::Decimal;
+    use solana_sdk::pubkey::Pubkey;
+    use crate::db::fixture::test_db;
+    use crate::db::tables::LaunchRecord;
+    use crate::leaderboard::refresh_leaderboards;
+    use super::*;
+
+    async fn board(rb: &RBatis, uri: &str, board: &str) -> serde_json::Value {
+        let resp = leaderboard(rb, &TestRequest::with_uri(uri).to_http_request(), board).await;
+        serde_json::from_slice(&to_bytes(resp.into_body()).await.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_entries_share_one_shape() {
+        let point = PointRank { address: "a".to_string(), point: 30, rank: 1 };
+        let launch = LeaderboardRank {
+            board: BOARD_MINT.to_string(),
+            address: "a".to_string(),
+            value: Decimal::from_str("30").unwrap(),
+            rank: 1,
+            refresh_time: 0,
+        };
+        assert_eq!(serde_json::to_value(LeaderboardEntry::from(&point)).unwrap(),
+                   serde_json::to_value(LeaderboardEntry::from(&launch)).unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_leaderboard_response() {
+        let db = test_db().await;
+        let mut rb = db.rb.clone();
+        let (a, b) = (Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string());
+        for (address, inviter) in [(&a, None), (&b, Some(&a))] {
+            rb.exec("insert into accounts (address,invite_code,inviter,create_time,point) values (?,?,?,0,0)",
+                    vec![rbs::to_value!(address), rbs::to_value!(address), rbs::to_value!(inviter)]).await.unwrap();
+        }
+        let launches = [(&b, "10", "tx1"), (&a, "5", "tx2")].map(|(address, amount, tx_hash)| LaunchRecord {
+            address: address.clone(),
+            launch_amount: Decimal::from_str(amount).unwrap(),
+            launch_block: 1,
+            launch_tx_hash: tx_hash.to_string(),
+            log_index: 0,
+            launch_time: 1,
+        });
+        db::save_launch_records(&mut rb, &launches.to_vec()).await.unwrap();
+        refresh_leaderboards(&mut rb).await.unwrap();
+
+        let rsp = board(&rb, &format!("/?page_size=1&address={a}"), BOARD_MINT).await;
+        assert_eq!(rsp["code"], "Ok");
+        let data = &rsp["data"];
+        assert_eq!(data["page_count"], 2);
+        assert_eq!(data["ranks"], serde_json::json!([{"rank": 1, "address": b, "value": "10"}]));
+        assert_eq!(data["own"], serde_json::json!({"rank": 2, "address": a, "value": "5"}));
+        assert!(data["refresh_time"].is_i64());
+
+        let rsp = board(&rb, &format!("/?address={b}"), BOARD_REFERRAL).await;
+        assert_eq!(rsp["data"]["ranks"], serde_json::json!([{"rank": 1, "address": a, "value": "10"}]));
+        assert!(rsp["data"]["own"].is_null());
+
+        assert_eq!(board(&rb, "/?address=nope", BOARD_MINT).await["code"], "InvalidAddress");
+        assert_eq!(board(&rb, "/?page_no=0", BOARD_MINT).await["code"], "InvalidParameters");
+    }
+}
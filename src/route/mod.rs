@@ -6,6 +6,12 @@ pub mod err;
 pub mod stat;
 pub mod account;
 pub mod utils;
+pub mod allocation;
+pub mod claim;
+pub mod admin;
+pub mod auth;
+pub mod points;
+pub mod leaderboard;
 
 #[derive(Debug, Serialize, Clone)]
 pub struct BackendResponse<T: Clone + Serialize> {
@@ -0,0 +1,134 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use qstring::QString;
+use crate::db;
+use crate::db::tables::Season;
+use crate::leaderboard::BOARD_POINTS;
+use crate::points::season::season_status;
+use crate::route::BackendResponse;
+use crate::route::err::BackendError;
+use crate::route::leaderboard::{leaderboard, LeaderboardEntry, LeaderboardRsp};
+use crate::route::utils::{address_error_response, get_solana_address_from_parameter, query_page};
+use crate::server::AppState;
+use crate::time::now_secs;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SeasonInfo {
+    pub season_id: i64,
+    pub name: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    // upcoming, active, ended or frozen
+    pub status: String,
+}
+
+fn error_response(code: BackendError, error: &str) -> HttpResponse {
+    let resp = BackendResponse {
+        code,
+        error: Some(error.to_owned()),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
+
+// Season picked by the "season" parameter, points are all time when it is missing.
+pub async fn season_from_query(data: &AppState, qs: &QString) -> Result<Option<Season>, HttpResponse> {
+    let Some(season_id) = qs.get("season") else {
+        return Ok(None);
+    };
+    let Ok(season_id) = season_id.parse::<i64>() else {
+        return Err(error_response(BackendError::InvalidParameters, "Invalid season"));
+    };
+    match db::get_season(&data.db, season_id).await {
+        Ok(Some(season)) => Ok(Some(season)),
+        Ok(None) => Err(error_response(BackendError::InvalidParameters, "Season not found")),
+        Err(e) => {
+            log::warn!("get_season failed,{e}");
+            Err(error_response(BackendError::InternalErr, "get season failed"))
+        }
+    }
+}
+
+// Points the address earned in the season, from the snapshot once the season is frozen.
+pub async fn season_point(data: &AppState, season: &Season, address: &str) -> Result<i64, HttpResponse> {
+    match db::get_point_rank(&data.db, season, address).await {
+        Ok(rank) => Ok(rank.map(|r| r.point).unwrap_or_default()),
+        Err(e) => {
+            log::warn!("get_point_rank failed,{e}");
+            Err(error_response(BackendError::InternalErr, "get season points failed"))
+        }
+    }
+}
+
+pub async fn get_seasons(data: web::Data<AppState>, _req: HttpRequest)
+                         -> actix_web::Result<HttpResponse> {
+    let now = now_secs();
+    match db::get_seasons(&data.db).await {
+        Ok(seasons) => {
+            let seasons = seasons.iter().map(|s| SeasonInfo {
+                season_id: s.season_id,
+                name: s.name.clone(),
+                start_time: s.start_time,
+                end_time: s.end_time,
+                status: season_status(s, now).to_string(),
+            }).collect::<Vec<_>>();
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(seasons)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        Err(e) => {
+            log::warn!("get_seasons failed,{e}");
+            Ok(error_response(BackendError::InternalErr, "get seasons failed"))
+        }
+    }
+}
+
+// All time points are read from the points board like the other boards, season points are ranked by season.
+pub async fn get_points_leaderboard(data: web::Data<AppState>, req: HttpRequest)
+                                    -> actix_web::Result<HttpResponse> {
+    let query_str = req.query_string();
+    let qs = QString::from(query_str);
+    let season = match season_from_query(&data, &qs).await {
+        Ok(Some(season)) => season,
+        Ok(None) => return Ok(leaderboard(&data.db, &req, BOARD_POINTS).await),
+        Err(resp) => return Ok(resp),
+    };
+    let page = match query_page(&qs, 20) {
+        Ok(page) => page,
+        Err(resp) => return Ok(resp),
+    };
+    let address = match qs.get("address") {
+        Some(address) => match get_solana_address_from_parameter(address,&data.db).await {
+            Ok(address) => address,
+            Err(e) => return Ok(address_error_response(e)),
+        },
+        None => None,
+    };
+
+    let own = match &address {
+        Some(address) => db::get_point_rank(&data.db, &season, address).await,
+        None => Ok(None),
+    };
+    match (db::get_point_ranks(&data.db, &season, &page).await, own) {
+        (Ok((page_count, ranks)), Ok(own)) => {
+            let data = LeaderboardRsp {
+                page_count,
+                ranks: ranks.iter().map(LeaderboardEntry::from).collect(),
+                own: own.as_ref().map(LeaderboardEntry::from),
+                refresh_time: season.snapshot_time,
+            };
+            let resp = BackendResponse {
+                code: BackendError::Ok,
+                error: None,
+                data: Some(data)
+            };
+            Ok(HttpResponse::Ok().json(resp))
+        },
+        (Err(e),_) | (_,Err(e)) => {
+            log::warn!("get_point_ranks failed,{e}");
+            Ok(error_response(BackendError::InternalErr, "get leaderboard failed"))
+        }
+    }
+}
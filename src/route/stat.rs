@@ -36,12 +36,11 @@ pub async fn get_mint_progress(data: web::Data<AppState>, _req: HttpRequest)
         }
     }
 }
+// Read from the rebate ledger, so launches since the last ledger sync are not counted yet.
 pub async fn get_total_commission(data: web::Data<AppState>, _req: HttpRequest)
                                -> actix_web::Result<HttpResponse> {
     match db::get_total_rebate(&data.db).await {
-        Ok(total_amount) => {
-            let total_rebate = BigDecimal::from_str(&total_amount.to_string())
-                .unwrap().div(BigDecimal::from(10));
+        Ok(total_rebate) => {
             let resp = BackendResponse {
                 code: BackendError::Ok,
                 error: None,
@@ -1,19 +1,138 @@
 use std::str::FromStr;
-use solana_sdk::pubkey::Pubkey;
+use actix_web::{HttpRequest, HttpResponse};
+use qstring::QString;
+use crate::address::AccountAddress;
 use crate::db;
-pub async fn get_solana_address_from_parameter(addr_str:&str,db:&rbatis::RBatis) -> Option<String> {
-    let solana_address = if let Ok(solana_key) = Pubkey::from_str(addr_str) {
-        Some(solana_key.to_string())
-    } else {
-        let mut evm_address = addr_str.to_lowercase();
-        if let Some(ret) = evm_address.strip_prefix("0x") {
-            evm_address = ret.to_string();
-        };
-        if let Ok(address) = db::get_user_bind_sol_address(db,&evm_address).await {
-            address
-        } else {
-            None
+use crate::pagination::{self, PageRequest};
+use crate::route::BackendResponse;
+use crate::route::err::BackendError;
+
+// Err(InvalidAddress) when the parameter is neither a solana nor an evm address, Err(DbErr) when the bound
+// address could not be read, Ok(None) when the evm address is not bound.
+pub async fn get_solana_address_from_parameter(addr_str:&str,db:&rbatis::RBatis) -> Result<Option<String>,BackendError> {
+    let solana_address = match AccountAddress::from_str(addr_str) {
+        Ok(AccountAddress::Solana(solana_key)) => Some(solana_key.to_string()),
+        Ok(AccountAddress::Evm(evm_address)) => db::get_user_bind_sol_address(db,&evm_address).await
+            .map_err(|e| {
+                log::warn!("get_user_bind_sol_address failed,{e}");
+                BackendError::DbErr
+            })?,
+        Err(_) => return Err(BackendError::InvalidAddress),
+    };
+    Ok(solana_address)
+}
+
+// Solana address of the parameter, Err with the response to send when it is invalid or not bound.
+pub async fn bound_solana_address(addr_str:&str,db:&rbatis::RBatis) -> Result<String,HttpResponse> {
+    match get_solana_address_from_parameter(addr_str,db).await {
+        Ok(Some(address)) => Ok(address),
+        Ok(None) => {
+            let resp = BackendResponse {
+                code: BackendError::InvalidParameters,
+                error: Some("Need bind solana address".to_owned()),
+                data: None::<()>
+            };
+            Err(HttpResponse::Ok().json(resp))
         }
+        Err(e) => Err(address_error_response(e)),
+    }
+}
+
+pub fn address_error_response(err: BackendError) -> HttpResponse {
+    if matches!(err, BackendError::InvalidAddress) {
+        return invalid_address_response("Invalid address");
+    }
+    let resp = BackendResponse {
+        code: err,
+        error: Some("Get bound address failed".to_owned()),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
+
+pub fn invalid_address_response(error: &str) -> HttpResponse {
+    let resp = BackendResponse {
+        code: BackendError::InvalidAddress,
+        error: Some(error.to_owned()),
+        data: None::<()>
     };
-    solana_address
-}
\ No newline at end of file
+    HttpResponse::Ok().json(resp)
+}
+
+// Ok(None) when the parameter is not set, Err with the response to send when it does not parse.
+pub fn parse_query_param<T: FromStr>(qs: &QString, name: &str) -> Result<Option<T>,HttpResponse> {
+    match qs.get(name) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse::<T>().map(Some).map_err(|_| invalid_parameters_response(format!("Invalid {name}"))),
+    }
+}
+
+fn invalid_parameters_response(error: String) -> HttpResponse {
+    let resp = BackendResponse {
+        code: BackendError::InvalidParameters,
+        error: Some(error),
+        data: None::<()>
+    };
+    HttpResponse::Ok().json(resp)
+}
+
+pub fn query_page(qs: &QString, default_size: i64) -> Result<PageRequest,HttpResponse> {
+    PageRequest::from_query(qs, default_size).map_err(|e| invalid_parameters_response(e.to_string()))
+}
+
+pub fn query_cursor(qs: &QString, sort: &str) -> Result<Option<Vec<String>>,HttpResponse> {
+    pagination::cursor_from_query(qs, sort).map_err(|e| invalid_parameters_response(e.to_string()))
+}
+
+// Ip of the client, X-Forwarded-For is only believed when the request comes from one of the trusted proxies.
+// The client can put anything in the header, so it is walked from the right and the first hop that is not
+// a trusted proxy is the client.
+pub fn client_ip(req: &HttpRequest, trusted_proxies: &str) -> Option<String> {
+    let trusted = |ip: &str| trusted_proxies.split(',').map(str::trim).any(|proxy| proxy == ip);
+    let mut client = req.peer_addr()?.ip().to_string();
+    if !trusted(&client) {
+        return Some(client);
+    }
+    let hops = req.headers().get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|hop| !hop.is_empty())
+        .collect::<Vec<_>>();
+    for hop in hops.into_iter().rev() {
+        client = hop.to_string();
+        if !trusted(hop) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+#[cfg(test)]
+mod test {
+    use actix_web::test::TestRequest;
+    use super::*;
+
+    #[test]
+    fn test_client_ip() {
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .insert_header(("x-forwarded-for", "1.2.3.4"))
+            .to_http_request();
+        assert_eq!(client_ip(&req, "").as_deref(), Some("10.0.0.1"));
+        assert_eq!(client_ip(&req, "10.0.0.2").as_deref(), Some("10.0.0.1"));
+        assert_eq!(client_ip(&req, "10.0.0.2, 10.0.0.1").as_deref(), Some("1.2.3.4"));
+
+        // a forged left-most entry is ignored, the hop the trusted proxies saw is the client
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .insert_header(("x-forwarded-for", "6.6.6.6, 1.2.3.4"))
+            .to_http_request();
+        assert_eq!(client_ip(&req, "10.0.0.1").as_deref(), Some("1.2.3.4"));
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .insert_header(("x-forwarded-for", "6.6.6.6, 1.2.3.4, 10.0.0.2"))
+            .to_http_request();
+        assert_eq!(client_ip(&req, "10.0.0.1,10.0.0.2").as_deref(), Some("1.2.3.4"));
+    }
+}
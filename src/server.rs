@@ -1,17 +1,32 @@
 use actix_web::{HttpServer, web};
+use std::sync::Arc;
 use std::net::SocketAddr;
 use actix_web::App;
 use std::thread;
 use actix_cors::Cors;
 use crate::config::Config;
+use crate::eligibility::EligibilityRules;
+use crate::orbiter::OrbiterClient;
+use crate::points::PointRules;
+use crate::rebate::RebatePolicy;
 use crate::route::{eligible::get_eligible,account::bind_sol_address};
-use crate::route::account::{get_account, get_account_invitees, get_account_rebate, get_mint_records,get_account_invitees_count};
+use crate::route::account::{get_account, get_account_summary, get_account_invitees, get_account_rebate, get_mint_records,get_point_history,get_account_invitees_count,get_account_allowance,get_referral_tree,set_invite_code};
 use crate::route::stat::{get_mint_progress, get_total_commission};
+use crate::route::allocation::get_allocation;
+use crate::route::claim::get_claim_proof;
+use crate::route::admin::{create_invite_code, create_season, export_season, list_abuse_flags, list_invite_codes, review_abuse_flag, revoke_invite_code, set_account_invite_code, upload_snapshot, SNAPSHOT_UPLOAD_LIMIT};
+use crate::route::auth::{get_bind_challenge, get_login_challenge, login, logout};
+use crate::route::points::{get_points_leaderboard, get_seasons};
+use crate::route::leaderboard::{get_mint_leaderboard, get_referral_leaderboard};
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
     pub db: rbatis::RBatis,
+    pub eligibility_rules: Arc<EligibilityRules>,
+    pub orbiter: Arc<OrbiterClient>,
+    pub rebate_policy: Arc<RebatePolicy>,
+    pub point_rules: Arc<PointRules>,
 }
 
 pub async fn run_server(app_state: AppState) {
@@ -36,13 +51,39 @@ pub async fn run_rpc_server(app_state: AppState) {
             .app_data(web::Data::new(app_state.clone()))
             .route("/get_eligible", web::get().to(get_eligible))
             .route("/get_account", web::get().to(get_account))
+            .route("/account_summary", web::get().to(get_account_summary))
+            .route("/auth/nonce", web::get().to(get_login_challenge))
+            .route("/auth/login", web::post().to(login))
+            .route("/auth/logout", web::post().to(logout))
+            .route("/get_bind_challenge", web::get().to(get_bind_challenge))
             .route("/bind_sol_address", web::post().to(bind_sol_address))
+            .route("/set_invite_code", web::post().to(set_invite_code))
             .route("/get_mint_records", web::get().to(get_mint_records))
+            .route("/get_point_history", web::get().to(get_point_history))
+            .route("/get_seasons", web::get().to(get_seasons))
+            .route("/get_points_leaderboard", web::get().to(get_points_leaderboard))
+            .route("/get_mint_leaderboard", web::get().to(get_mint_leaderboard))
+            .route("/get_referral_leaderboard", web::get().to(get_referral_leaderboard))
             .route("/get_account_invitees", web::get().to(get_account_invitees))
             .route("/get_account_invitees_count", web::get().to(get_account_invitees_count))
+            .route("/get_referral_tree", web::get().to(get_referral_tree))
             .route("/get_account_rebate", web::get().to(get_account_rebate))
+            .route("/get_account_allowance", web::get().to(get_account_allowance))
             .route("/get_mint_progress", web::get().to(get_mint_progress))
             .route("/get_total_commission", web::get().to(get_total_commission))
+            .route("/get_allocation", web::get().to(get_allocation))
+            .route("/claim_proof", web::get().to(get_claim_proof))
+            .service(web::resource("/admin/import_snapshot")
+                .app_data(web::PayloadConfig::new(SNAPSHOT_UPLOAD_LIMIT))
+                .route(web::post().to(upload_snapshot)))
+            .route("/admin/set_invite_code", web::post().to(set_account_invite_code))
+            .route("/admin/invite_codes", web::get().to(list_invite_codes))
+            .route("/admin/invite_codes", web::post().to(create_invite_code))
+            .route("/admin/invite_codes/revoke", web::post().to(revoke_invite_code))
+            .route("/admin/abuse_flags", web::get().to(list_abuse_flags))
+            .route("/admin/abuse_flags/review", web::post().to(review_abuse_flag))
+            .route("/admin/seasons", web::post().to(create_season))
+            .route("/admin/seasons/export", web::get().to(export_season))
     })
         .workers(works_number as usize)
         .bind(&bind_to)
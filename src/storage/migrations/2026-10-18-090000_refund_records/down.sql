@@ -0,0 +1,2 @@
+DROP SEQUENCE refund_batch_id_seq;
+DROP TABLE refund_records;
@@ -0,0 +1,15 @@
+CREATE TABLE refund_records (
+     launch_tx_hash text NOT NULL,
+     log_index smallint NOT NULL,
+     address text NOT NULL, -- solana address
+     refund_amount numeric NOT NULL,
+     refund_lamports bigint NOT NULL,
+     batch_id bigint,
+     tx_signature text,
+     status text NOT NULL, -- pending, sent, confirmed
+     update_time bigint NOT NULL,
+     PRIMARY KEY (launch_tx_hash,log_index)
+);
+
+CREATE INDEX refund_records_batch_id ON refund_records (batch_id);
+CREATE SEQUENCE refund_batch_id_seq;
@@ -0,0 +1,2 @@
+DROP INDEX launch_records_address;
+DROP TABLE launch_record_checks;
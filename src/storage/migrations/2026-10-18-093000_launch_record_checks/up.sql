@@ -0,0 +1,11 @@
+CREATE TABLE launch_record_checks (
+     launch_tx_hash text NOT NULL,
+     log_index smallint NOT NULL,
+     address text NOT NULL, -- solana address
+     cumulative_amount numeric NOT NULL,
+     violation text, -- below_min, above_max
+     PRIMARY KEY (launch_tx_hash,log_index)
+);
+
+CREATE INDEX launch_record_checks_address ON launch_record_checks (address);
+CREATE INDEX launch_records_address ON launch_records (address);
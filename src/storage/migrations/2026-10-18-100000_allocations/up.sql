@@ -0,0 +1,8 @@
+CREATE TABLE allocations (
+     address text NOT NULL, -- solana address
+     contributed_amount numeric NOT NULL,
+     token_amount numeric NOT NULL, -- token base units
+     pricing_rule text NOT NULL,
+     update_time bigint NOT NULL,
+     PRIMARY KEY (address)
+);
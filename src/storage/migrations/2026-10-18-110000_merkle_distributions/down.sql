@@ -0,0 +1,2 @@
+DROP TABLE merkle_leaves;
+DROP TABLE merkle_distributions;
@@ -0,0 +1,19 @@
+CREATE TABLE merkle_distributions (
+     version bigserial NOT NULL,
+     root text NOT NULL, -- hex
+     total_amount numeric NOT NULL, -- token base units
+     num_nodes bigint NOT NULL,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (version)
+);
+
+CREATE TABLE merkle_leaves (
+     version bigint NOT NULL,
+     idx bigint NOT NULL,
+     claimant text NOT NULL, -- solana address
+     amount numeric NOT NULL, -- token base units
+     proof text NOT NULL, -- comma separated hex hashes
+     PRIMARY KEY (version,idx)
+);
+
+CREATE INDEX merkle_leaves_claimant ON merkle_leaves (claimant,version);
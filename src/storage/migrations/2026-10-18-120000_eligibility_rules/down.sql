@@ -0,0 +1,2 @@
+ALTER TABLE query_accounts DROP COLUMN breakdown;
+ALTER TABLE query_accounts DROP COLUMN rules_version;
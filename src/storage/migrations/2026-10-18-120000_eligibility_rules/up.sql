@@ -0,0 +1,2 @@
+ALTER TABLE query_accounts ADD COLUMN rules_version text;
+ALTER TABLE query_accounts ADD COLUMN breakdown text; -- json array of EligibleBreakdown
@@ -0,0 +1,2 @@
+DROP TABLE eligibility_snapshot_entries;
+DROP TABLE eligibility_snapshots;
@@ -0,0 +1,16 @@
+CREATE TABLE eligibility_snapshots (
+     version bigserial NOT NULL,
+     source text NOT NULL,
+     row_count bigint NOT NULL,
+     total_amount numeric NOT NULL,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (version)
+);
+
+CREATE TABLE eligibility_snapshot_entries (
+     version bigint NOT NULL,
+     address text NOT NULL, -- lowercase evm address without 0x, or solana address
+     amount numeric NOT NULL,
+     reason text NOT NULL,
+     PRIMARY KEY (version,address)
+);
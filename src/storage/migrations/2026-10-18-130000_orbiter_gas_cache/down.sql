@@ -0,0 +1 @@
+DROP TABLE orbiter_gas_cache;
@@ -0,0 +1,6 @@
+CREATE TABLE orbiter_gas_cache (
+     address text NOT NULL, -- lowercase evm address without 0x
+     gas text NOT NULL, -- json map of currency to gas spent
+     fetch_time bigint NOT NULL,
+     PRIMARY KEY (address)
+);
@@ -0,0 +1 @@
+DROP TABLE bind_challenges;
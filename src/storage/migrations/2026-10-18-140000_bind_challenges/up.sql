@@ -0,0 +1,10 @@
+CREATE TABLE bind_challenges (
+     nonce text NOT NULL,
+     address text NOT NULL, -- lowercase evm address without 0x
+     sol_address text NOT NULL,
+     message text NOT NULL, -- exact text both wallets sign
+     expire_time bigint NOT NULL,
+     consumed boolean NOT NULL DEFAULT false,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (nonce)
+);
@@ -0,0 +1,18 @@
+CREATE TABLE login_challenges (
+     nonce text NOT NULL,
+     address text NOT NULL, -- solana address, or lowercase evm address without 0x
+     message text NOT NULL,
+     expire_time bigint NOT NULL,
+     consumed boolean NOT NULL DEFAULT false,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (nonce)
+);
+
+CREATE TABLE sessions (
+     token_hash text NOT NULL, -- keccak of the bearer token
+     address text NOT NULL,
+     expire_time bigint NOT NULL,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (token_hash)
+);
+CREATE INDEX sessions_expire_time_idx ON sessions (expire_time);
@@ -0,0 +1 @@
+CREATE INDEX accounts_inviter ON accounts (inviter);
@@ -0,0 +1,2 @@
+DROP TABLE rebate_entries;
+DROP TABLE rebate_batches;
@@ -0,0 +1,26 @@
+CREATE TABLE rebate_batches (
+     batch_id bigserial NOT NULL,
+     status text NOT NULL, -- pending, sent, confirmed
+     total_amount numeric NOT NULL,
+     tx_signature text,
+     create_time bigint NOT NULL,
+     update_time bigint NOT NULL,
+     PRIMARY KEY (batch_id)
+);
+
+CREATE TABLE rebate_entries (
+     launch_tx_hash text NOT NULL,
+     log_index smallint NOT NULL,
+     inviter text NOT NULL, -- solana address earning the rebate
+     address text NOT NULL, -- solana address of the launch sender
+     level integer NOT NULL,
+     launch_amount numeric NOT NULL,
+     rate numeric NOT NULL,
+     rebate_amount numeric NOT NULL,
+     batch_id bigint,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (launch_tx_hash,log_index,inviter)
+);
+
+CREATE INDEX rebate_entries_inviter ON rebate_entries (inviter);
+CREATE INDEX rebate_entries_batch_id ON rebate_entries (batch_id);
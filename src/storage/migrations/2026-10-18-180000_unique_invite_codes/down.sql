@@ -0,0 +1 @@
+DROP INDEX accounts_invite_code;
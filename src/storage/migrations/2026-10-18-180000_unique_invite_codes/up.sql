@@ -0,0 +1,10 @@
+-- Later owners of a duplicated code get a longer code, the earliest owner keeps the original.
+-- Their invitees are already stored by inviter address and stay where they are.
+UPDATE accounts a SET invite_code = a.invite_code || upper(substr(md5(a.address), 1, 4))
+FROM (
+     SELECT address, row_number() OVER (PARTITION BY invite_code ORDER BY create_time, address) AS n
+     FROM accounts
+) d
+WHERE a.address = d.address AND d.n > 1;
+
+CREATE UNIQUE INDEX accounts_invite_code ON accounts (invite_code);
@@ -0,0 +1,2 @@
+ALTER TABLE accounts DROP COLUMN invite_code_used;
+DROP TABLE invite_codes;
@@ -0,0 +1,17 @@
+CREATE TABLE invite_codes (
+     code text NOT NULL,
+     owner text, -- solana address credited as inviter, null for codes without an inviter
+     max_uses integer, -- null for unlimited
+     used_count integer NOT NULL DEFAULT 0,
+     start_time bigint,
+     end_time bigint,
+     campaign text,
+     bonus_point bigint NOT NULL DEFAULT 0, -- extra points for the account using the code
+     revoked boolean NOT NULL DEFAULT false,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (code)
+);
+
+CREATE INDEX invite_codes_campaign ON invite_codes (campaign);
+
+ALTER TABLE accounts ADD COLUMN invite_code_used text;
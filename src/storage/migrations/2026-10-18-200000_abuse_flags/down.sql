@@ -0,0 +1,3 @@
+DROP TABLE bind_requests;
+DROP TABLE funding_sources;
+DROP TABLE abuse_flags;
@@ -0,0 +1,35 @@
+CREATE TABLE abuse_flags (
+     address text NOT NULL, -- solana address of the flagged account
+     reasons text NOT NULL, -- comma separated: self_referral, shared_funder, ip_burst
+     detail text NOT NULL,
+     status text NOT NULL, -- open, cleared, confirmed
+     held_point bigint NOT NULL DEFAULT 0, -- points of the account withheld while open
+     inviter text,
+     inviter_held_point bigint NOT NULL DEFAULT 0, -- points the inviter earned through the account, withheld while open
+     create_time bigint NOT NULL,
+     review_time bigint,
+     PRIMARY KEY (address)
+);
+
+CREATE INDEX abuse_flags_status ON abuse_flags (status);
+
+-- first wallet the watcher saw paying for a launch of the account
+CREATE TABLE funding_sources (
+     address text NOT NULL,
+     funder text,
+     tx_hash text,
+     fund_time bigint,
+     resolve_time bigint NOT NULL,
+     PRIMARY KEY (address)
+);
+
+CREATE INDEX funding_sources_funder ON funding_sources (funder);
+
+CREATE TABLE bind_requests (
+     address text NOT NULL, -- solana address of the new account
+     ip text NOT NULL,
+     create_time bigint NOT NULL,
+     PRIMARY KEY (address)
+);
+
+CREATE INDEX bind_requests_ip ON bind_requests (ip, create_time);
@@ -0,0 +1,13 @@
+-- every change of a point balance, accounts.point is the sum of delta per address
+CREATE TABLE point_events (
+     event_id bigserial NOT NULL,
+     address text NOT NULL, -- solana address of the account
+     delta bigint NOT NULL,
+     reason text NOT NULL, -- mint, invitee_mint, invite, invited, campaign_bonus, withheld
+     source text NOT NULL, -- launch_tx_hash:log_index, invitee or inviter address, campaign code, reason:source for withheld
+     create_time bigint NOT NULL,
+     PRIMARY KEY (event_id)
+);
+
+CREATE INDEX point_events_address ON point_events (address, event_id);
+CREATE INDEX point_events_source ON point_events (address, reason, source);
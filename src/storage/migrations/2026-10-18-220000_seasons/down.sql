@@ -0,0 +1,3 @@
+DROP INDEX point_events_time;
+DROP TABLE season_snapshots;
+DROP TABLE seasons;
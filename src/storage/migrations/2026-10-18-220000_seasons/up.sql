@@ -0,0 +1,21 @@
+CREATE TABLE seasons (
+     season_id bigserial NOT NULL,
+     name text NOT NULL,
+     start_time bigint NOT NULL,
+     end_time bigint NOT NULL, -- exclusive
+     snapshot_time bigint, -- set when the season end snapshot is frozen
+     create_time bigint NOT NULL,
+     PRIMARY KEY (season_id)
+);
+
+-- season points per account as they stood when the season ended
+CREATE TABLE season_snapshots (
+     season_id bigint NOT NULL,
+     address text NOT NULL,
+     point bigint NOT NULL,
+     rank bigint NOT NULL,
+     PRIMARY KEY (season_id, address)
+);
+
+CREATE INDEX season_snapshots_rank ON season_snapshots (season_id, rank);
+CREATE INDEX point_events_time ON point_events (create_time);
@@ -0,0 +1,11 @@
+-- precomputed leaderboards, every board is replaced as a whole on refresh
+CREATE TABLE leaderboard_ranks (
+     board text NOT NULL, -- points, mint, referral
+     address text NOT NULL, -- solana address
+     value numeric NOT NULL,
+     rank bigint NOT NULL,
+     refresh_time bigint NOT NULL,
+     PRIMARY KEY (board, address)
+);
+
+CREATE INDEX leaderboard_ranks_rank ON leaderboard_ranks (board, rank);
@@ -0,0 +1,161 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::Message;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use solana_sdk::{pubkey, system_program};
+use std::str::FromStr;
+
+// getSignatureStatuses accepts at most 256 signatures per call
+const SIGNATURE_STATUS_CHUNK: usize = 256;
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+// spl token instruction tag of TransferChecked
+const TOKEN_TRANSFER_CHECKED: u8 = 12;
+// associated token account instruction tag of CreateIdempotent
+const ATA_CREATE_IDEMPOTENT: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignatureState {
+    Unknown,
+    Confirmed,
+    Failed,
+}
+
+// The blockhash is left as a placeholder, the signer replaces it with a recent one before signing.
+pub fn build_unsigned_transaction(payer: &Pubkey, instructions: &[Instruction]) -> Transaction {
+    let message = Message::new_with_blockhash(instructions, Some(payer), &Hash::default());
+    Transaction::new_unsigned(message)
+}
+
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],
+                                 &ASSOCIATED_TOKEN_PROGRAM_ID).0
+}
+
+// Creates the token account of the owner when it does not exist yet, a no-op otherwise.
+pub fn create_associated_token_account_idempotent(payer: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction::new_with_bytes(ASSOCIATED_TOKEN_PROGRAM_ID, &[ATA_CREATE_IDEMPOTENT], vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(associated_token_address(owner, mint), false),
+        AccountMeta::new_readonly(*owner, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(TOKEN_PROGRAM_ID, false),
+    ])
+}
+
+// Moves amount base units of the mint between the associated token accounts of from and to.
+pub fn token_transfer_checked(from: &Pubkey, to: &Pubkey, mint: &Pubkey, amount: u64, decimals: u8) -> Instruction {
+    let mut data = vec![TOKEN_TRANSFER_CHECKED];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Instruction::new_with_bytes(TOKEN_PROGRAM_ID, &data, vec![
+        AccountMeta::new(associated_token_address(from, mint), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(associated_token_address(to, mint), false),
+        AccountMeta::new_readonly(*from, true),
+    ])
+}
+
+pub fn transaction_fits(tx: &Transaction) -> bool {
+    bincode::serialized_size(tx)
+        .map(|size| size as usize <= PACKET_DATA_SIZE)
+        .unwrap_or(false)
+}
+
+// Packs instruction groups into as few transactions as possible, every group stays in a single transaction.
+// Returns the group indexes of every transaction.
+pub fn pack_instruction_groups(payer: &Pubkey, groups: &[Vec<Instruction>]) -> anyhow::Result<Vec<Vec<usize>>> {
+    let mut packs = vec![];
+    let mut current = vec![];
+    let mut instructions: Vec<Instruction> = vec![];
+    for (i, group) in groups.iter().enumerate() {
+        let mut candidate = instructions.clone();
+        candidate.extend(group.iter().cloned());
+        if transaction_fits(&build_unsigned_transaction(payer, &candidate)) {
+            instructions = candidate;
+            current.push(i);
+            continue;
+        }
+        if current.is_empty() || !transaction_fits(&build_unsigned_transaction(payer, group)) {
+            anyhow::bail!("instruction group {i} does not fit in a single transaction");
+        }
+        packs.push(std::mem::take(&mut current));
+        instructions = group.clone();
+        current.push(i);
+    }
+    if !current.is_empty() {
+        packs.push(current);
+    }
+    Ok(packs)
+}
+
+pub fn encode_transaction(tx: &Transaction) -> anyhow::Result<String> {
+    let bytes = bincode::serialize(tx)?;
+    Ok(STANDARD.encode(bytes))
+}
+
+pub async fn get_signature_states(client: &RpcClient, signatures: &[String]) -> anyhow::Result<Vec<(String, SignatureState)>> {
+    let mut states = vec![];
+    for chunk in signatures.chunks(SIGNATURE_STATUS_CHUNK) {
+        let parsed = chunk.iter()
+            .map(|s| Signature::from_str(s))
+            .collect::<Result<Vec<_>, _>>()?;
+        let statuses = client.get_signature_statuses(&parsed).await?.value;
+        for (signature, status) in chunk.iter().zip(statuses) {
+            let state = match status {
+                Some(status) if status.err.is_some() => SignatureState::Failed,
+                Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => SignatureState::Confirmed,
+                _ => SignatureState::Unknown,
+            };
+            states.push((signature.clone(), state));
+        }
+    }
+    Ok(states)
+}
+
+#[cfg(test)]
+mod test {
+    use solana_sdk::system_instruction;
+    use super::*;
+
+    #[test]
+    fn test_pack_transfers() {
+        let payer = Pubkey::new_unique();
+        let groups = (0..50)
+            .map(|i| vec![system_instruction::transfer(&payer, &Pubkey::new_unique(), 1000 + i)])
+            .collect::<Vec<_>>();
+        let packs = pack_instruction_groups(&payer, &groups).unwrap();
+        assert!(packs.len() > 1);
+        let packed: Vec<usize> = packs.iter().flatten().cloned().collect();
+        assert_eq!(packed, (0..50).collect::<Vec<_>>());
+        for pack in packs {
+            let instructions = pack.iter().flat_map(|i| groups[*i].clone()).collect::<Vec<_>>();
+            let tx = build_unsigned_transaction(&payer, &instructions);
+            assert!(transaction_fits(&tx));
+            assert!(!encode_transaction(&tx).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_token_transfer_checked() {
+        let (from, to, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = token_transfer_checked(&from, &to, &mint, 1500, 6);
+        assert_eq!(ix.program_id, TOKEN_PROGRAM_ID);
+        assert_eq!(ix.data, [vec![12], 1500u64.to_le_bytes().to_vec(), vec![6]].concat());
+        assert_eq!(ix.accounts[0].pubkey, associated_token_address(&from, &mint));
+        assert_eq!(ix.accounts[2].pubkey, associated_token_address(&to, &mint));
+        assert!(ix.accounts[3].is_signer);
+        let create = create_associated_token_account_idempotent(&from, &to, &mint);
+        assert_eq!(create.accounts[1].pubkey, ix.accounts[2].pubkey);
+    }
+}
@@ -22,9 +22,19 @@ use crate::config::Config;
 use crate::db;
 use solana_transaction_status::{EncodedTransactionWithStatusMeta, TransactionDetails, UiConfirmedBlock, UiTransactionEncoding};
 use solana_transaction_status::UiInstruction::Compiled;
-use crate::db::tables::LaunchRecord;
+use crate::db::tables::{FundingSource, LaunchRecord};
 use rayon::iter::ParallelIterator;
 use tokio::sync::Mutex as TokioMutex;
+use crate::refund;
+use crate::abuse;
+use crate::leaderboard;
+use crate::points::{self, PointRules};
+use crate::rebate::{self, RebatePolicy};
+use crate::contribution::{self, ContributionLimits};
+use crate::time::now_secs;
+
+// launch amounts are stored as lamports / LAMPORTS_PER_LAUNCH_UNIT
+pub const LAMPORTS_PER_LAUNCH_UNIT: u64 = 100000000;
 
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, PartialEq)]
 pub struct Mint {
@@ -38,11 +48,13 @@ pub struct ChainWatcher {
     pub db: rbatis::RBatis,
     pub blocks_queue: Arc<TokioMutex<VecDeque<UiConfirmedBlock>>>,
 }
+// Launch records of the block, with the wallet that paid for every launch a minter did not pay for itself.
 fn parse_transfer_logs(transactions:Vec<EncodedTransactionWithStatusMeta>,
                        slot: i64,
                        block_time: i64,
-) ->Vec<LaunchRecord> {
+) ->(Vec<LaunchRecord>,Vec<FundingSource>) {
     let records = Arc::new(Mutex::new(vec![]));
+    let funding_sources = Arc::new(Mutex::new(vec![]));
     transactions.par_iter().for_each(|tx| {
         let Some(decoded_tx) = tx.transaction.decode() else {
             return;
@@ -84,9 +96,20 @@ fn parse_transfer_logs(transactions:Vec<EncodedTransactionWithStatusMeta>,
                         let amount_str = mint_metas[1].split('=').collect::<Vec<_>>()[1].trim();
                         let sol_amount = BigDecimal::from_str(amount_str)
                             .unwrap_or(BigDecimal::from(0))
-                            .div(BigDecimal::from(100000000));
+                            .div(BigDecimal::from(LAMPORTS_PER_LAUNCH_UNIT));
                         log::info!("Get mint event from {:?} buy {:?} lamport at slot {} tx {}",
                                                  account_from,sol_amount, slot, decoded_tx.signatures[0].to_string());
+                        let funder = Pubkey::from_str(account_from).ok()
+                            .and_then(|minter| abuse::launch_funder(&decoded_tx, &minter));
+                        if let Some(funder) = funder {
+                            funding_sources.lock().unwrap().push(FundingSource {
+                                address: account_from.to_string(),
+                                funder: Some(funder.to_string()),
+                                tx_hash: Some(decoded_tx.signatures[0].to_string()),
+                                fund_time: Some(block_time),
+                                resolve_time: now_secs(),
+                            });
+                        }
                         records.lock().unwrap().push(LaunchRecord {
                             address: account_from.to_string(),
                             launch_amount: Decimal::from_str(&sol_amount.to_string()).unwrap(),
@@ -102,7 +125,15 @@ fn parse_transfer_logs(transactions:Vec<EncodedTransactionWithStatusMeta>,
         }
     });
     let records = records.lock().unwrap().to_vec();
-    records
+    let funding_sources = funding_sources.lock().unwrap().to_vec();
+    (records,funding_sources)
+}
+async fn save_launch_records(rb: &mut rbatis::RBatis, config: &Config, records: &Vec<LaunchRecord>) -> anyhow::Result<()> {
+    let limits = ContributionLimits::from_config(config);
+    let checks = contribution::check_launch_records(rb, &limits, records).await?;
+    db::save_launch_records(rb, records).await?;
+    db::save_launch_record_checks(rb, &checks).await?;
+    Ok(())
 }
 impl ChainWatcher {
     pub fn new(config:Config,db: rbatis::RBatis) -> Self {
@@ -183,7 +214,7 @@ impl ChainWatcher {
                                             continue;
                                         };
 
-                                        let sol_amount = BigDecimal::from(lamports).div(BigDecimal::from(100000000));
+                                        let sol_amount = BigDecimal::from(lamports).div(BigDecimal::from(LAMPORTS_PER_LAUNCH_UNIT));
                                         log::info!("there is sol transfer {:?} from {:?} to {:?} at slot {} tx {}",
                                                  sol_amount, account_from, account_to, slot, decoded_tx.signatures[0].to_string());
                                         records.push(LaunchRecord {
@@ -205,7 +236,7 @@ impl ChainWatcher {
                     }
                 }
             }
-            db::save_launch_records(&mut self.db,&records).await?;
+            save_launch_records(&mut self.db, &self.config, &records).await?;
             start_block = end_block + 1;
             db::upsert_last_sync_block(
                 &mut self.db,
@@ -254,12 +285,13 @@ impl ChainWatcher {
                 let Some(transactions) = block.transactions else {
                     continue;
                 };
-                let records = parse_transfer_logs(transactions,
+                let (records,funding_sources) = parse_transfer_logs(transactions,
                                                        block.block_height.unwrap_or_default() as i64,
                                                        block.block_time.unwrap_or_default());
                 if !records.is_empty() {
                     log::info!("get mint records in block {:?}",block.block_height);
-                    db::save_launch_records(&mut self.db, &records).await?;
+                    save_launch_records(&mut self.db, &self.config, &records).await?;
+                    db::save_funding_sources(&mut self.db, &funding_sources).await?;
                 }
             } else {
                 log::info!("no block need to process");
@@ -281,11 +313,94 @@ impl ChainWatcher {
 
         }
     }
+
+    pub async fn run_rebate_ledger_server(mut self) {
+        let policy = match RebatePolicy::load(&self.config) {
+            Ok(policy) => policy,
+            Err(e) => {
+                log::error!("load rebate policy failed, rebate ledger is not synced {:?}", e);
+                return;
+            }
+        };
+        let mut sync_poll = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            sync_poll.tick().await;
+            match rebate::ledger::sync_rebate_ledger(&mut self.db, &policy).await {
+                Ok(0) => {}
+                Ok(count) => log::info!("{count} rebate entries accrued"),
+                Err(e) => log::error!("sync_rebate_ledger error occurred {:?}", e),
+            }
+        }
+    }
+
+    pub async fn run_abuse_detection_server(mut self) {
+        let policy = match RebatePolicy::load(&self.config) {
+            Ok(policy) => policy,
+            Err(e) => {
+                log::error!("load rebate policy failed, abuse detection is not run {:?}", e);
+                return;
+            }
+        };
+        let mut detect_poll = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            detect_poll.tick().await;
+            match abuse::flag_abuse(&mut self.db, &self.config, policy.depth()).await {
+                Ok(0) => {}
+                Ok(count) => log::warn!("{count} accounts flagged for referral abuse"),
+                Err(e) => log::error!("flag_abuse error occurred {:?}", e),
+            }
+        }
+    }
+
+    pub async fn run_points_server(mut self) {
+        let rules = match PointRules::from_config(&self.config) {
+            Ok(rules) => rules,
+            Err(e) => {
+                log::error!("load point rules failed, points are not synced {:?}", e);
+                return;
+            }
+        };
+        let mut sync_poll = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            sync_poll.tick().await;
+            if let Err(e) = points::sync_points(&mut self.db, &rules).await {
+                log::error!("sync_points error occurred {:?}", e);
+            }
+        }
+    }
+
+    pub async fn run_leaderboard_server(mut self) {
+        let mut refresh_poll = tokio::time::interval(Duration::from_secs(self.config.leaderboard_refresh_interval.max(1)));
+        loop {
+            refresh_poll.tick().await;
+            if let Err(e) = leaderboard::refresh_leaderboards(&mut self.db).await {
+                log::error!("refresh_leaderboards error occurred {:?}", e);
+            }
+        }
+    }
+
+    pub async fn run_refund_confirm_server(mut self) {
+        let mut confirm_poll = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            confirm_poll.tick().await;
+            if let Err(e) = refund::confirm_refund_transfers(&mut self.db, &self.client).await {
+                log::error!("confirm_refund_transfers error occurred {:?}", e);
+            }
+            if let Err(e) = rebate::payout::confirm_rebate_payouts(&mut self.db, &self.client).await {
+                log::error!("confirm_rebate_payouts error occurred {:?}", e);
+            }
+        }
+    }
 }
 pub async fn run_watcher(config: Config, db: rbatis::RBatis) -> JoinHandle<()> {
     log::info!("Starting watcher!");
     let mut watcher = ChainWatcher::new(config, db);
     tokio::spawn(watcher.clone().run_sync_transfers_logs());
+    tokio::spawn(watcher.clone().run_refund_confirm_server());
+    tokio::spawn(watcher.clone().run_rebate_ledger_server());
+    tokio::spawn(watcher.clone().run_abuse_detection_server());
+    tokio::spawn(watcher.clone().run_points_server());
+    tokio::spawn(watcher.clone().run_leaderboard_server());
     tokio::spawn(watcher.run_get_blocks_server())
 }
 